@@ -0,0 +1,138 @@
+/*!
+Administrative endpoints, served alongside the mocked routes of every server under `ADMIN_PATH`.
+
+- `GET    /__mockerize/health`: respond with `{"status": "ok"}` once the server is accepting requests
+- `GET    /__mockerize/state`: dump the entire state store as a JSON object
+- `DELETE /__mockerize/state`: reset the state store
+- `GET    /__mockerize/state/{key}`: fetch a single value, or 404 if not set
+- `PUT    /__mockerize/state/{key}`: store the request body (JSON, or otherwise a string) under `key`
+- `DELETE /__mockerize/state/{key}`: remove a single key
+- `GET    /__mockerize/scenarios`: list the current state of every scenario
+- `DELETE /__mockerize/scenarios`: return every scenario to its initial state
+- `PUT    /__mockerize/scenarios/{name}`: set a scenario's state, given a body of `{"state": "..."}`
+- `DELETE /__mockerize/scenarios/{name}`: return a single scenario to its initial state
+- `DELETE /__mockerize/resources`: return every resource to its seed data
+- `DELETE /__mockerize/resources/{name}`: return a single resource to its seed data
+*/
+
+use actix_web::web::{self, Bytes};
+use actix_web::HttpResponse;
+use serde::Deserialize;
+use serde_json::{json, Value};
+use tracing::info;
+
+use crate::resource::ResourceStore;
+use crate::state::{ScenarioStore, StateStore};
+
+pub const ADMIN_PATH: &str = "/__mockerize";
+/// Path of the health endpoint, relative to `ADMIN_PATH`
+pub const HEALTH_PATH: &str = "/health";
+
+/// Register all admin endpoints onto an Actix app
+pub fn configure(cfg: &mut web::ServiceConfig) {
+    cfg.service(
+        web::scope(ADMIN_PATH)
+            .route(HEALTH_PATH, web::get().to(health))
+            .route("/state", web::get().to(get_state))
+            .route("/state", web::delete().to(reset_state))
+            .route("/state/{key:.+}", web::get().to(get_state_key))
+            .route("/state/{key:.+}", web::put().to(put_state_key))
+            .route("/state/{key:.+}", web::delete().to(delete_state_key))
+            .route("/scenarios", web::get().to(get_scenarios))
+            .route("/scenarios", web::delete().to(reset_scenarios))
+            .route("/scenarios/{name}", web::put().to(set_scenario))
+            .route("/scenarios/{name}", web::delete().to(reset_scenario))
+            .route("/resources", web::delete().to(reset_resources))
+            .route("/resources/{name}", web::delete().to(reset_resource)),
+    );
+}
+
+async fn health() -> HttpResponse {
+    HttpResponse::Ok().json(json!({ "status": "ok" }))
+}
+
+async fn get_state(state: web::Data<StateStore>) -> HttpResponse {
+    HttpResponse::Ok().json(&*state.read())
+}
+
+async fn reset_state(state: web::Data<StateStore>) -> HttpResponse {
+    info!("Resetting state store");
+    state.reset();
+    HttpResponse::NoContent().finish()
+}
+
+async fn get_state_key(state: web::Data<StateStore>, key: web::Path<String>) -> HttpResponse {
+    match state.read().get(key.as_str()) {
+        Some(value) => HttpResponse::Ok().json(value),
+        None => HttpResponse::NotFound().finish(),
+    }
+}
+
+async fn put_state_key(
+    state: web::Data<StateStore>,
+    key: web::Path<String>,
+    body: Bytes,
+) -> HttpResponse {
+    let body = String::from_utf8_lossy(&body).into_owned();
+    let value = serde_json::from_str(&body).unwrap_or(Value::String(body));
+    state.write().insert(key.into_inner(), value);
+    HttpResponse::NoContent().finish()
+}
+
+async fn delete_state_key(state: web::Data<StateStore>, key: web::Path<String>) -> HttpResponse {
+    state.write().remove(key.as_str());
+    HttpResponse::NoContent().finish()
+}
+
+async fn get_scenarios(scenarios: web::Data<ScenarioStore>) -> HttpResponse {
+    HttpResponse::Ok().json(scenarios.snapshot())
+}
+
+async fn reset_scenarios(scenarios: web::Data<ScenarioStore>) -> HttpResponse {
+    info!("Resetting all scenarios");
+    scenarios.reset();
+    HttpResponse::NoContent().finish()
+}
+
+#[derive(Deserialize)]
+struct ScenarioState {
+    state: String,
+}
+
+async fn set_scenario(
+    scenarios: web::Data<ScenarioStore>,
+    name: web::Path<String>,
+    body: web::Json<ScenarioState>,
+) -> HttpResponse {
+    info!("Setting scenario `{}` to state `{}`", name, body.state);
+    scenarios.set(&name, &body.state);
+    HttpResponse::NoContent().finish()
+}
+
+async fn reset_scenario(
+    scenarios: web::Data<ScenarioStore>,
+    name: web::Path<String>,
+) -> HttpResponse {
+    info!("Resetting scenario `{}`", name);
+    scenarios.reset_scenario(&name);
+    HttpResponse::NoContent().finish()
+}
+
+async fn reset_resources(resources: web::Data<ResourceStore>) -> HttpResponse {
+    info!("Resetting all resources to seed data");
+    resources.reset();
+    HttpResponse::NoContent().finish()
+}
+
+async fn reset_resource(
+    resources: web::Data<ResourceStore>,
+    name: web::Path<String>,
+) -> HttpResponse {
+    match resources.reset_resource(&name) {
+        Ok(_) => {
+            info!("Resetting resource `{}` to seed data", name);
+            HttpResponse::NoContent().finish()
+        }
+        Err(_) => HttpResponse::NotFound().finish(),
+    }
+}
@@ -1,7 +1,11 @@
 use clap::{Parser, Subcommand};
 use std::io;
+use std::time::Duration;
 
-use super::{NewCommand, RunCommand, TestCommand};
+use super::{
+    HeaderCommand, NewCommand, ResponseCommand, RestartCommand, RouteCommand, RunCommand,
+    StatusCommand, StopCommand, TestCommand, UpCommand, WaitCommand,
+};
 
 #[derive(Parser, Debug)]
 #[command(name = "mockerize-cli")]
@@ -16,6 +20,14 @@ pub enum Commands {
     New(NewCommand),
     Run(RunCommand),
     Test(TestCommand),
+    Up(UpCommand),
+    Stop(StopCommand),
+    Status(StatusCommand),
+    Restart(RestartCommand),
+    Wait(WaitCommand),
+    Route(RouteCommand),
+    Response(ResponseCommand),
+    Header(HeaderCommand),
 }
 
 /// Require that a user confirm an action. They *must* enter yes/y or no/n
@@ -36,3 +48,40 @@ pub fn prompt_for_confirmation() -> bool {
         }
     }
 }
+
+/// Parse a duration given on the command line, such as `30s`, `500ms` or `2m`. Plain numbers are seconds.
+pub fn parse_duration(input: &str) -> Result<Duration, String> {
+    let input = input.trim();
+    let split = input
+        .find(|c: char| !c.is_ascii_digit())
+        .unwrap_or(input.len());
+    let (amount, unit) = input.split_at(split);
+    let amount: u64 = amount
+        .parse()
+        .map_err(|_| format!("`{}` is not a duration, such as `30s`", input))?;
+
+    match unit {
+        "ms" => Ok(Duration::from_millis(amount)),
+        "" | "s" => Ok(Duration::from_secs(amount)),
+        "m" => Ok(Duration::from_secs(amount * 60)),
+        _ => Err(format!(
+            "Unknown unit `{}`, expected `ms`, `s` or `m`",
+            unit
+        )),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_durations_with_units() {
+        assert_eq!(parse_duration("30"), Ok(Duration::from_secs(30)));
+        assert_eq!(parse_duration("30s"), Ok(Duration::from_secs(30)));
+        assert_eq!(parse_duration("500ms"), Ok(Duration::from_millis(500)));
+        assert_eq!(parse_duration("2m"), Ok(Duration::from_secs(120)));
+        assert!(parse_duration("2h").is_err());
+        assert!(parse_duration("s").is_err());
+    }
+}
@@ -0,0 +1,260 @@
+use anyhow::{bail, Context, Result};
+use fs2::FileExt;
+use std::env;
+use std::ffi::OsString;
+use std::fs::{self, File, OpenOptions};
+use std::path::Path;
+use std::process::{Command, Stdio};
+use std::thread::sleep;
+use std::time::{Duration, Instant};
+
+// How long to wait for a detached server to claim its PID file
+const STARTUP_TIMEOUT: Duration = Duration::from_secs(10);
+const POLL_INTERVAL: Duration = Duration::from_millis(100);
+
+/// The state of the server recorded in a PID file
+#[derive(Debug, PartialEq)]
+pub enum ServerStatus {
+    /// A mockerize-cli process with this PID is running, and holds the PID file
+    Running(u32),
+    /// The PID file names a process which is no longer running, or is not mockerize-cli
+    Stale(u32),
+    /// There is no PID file
+    NotRunning,
+}
+
+/// Work out whether the server which wrote `pid_file` is still running
+pub fn read_status(pid_file: &str) -> Result<ServerStatus> {
+    let path = Path::new(pid_file);
+    if !path.exists() {
+        return Ok(ServerStatus::NotRunning);
+    }
+
+    let contents = fs::read_to_string(path)
+        .with_context(|| format!("Could not read PID file `{}`", pid_file))?;
+    let pid = contents
+        .trim()
+        .parse::<u32>()
+        .with_context(|| format!("PID file `{}` does not contain a PID", pid_file))?;
+
+    // A running server holds an exclusive lock on its PID file for as long as it runs
+    let file =
+        File::open(path).with_context(|| format!("Could not open PID file `{}`", pid_file))?;
+    let locked = FileExt::try_lock_shared(&file).is_err();
+    if !locked {
+        FileExt::unlock(&file)?;
+    }
+
+    if locked && is_alive(pid) && is_mockerize(pid) {
+        Ok(ServerStatus::Running(pid))
+    } else {
+        Ok(ServerStatus::Stale(pid))
+    }
+}
+
+/**
+Start the current command again as a background process, replacing the subcommand with
+`subcommand` and leaving out `--detach` and `--timeout`. Its output is appended to `log_file`.
+Returns once the new process has claimed `pid_file`, failing if it exits first.
+**/
+pub fn detach(subcommand: &str, pid_file: &str, log_file: &str) -> Result<u32> {
+    let log = OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(log_file)
+        .with_context(|| format!("Could not open log file `{}`", log_file))?;
+
+    let mut command = Command::new(env::current_exe()?);
+    command
+        .args(child_args(subcommand, env::args_os()))
+        .stdin(Stdio::null())
+        .stdout(log.try_clone()?)
+        .stderr(log);
+
+    // Keep CTRL+C in the launching terminal from reaching the server
+    #[cfg(unix)]
+    std::os::unix::process::CommandExt::process_group(&mut command, 0);
+
+    let mut child = command
+        .spawn()
+        .context("Could not start server in the background")?;
+    let pid = child.id();
+
+    let deadline = Instant::now() + STARTUP_TIMEOUT;
+    loop {
+        if let Some(status) = child.try_wait()? {
+            bail!(
+                "Server exited during startup ({}), see `{}` for details",
+                status,
+                log_file
+            );
+        }
+        if read_status(pid_file).ok() == Some(ServerStatus::Running(pid)) {
+            return Ok(pid);
+        }
+        if Instant::now() > deadline {
+            bail!(
+                "Server did not write PID file `{}` within {}s, see `{}` for details",
+                pid_file,
+                STARTUP_TIMEOUT.as_secs(),
+                log_file
+            );
+        }
+        sleep(POLL_INTERVAL);
+    }
+}
+
+/**
+Ask the server recorded in `pid_file` to shut down, and wait up to `timeout` for it to exit.
+Returns the PID of the stopped server, or `None` if none was running, removing any stale PID file.
+**/
+pub fn stop(pid_file: &str, timeout: Duration) -> Result<Option<u32>> {
+    let pid = match read_status(pid_file)? {
+        ServerStatus::Running(pid) => pid,
+        ServerStatus::Stale(_) => {
+            fs::remove_file(pid_file)
+                .with_context(|| format!("Could not remove stale PID file `{}`", pid_file))?;
+            return Ok(None);
+        }
+        ServerStatus::NotRunning => return Ok(None),
+    };
+
+    terminate(pid)?;
+
+    let deadline = Instant::now() + timeout;
+    while is_alive(pid) {
+        if Instant::now() > deadline {
+            bail!("Server with PID {} did not stop within {:?}", pid, timeout);
+        }
+        sleep(POLL_INTERVAL);
+    }
+
+    // A server killed before cleaning up leaves its PID file behind
+    if Path::new(pid_file).exists() {
+        fs::remove_file(pid_file)
+            .with_context(|| format!("Could not remove PID file `{}`", pid_file))?;
+    }
+
+    Ok(Some(pid))
+}
+
+// Arguments for a background copy of this process: the same options, under `subcommand`
+fn child_args(subcommand: &str, args: impl IntoIterator<Item = OsString>) -> Vec<OsString> {
+    let mut child = vec![OsString::from(subcommand)];
+    let mut args = args.into_iter().skip(2);
+
+    while let Some(arg) = args.next() {
+        if arg == "--detach" || arg.to_string_lossy().starts_with("--timeout=") {
+            continue;
+        }
+        if arg == "--timeout" {
+            args.next();
+            continue;
+        }
+        child.push(arg);
+    }
+
+    child
+}
+
+#[cfg(unix)]
+fn is_alive(pid: u32) -> bool {
+    // Signal 0 checks that the process exists, without signalling it
+    let result = unsafe { libc::kill(pid as libc::pid_t, 0) };
+    result == 0 || std::io::Error::last_os_error().raw_os_error() == Some(libc::EPERM)
+}
+
+#[cfg(not(unix))]
+fn is_alive(_pid: u32) -> bool {
+    false
+}
+
+#[cfg(unix)]
+fn terminate(pid: u32) -> Result<()> {
+    if unsafe { libc::kill(pid as libc::pid_t, libc::SIGTERM) } != 0 {
+        return Err(std::io::Error::last_os_error())
+            .with_context(|| format!("Could not signal process {}", pid));
+    }
+    Ok(())
+}
+
+#[cfg(not(unix))]
+fn terminate(_pid: u32) -> Result<()> {
+    bail!("Stopping servers is not supported on this platform")
+}
+
+// Whether process `pid` runs the same executable as this one
+fn is_mockerize(pid: u32) -> bool {
+    let Some(name) = process_name(pid) else {
+        return false;
+    };
+    let own_name = env::current_exe().ok().and_then(|exe| {
+        exe.file_name()
+            .map(|name| name.to_string_lossy().into_owned())
+    });
+
+    own_name.is_some_and(|own_name| name == own_name)
+}
+
+#[cfg(target_os = "linux")]
+fn process_name(pid: u32) -> Option<String> {
+    let cmdline = fs::read(format!("/proc/{}/cmdline", pid)).ok()?;
+    let program = cmdline.split(|&byte| byte == 0).next()?;
+    let program = String::from_utf8_lossy(program);
+    Path::new(program.as_ref())
+        .file_name()
+        .map(|name| name.to_string_lossy().into_owned())
+}
+
+#[cfg(not(target_os = "linux"))]
+fn process_name(pid: u32) -> Option<String> {
+    let output = Command::new("ps")
+        .args(["-o", "comm=", "-p", &pid.to_string()])
+        .output()
+        .ok()?;
+    let program = String::from_utf8_lossy(&output.stdout);
+    Path::new(program.trim())
+        .file_name()
+        .map(|name| name.to_string_lossy().into_owned())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn child_args_replace_subcommand_and_drop_detach_options() {
+        let args = [
+            "mockerize-cli",
+            "restart",
+            "users.json",
+            "--timeout",
+            "5",
+            "--detach",
+            "--pid-file",
+            "users.pid",
+            "--timeout=5",
+        ]
+        .map(OsString::from);
+
+        assert_eq!(
+            child_args("run", args),
+            ["run", "users.json", "--pid-file", "users.pid"].map(OsString::from)
+        );
+    }
+
+    #[test]
+    fn missing_or_unlocked_pid_files_are_not_running() {
+        let pid_file = env::temp_dir().join(format!("mockerize-{}.pid", uuid::Uuid::new_v4()));
+        let pid_file = pid_file.to_string_lossy().to_string();
+        assert_eq!(read_status(&pid_file).unwrap(), ServerStatus::NotRunning);
+
+        // Even a live process is not running a server unless it holds the PID file
+        fs::write(&pid_file, format!("{}\n", std::process::id())).unwrap();
+        assert_eq!(
+            read_status(&pid_file).unwrap(),
+            ServerStatus::Stale(std::process::id())
+        );
+        fs::remove_file(&pid_file).unwrap();
+    }
+}
@@ -0,0 +1,270 @@
+use anyhow::{anyhow, bail, Context, Result};
+use clap::Args;
+use serde::Serialize;
+use serde_json::ser::PrettyFormatter;
+use serde_json::{Map, Value};
+use std::fs;
+
+use crate::http::{Method, ResponseBuilder, ResponseType, ServerInfo};
+
+/**
+A server config file, edited as parsed JSON rather than as a `ServerInfo`, so that
+saving it only changes the nodes that were edited. Key order, indentation and the
+trailing newline of the file are kept.
+**/
+pub struct ConfigFile {
+    path: String,
+    value: Value,
+    indent: String,
+    trailing_newline: bool,
+}
+
+impl ConfigFile {
+    /// Load the config at `path`, failing if it is not a valid server config
+    pub fn open(path: &str) -> Result<Self> {
+        ServerInfo::from_file(path)?;
+
+        let data = fs::read_to_string(path)
+            .with_context(|| format!("Failed to read config file `{}`", path))?;
+        let value = serde_json::from_str(&data)
+            .with_context(|| format!("Could not parse config file `{}` as JSON", path))?;
+
+        Ok(Self {
+            path: path.to_string(),
+            value,
+            indent: detect_indent(&data),
+            trailing_newline: data.ends_with('\n'),
+        })
+    }
+
+    /// The `server` object of the config
+    pub fn server(&mut self) -> &mut Value {
+        &mut self.value["server"]
+    }
+
+    /// The `router.routes` array of the config
+    pub fn routes(&mut self) -> &mut Vec<Value> {
+        array(&mut self.value["router"], "routes")
+    }
+
+    /// Write the edited config back to its file, unless it is no longer a valid server config
+    pub fn save(&self) -> Result<()> {
+        serde_json::from_value::<ServerInfo>(self.value.clone())
+            .with_context(|| format!("Edit would make config `{}` invalid", self.path))?;
+
+        let mut json = Vec::new();
+        let formatter = PrettyFormatter::with_indent(self.indent.as_bytes());
+        let mut serializer = serde_json::Serializer::with_formatter(&mut json, formatter);
+        self.value
+            .serialize(&mut serializer)
+            .context("Could not serialize config into JSON")?;
+        if self.trailing_newline {
+            json.push(b'\n');
+        }
+
+        fs::write(&self.path, json)
+            .with_context(|| format!("Failed to save config `{}`", self.path))
+    }
+}
+
+/// The array at `key` of the object `value`, created empty if missing
+pub fn array<'a>(value: &'a mut Value, key: &str) -> &'a mut Vec<Value> {
+    if !value.is_object() {
+        *value = Value::Object(Map::new());
+    }
+    let entry = &mut value[key];
+    if !entry.is_array() {
+        *entry = Value::Array(vec![]);
+    }
+    // Safe to unwrap; `entry` was just made an array
+    entry.as_array_mut().unwrap()
+}
+
+/// A route of a server config, chosen by its method and path
+#[derive(Args, Debug)]
+pub struct RouteArgs {
+    /// Server config file to edit
+    pub config: String,
+
+    /// Method of the route, e.g. `GET`
+    pub method: Method,
+
+    /// Path of the route, exactly as in the config, e.g. `/users/:id`
+    pub path: String,
+}
+
+impl RouteArgs {
+    /// The index of the chosen route in `routes`
+    pub fn position(&self, routes: &[Value]) -> Result<usize> {
+        routes
+            .iter()
+            .position(|route| {
+                route["path"] == self.path.as_str()
+                    && serde_json::from_value::<Method>(route["method"].clone())
+                        .is_ok_and(|method| method == self.method)
+            })
+            .ok_or_else(|| {
+                anyhow!(
+                    "Config `{}` has no route {} `{}`",
+                    self.config,
+                    self.method,
+                    self.path
+                )
+            })
+    }
+
+    /// Find the chosen route in `config`
+    pub fn find<'a>(&self, config: &'a mut ConfigFile) -> Result<&'a mut Value> {
+        let routes = config.routes();
+        let index = self.position(routes)?;
+        Ok(&mut routes[index])
+    }
+
+    /// The index of the response named `name` within the chosen `route`
+    pub fn find_response(&self, route: &mut Value, name: &str) -> Result<usize> {
+        let responses = array(route, "responses");
+        let mut found = responses
+            .iter()
+            .enumerate()
+            .filter(|(_, r)| r["name"] == name);
+        match (found.next(), found.next()) {
+            (Some((index, _)), None) => Ok(index),
+            (Some(_), Some(_)) => bail!(
+                "Route {} `{}` has more than one response named `{}`",
+                self.method,
+                self.path,
+                name
+            ),
+            (None, _) => bail!(
+                "Route {} `{}` has no response named `{}`",
+                self.method,
+                self.path,
+                name
+            ),
+        }
+    }
+}
+
+/// Settings of a response added on the command line
+#[derive(Args, Debug)]
+pub struct ResponseArgs {
+    /// Status code to respond with
+    #[arg(long, default_value_t = 200)]
+    pub status: u16,
+
+    /// Body to respond with
+    #[arg(long, default_value = "", conflicts_with = "body_file")]
+    pub body: String,
+
+    /// Respond with the contents of this file
+    #[arg(long)]
+    pub body_file: Option<String>,
+
+    /// How the body is served: `text`, `json` or `sse`
+    #[arg(long = "type", default_value = "text", value_parser = parse_response_type)]
+    pub response_type: ResponseType,
+}
+
+impl ResponseArgs {
+    /// A builder for a response named `name` with these settings
+    pub fn builder(&self, name: &str) -> Result<ResponseBuilder> {
+        let body = match &self.body_file {
+            Some(path) => fs::read_to_string(path)
+                .with_context(|| format!("Failed to read body file `{}`", path))?,
+            None => self.body.clone(),
+        };
+
+        Ok(ResponseBuilder::new(name)
+            .status(self.status)
+            .response_type(self.response_type.clone())
+            .body(&body))
+    }
+}
+
+// The indentation of the first indented line of `data`, or two spaces as `to_string_pretty` uses
+fn detect_indent(data: &str) -> String {
+    data.lines()
+        .map(|line| &line[..line.len() - line.trim_start().len()])
+        .find(|indent| !indent.is_empty())
+        .unwrap_or("  ")
+        .to_string()
+}
+
+fn parse_response_type(input: &str) -> Result<ResponseType, String> {
+    serde_json::from_value(Value::String(input.to_lowercase())).map_err(|_| {
+        format!(
+            "`{}` is not a response type, expected `text`, `json` or `sse`",
+            input
+        )
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const EXAMPLE: &str = "tests/data/example.server.json";
+
+    fn copy_of(data: &str, name: &str) -> String {
+        let path = std::env::temp_dir()
+            .join(format!("mockerize-{}-{}.json", name, std::process::id()))
+            .to_string_lossy()
+            .to_string();
+        fs::write(&path, data).unwrap();
+        path
+    }
+
+    #[test]
+    fn saving_unedited_config_leaves_it_byte_identical() {
+        let original = fs::read_to_string(EXAMPLE).unwrap();
+        let path = copy_of(&original, "unedited");
+
+        ConfigFile::open(&path).unwrap().save().unwrap();
+        assert_eq!(fs::read_to_string(&path).unwrap(), original);
+
+        fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn editing_config_only_changes_edited_node() {
+        let original = fs::read_to_string(EXAMPLE).unwrap();
+        let path = copy_of(&original, "edited");
+
+        let mut config = ConfigFile::open(&path).unwrap();
+        let name = config.server()["name"].as_str().unwrap().to_string();
+        config.server()["name"] = Value::from("Renamed server");
+        config.save().unwrap();
+
+        let written = fs::read_to_string(&path).unwrap();
+        assert_eq!(
+            written,
+            original.replacen(
+                &format!("\"name\": \"{}\"", name),
+                "\"name\": \"Renamed server\"",
+                1
+            )
+        );
+
+        fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn invalid_edits_are_not_saved() {
+        let original = fs::read_to_string(EXAMPLE).unwrap();
+        let path = copy_of(&original, "invalid");
+
+        let mut config = ConfigFile::open(&path).unwrap();
+        config.server()["port"] = Value::from("not a port");
+        assert!(config.save().is_err());
+        assert_eq!(fs::read_to_string(&path).unwrap(), original);
+
+        fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn detects_indentation() {
+        assert_eq!(detect_indent("{\n    \"a\": 1\n}"), "    ");
+        assert_eq!(detect_indent("{\n\t\"a\": 1\n}"), "\t");
+        assert_eq!(detect_indent("{}"), "  ");
+    }
+}
@@ -0,0 +1,129 @@
+use anyhow::{bail, Result};
+use clap::{Args, Parser, Subcommand};
+
+use serde_json::Value;
+
+use super::{array, ConfigFile, RouteArgs};
+use crate::http::{Header, Method};
+
+/// Add or remove headers of a server, route or response
+#[derive(Parser, Debug)]
+pub struct HeaderCommand {
+    #[command(subcommand)]
+    pub action: HeaderAction,
+}
+
+#[derive(Subcommand, Debug)]
+pub enum HeaderAction {
+    /// Add a header, replacing any with the same key
+    Add {
+        /// Server config file to edit
+        config: String,
+
+        /// Name of the header, e.g. `Content-Type`
+        key: String,
+
+        /// Value of the header
+        value: String,
+
+        #[command(flatten)]
+        target: HeaderTarget,
+    },
+    /// Remove a header
+    Remove {
+        /// Server config file to edit
+        config: String,
+
+        /// Name of the header, ignoring case
+        key: String,
+
+        #[command(flatten)]
+        target: HeaderTarget,
+    },
+}
+
+/// Where a header is set: on the server, unless a route, and optionally one of its responses, is given
+#[derive(Args, Debug)]
+pub struct HeaderTarget {
+    /// Method of the route to edit the headers of
+    #[arg(long, requires = "path")]
+    pub method: Option<Method>,
+
+    /// Path of the route to edit the headers of
+    #[arg(long, requires = "method")]
+    pub path: Option<String>,
+
+    /// Name of the route's response to edit the headers of
+    #[arg(long, requires = "path")]
+    pub response: Option<String>,
+}
+
+impl HeaderTarget {
+    // The headers this target refers to within `config`
+    fn headers<'a>(&self, config: &'a mut ConfigFile, path: &str) -> Result<&'a mut Vec<Value>> {
+        let (Some(method), Some(route_path)) = (&self.method, &self.path) else {
+            return Ok(array(config.server(), "headers"));
+        };
+
+        let args = RouteArgs {
+            config: path.to_string(),
+            method: method.clone(),
+            path: route_path.clone(),
+        };
+        let route = args.find(config)?;
+        match &self.response {
+            Some(name) => {
+                let index = args.find_response(route, name)?;
+                Ok(array(&mut route["responses"][index], "headers"))
+            }
+            None => Ok(array(route, "headers")),
+        }
+    }
+}
+
+impl HeaderCommand {
+    /// Handles `mockerize-cli header <ACTION>` - edit the headers of a server, route or response
+    pub fn handle(&self) -> Result<()> {
+        let (config, target) = match &self.action {
+            HeaderAction::Add { config, target, .. }
+            | HeaderAction::Remove { config, target, .. } => (config, target),
+        };
+        let mut file = ConfigFile::open(config)?;
+        let headers = target.headers(&mut file, config)?;
+        let matches = |h: &Value, key: &str| {
+            h["key"]
+                .as_str()
+                .is_some_and(|k| k.eq_ignore_ascii_case(key))
+        };
+
+        let message = match &self.action {
+            HeaderAction::Add { key, value, .. } => {
+                let header = Header::try_new(key, value)?;
+                match headers.iter_mut().find(|h| matches(h, key)) {
+                    // Keep the header's id and position
+                    Some(existing) => {
+                        existing["key"] = Value::from(header.key);
+                        existing["value"] = Value::from(header.value);
+                        format!("Replaced header `{}`.", key)
+                    }
+                    None => {
+                        headers.push(serde_json::to_value(header)?);
+                        format!("Added header `{}`.", key)
+                    }
+                }
+            }
+            HeaderAction::Remove { key, .. } => {
+                let count = headers.len();
+                headers.retain(|h| !matches(h, key));
+                if headers.len() == count {
+                    bail!("No header `{}` to remove", key);
+                }
+                format!("Removed header `{}`.", key)
+            }
+        };
+
+        file.save()?;
+        println!("{}", message);
+        Ok(())
+    }
+}
@@ -1,9 +1,31 @@
 mod command;
+mod daemon;
+mod edit;
+mod header;
 mod new;
+mod response;
+mod restart;
+mod route;
 mod run;
+mod shutdown;
+mod status;
+mod stop;
 mod test;
+mod up;
+mod wait;
 
 pub use command::*;
+pub use daemon::*;
+pub use edit::*;
+pub use header::*;
 pub use new::*;
+pub use response::*;
+pub use restart::*;
+pub use route::*;
 pub use run::*;
+pub use shutdown::*;
+pub use status::*;
+pub use stop::*;
 pub use test::*;
+pub use up::*;
+pub use wait::*;
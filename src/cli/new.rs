@@ -1,11 +1,10 @@
 use anyhow::{Context, Result};
 use clap::Parser;
 use std::fs;
-use std::net::IpAddr;
 
 use crate::{
     cli::prompt_for_confirmation,
-    http::{self, Response, ResponseType, Route, ServerInfo},
+    http::{self, ResponseBuilder, RouteBuilder, ServerInfoBuilder},
 };
 
 /// Generate a new server config file
@@ -48,18 +47,15 @@ impl NewCommand {
             }
         }
 
-        let response = Response::new("Example", 200, ResponseType::Text, "Hello, World");
-        let resp_id = response.id; // Copy now to prevent move-related issues a few lines down
-        let mut route = Route::new("/hello-world", http::Method::GET);
-        route.add_response(response);
-        route.set_active_response(resp_id);
-
-        let mut serverinfo = ServerInfo::new()?;
-        serverinfo.server.address = self.address.parse::<IpAddr>()?;
-        serverinfo.server.port = self.port;
-        serverinfo.server.name.clone_from(&self.name);
-        serverinfo.server.description = "".into();
-        serverinfo.router.add_route(route);
+        let serverinfo = ServerInfoBuilder::new()
+            .name(&self.name)
+            .address(&self.address)
+            .port(self.port)
+            .route(
+                RouteBuilder::new(http::Method::GET, "/hello-world")
+                    .response(ResponseBuilder::new("Example").body("Hello, World")),
+            )
+            .build()?;
         serverinfo
             .write_to_file(&self.config_path)
             .with_context(|| {
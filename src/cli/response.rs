@@ -0,0 +1,104 @@
+use anyhow::{bail, Result};
+use clap::{Parser, Subcommand};
+use serde_json::Value;
+
+use super::{array, ConfigFile, ResponseArgs, RouteArgs};
+
+/// Add, remove or activate the responses of a route
+#[derive(Parser, Debug)]
+pub struct ResponseCommand {
+    #[command(subcommand)]
+    pub action: ResponseAction,
+}
+
+#[derive(Subcommand, Debug)]
+pub enum ResponseAction {
+    /// Add a response to a route
+    Add {
+        #[command(flatten)]
+        route: RouteArgs,
+
+        /// Name of the new response, unique within the route
+        name: String,
+
+        #[command(flatten)]
+        response: ResponseArgs,
+
+        /// Serve the new response from now on
+        #[arg(long)]
+        activate: bool,
+    },
+    /// Remove a response from a route. If it was active, the route's first response becomes active.
+    Remove {
+        #[command(flatten)]
+        route: RouteArgs,
+
+        /// Name of the response to remove
+        name: String,
+    },
+    /// Serve this response from the route
+    Activate {
+        #[command(flatten)]
+        route: RouteArgs,
+
+        /// Name of the response to serve
+        name: String,
+    },
+}
+
+impl ResponseCommand {
+    /// Handles `mockerize-cli response <ACTION>` - edit the responses of a route
+    pub fn handle(&self) -> Result<()> {
+        let args = match &self.action {
+            ResponseAction::Add { route, .. }
+            | ResponseAction::Remove { route, .. }
+            | ResponseAction::Activate { route, .. } => route,
+        };
+        let mut config = ConfigFile::open(&args.config)?;
+        let route = args.find(&mut config)?;
+
+        let message = match &self.action {
+            ResponseAction::Add {
+                name,
+                response,
+                activate,
+                ..
+            } => {
+                if args.find_response(route, name).is_ok() {
+                    bail!(
+                        "Route {} `{}` already has a response named `{}`",
+                        args.method,
+                        args.path,
+                        name
+                    );
+                }
+
+                let response = response.builder(name)?.build()?;
+                let id = Value::from(response.id.to_string());
+                array(route, "responses").push(serde_json::to_value(response)?);
+                if *activate {
+                    route["activeResponse"] = id;
+                }
+                format!("Added response `{}`.", name)
+            }
+            ResponseAction::Remove { name, .. } => {
+                let index = args.find_response(route, name)?;
+                let removed = array(route, "responses").remove(index);
+                // The route falls back to its first response
+                if route["activeResponse"] == removed["id"] {
+                    route["activeResponse"] = Value::Null;
+                }
+                format!("Removed response `{}`.", name)
+            }
+            ResponseAction::Activate { name, .. } => {
+                let index = args.find_response(route, name)?;
+                route["activeResponse"] = route["responses"][index]["id"].clone();
+                format!("Activated response `{}`.", name)
+            }
+        };
+
+        config.save()?;
+        println!("{}", message);
+        Ok(())
+    }
+}
@@ -0,0 +1,35 @@
+use anyhow::Result;
+use clap::Parser;
+use std::time::Duration;
+
+use super::{parse_duration, stop, RunCommand};
+
+/// Stop the running server, then run mock servers from config files again
+#[derive(Parser, Debug)]
+pub struct RestartCommand {
+    #[command(flatten)]
+    pub run: RunCommand,
+
+    /// How long to wait for the running server to shut down. The default outlasts the default
+    /// `--shutdown-timeout` of `run`, so that in-flight requests can finish.
+    #[arg(long, default_value = "35s", value_parser = parse_duration)]
+    pub timeout: Duration,
+}
+
+impl RestartCommand {
+    /// Handles `mockerize-cli restart <FILENAME>...` - as `stop`, followed by `run` with the same options
+    pub async fn handle(&self) -> Result<()> {
+        // Configs are checked before stopping, so a broken config doesn't take down the running server
+        let configs = self.run.load_configs()?;
+
+        let serve = &self.run.serve;
+        if let Some(pid) = stop(&serve.pid_file, self.timeout)? {
+            println!("Stopped server with PID {}.", pid);
+        }
+
+        if serve.detach {
+            return serve.start_detached("run");
+        }
+        serve.serve(configs, || self.run.load_configs()).await
+    }
+}
@@ -0,0 +1,94 @@
+use anyhow::{bail, Result};
+use clap::{Parser, Subcommand};
+
+use super::{ConfigFile, ResponseArgs, RouteArgs};
+use crate::http::{RouteBuilder, ServerInfo};
+
+/// Add, remove or list the routes of a server config
+#[derive(Parser, Debug)]
+pub struct RouteCommand {
+    #[command(subcommand)]
+    pub action: RouteAction,
+}
+
+#[derive(Subcommand, Debug)]
+pub enum RouteAction {
+    /// Add a route, with a single response
+    Add {
+        #[command(flatten)]
+        route: RouteArgs,
+
+        /// Name of the route's response
+        #[arg(long, default_value = "Default")]
+        name: String,
+
+        #[command(flatten)]
+        response: ResponseArgs,
+    },
+    /// Remove a route and all of its responses
+    Remove {
+        #[command(flatten)]
+        route: RouteArgs,
+    },
+    /// List every route and its responses, marking the active response with `*`
+    List {
+        /// Server config file to list
+        config: String,
+    },
+}
+
+impl RouteCommand {
+    /// Handles `mockerize-cli route <ACTION>` - edit or list the routes of a config
+    pub fn handle(&self) -> Result<()> {
+        match &self.action {
+            RouteAction::Add {
+                route: args,
+                name,
+                response,
+            } => {
+                let mut config = ConfigFile::open(&args.config)?;
+                if args.find(&mut config).is_ok() {
+                    bail!(
+                        "Config `{}` already has a route {} `{}`",
+                        args.config,
+                        args.method,
+                        args.path
+                    );
+                }
+
+                let route = RouteBuilder::new(args.method.clone(), &args.path)
+                    .response(response.builder(name)?)
+                    .build()?;
+                config.routes().push(serde_json::to_value(route)?);
+                config.save()?;
+                println!("Added route {} `{}`.", args.method, args.path);
+            }
+            RouteAction::Remove { route: args } => {
+                let mut config = ConfigFile::open(&args.config)?;
+                let routes = config.routes();
+                let index = args.position(routes)?;
+                routes.remove(index);
+                config.save()?;
+                println!("Removed route {} `{}`.", args.method, args.path);
+            }
+            RouteAction::List { config } => {
+                let serverinfo = ServerInfo::from_file(config)?;
+                for route in &serverinfo.router.routes {
+                    println!("{:<7} {}", route.method.to_string(), route.path);
+
+                    let active = route.get_active_response().map(|r| r.id);
+                    for response in &route.responses {
+                        let marker = if Some(response.id) == active {
+                            '*'
+                        } else {
+                            ' '
+                        };
+                        println!("  {} {} ({})", marker, response.name, response.status);
+                    }
+                }
+            }
+        }
+
+        Ok(())
+    }
+}
@@ -1,20 +1,41 @@
-use anyhow::{Context, Result};
+use anyhow::{bail, Context, Result};
 use clap::Parser;
 use fs2::FileExt;
-use std::fs::{remove_file, File};
+use std::fs::{self, remove_file, File};
 use std::io::Write;
-use std::net::TcpListener;
-use std::path::Path;
+use std::net::{SocketAddr, TcpListener};
+use std::path::{Path, PathBuf};
 use std::process::{self};
+use std::sync::Arc;
+use std::time::Duration;
+use tracing::{error, info, warn};
 
-use crate::{http::ServerInfo, startup::run};
+use super::{
+    detach, parse_duration, read_status, Cleanup, RunningServer, ServerSet, ServerStatus,
+    ShutdownMode, Signal, Signals,
+};
+use crate::{
+    grpc,
+    http::{ServerInfo, TlsConfig},
+    openapi::OpenApiContract,
+    startup::{run_virtual_hosts, Listener, RunOptions},
+    tls::{shared_client_auth, EphemeralCa},
+};
 
-/// Run a mock server from a config file
+/// Run mock servers from config files
 #[derive(Parser, Debug)]
 pub struct RunCommand {
-    /// Server config file to load
-    pub config_path: String,
+    /// Server config files to load, or directories or glob patterns matching them. Each config is run as a separate server.
+    #[arg(required = true)]
+    pub config_paths: Vec<String>,
 
+    #[command(flatten)]
+    pub serve: ServeArgs,
+}
+
+/// Options for serving mock servers, shared by `run` and `up`
+#[derive(clap::Args, Debug)]
+pub struct ServeArgs {
     /// Specify the number of workers to use
     #[arg(short, long)]
     pub workers: Option<usize>,
@@ -22,34 +43,638 @@ pub struct RunCommand {
     /// Path to write PID file to. Recommended if running multiple instances.
     #[arg(short, long, default_value = "mockerize-cli.pid")]
     pub pid_file: String,
+
+    /// OpenAPI document (JSON or YAML) to check served responses against. Violations are logged as warnings.
+    #[arg(long)]
+    pub openapi: Option<String>,
+
+    /// Serve HTTPS using this PEM encoded certificate chain, overriding the certificate and key in the config
+    #[arg(long, requires = "tls_key", conflicts_with = "self_signed")]
+    pub tls_cert: Option<String>,
+
+    /// PEM encoded private key for `--tls-cert`
+    #[arg(long, requires = "tls_cert")]
+    pub tls_key: Option<String>,
+
+    /// Serve HTTPS using an ephemeral certificate for `localhost` and the server's address
+    #[arg(long)]
+    pub self_signed: bool,
+
+    /// Path to write the CA certificate of `--self-signed` to, for clients to trust
+    #[arg(long, default_value = "mockerize-ca.pem")]
+    pub ca_cert: String,
+
+    /// Listen on this Unix domain socket instead of the server's address and port. Only valid with a single config.
+    #[arg(long)]
+    pub unix_socket: Option<String>,
+
+    /// Create this file once every server is accepting connections, removing it again on shutdown
+    #[arg(long)]
+    pub ready_file: Option<String>,
+
+    /// Write the port of every TCP listener to this file, one per line, once bound.
+    /// Ports are listed in the order of servers and their listeners, showing the port chosen for port 0.
+    #[arg(long)]
+    pub port_file: Option<String>,
+
+    /// Run in the background, returning once the server has started. Stop it with `mockerize-cli stop`.
+    #[arg(long)]
+    pub detach: bool,
+
+    /// File to append the output of a detached server to
+    #[arg(long, default_value = "mockerize-cli.log")]
+    pub log_file: String,
+
+    /// How long a graceful shutdown waits for in-flight requests, e.g. `500ms`, `30s` or `1m`
+    #[arg(long, default_value = "30s", value_parser = parse_duration)]
+    pub shutdown_timeout: Duration,
+
+    /// How to shut down on SIGINT (CTRL+C)
+    #[arg(long, value_enum, default_value = "immediate")]
+    pub on_sigint: ShutdownMode,
+
+    /// How to shut down on SIGTERM, as sent by `mockerize-cli stop`
+    #[arg(long, value_enum, default_value = "graceful")]
+    pub on_sigterm: ShutdownMode,
+}
+
+// Where a server will accept connections, once bound
+#[derive(Debug, Clone, PartialEq)]
+enum Binding {
+    Tcp { addr: SocketAddr, https: bool },
+    Unix(String),
+}
+
+impl Binding {
+    // Whether two servers binding `self` and `other` would fight over the same socket
+    fn collides_with(&self, other: &Binding) -> bool {
+        match (self, other) {
+            (Binding::Tcp { addr: a, .. }, Binding::Tcp { addr: b, .. }) => {
+                // Port 0 asks the OS for any free port, so never collides
+                a.port() != 0
+                    && a.port() == b.port()
+                    && (a.ip() == b.ip() || a.ip().is_unspecified() || b.ip().is_unspecified())
+            }
+            (Binding::Unix(a), Binding::Unix(b)) => a == b,
+            _ => false,
+        }
+    }
+
+    // Bind the socket, recording the port the OS chose if port 0 was asked for
+    fn bind(&mut self) -> Result<Listener> {
+        match self {
+            Binding::Tcp { addr, https } => {
+                let listener = TcpListener::bind(*addr)
+                    .with_context(|| format!("Failed to bind to {}", addr))?;
+                *addr = listener.local_addr()?;
+                Ok(if *https {
+                    Listener::Tcp(listener)
+                } else {
+                    Listener::Cleartext(listener)
+                })
+            }
+            Binding::Unix(path) => bind_unix_socket(path),
+        }
+    }
+}
+
+impl std::fmt::Display for Binding {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Binding::Tcp { addr, https: true } => write!(f, "https://{}", addr),
+            Binding::Tcp { addr, https: false } => write!(f, "{}", addr),
+            Binding::Unix(path) => write!(f, "unix:{}", path),
+        }
+    }
+}
+
+// Server configs sharing the same sockets as virtual hosts (usually just one), and everything needed to start them
+struct PlannedServer {
+    label: String,
+    servers: Vec<ServerInfo>,
+    // The label of each server, for logging
+    labels: Vec<String>,
+    tls: Option<rustls::ServerConfig>,
+    bindings: Vec<Binding>,
+}
+
+impl PlannedServer {
+    // Whether `other` can be served on the same sockets as these servers, dispatching between them by host
+    fn can_share_with(&self, other: &PlannedServer) -> bool {
+        let shareable = |planned: &PlannedServer| {
+            planned.servers.iter().all(|s| s.server.grpc.is_none())
+                && planned.bindings.iter().all(
+                    |binding| !matches!(binding, Binding::Tcp { addr, .. } if addr.port() == 0),
+                )
+        };
+        let has_default =
+            |planned: &PlannedServer| planned.servers.iter().any(|s| s.server.hosts.is_empty());
+
+        self.bindings == other.bindings
+            && shareable(self)
+            && shareable(other)
+            && !(has_default(self) && has_default(other))
+    }
+}
+
+// The sockets servers listen on, as planned and as bound, which are kept open across reloads
+struct Sockets {
+    requested: Vec<Vec<Binding>>,
+    bound: Vec<Vec<Binding>>,
+    listeners: Vec<Vec<Listener>>,
 }
 
 impl RunCommand {
-    /// Handles `mockerize-cli <FILENAME>` - run a mock server
+    /// Handles `mockerize-cli run <FILENAME>...` - run one mock server per config file
     pub async fn handle(&self) -> Result<()> {
-        let pid_handle = create_pid_file(&self.pid_file)?;
-        let serverinfo = ServerInfo::from_file(Path::new(&self.config_path))?;
+        let configs = self.load_configs()?;
+        if self.serve.detach {
+            return self.serve.start_detached("run");
+        }
 
-        let addr = format!("{}:{}", serverinfo.server.address, serverinfo.server.port);
-        println!("Listening on {}. Press CTRL+C to exit.", &addr);
+        self.serve.serve(configs, || self.load_configs()).await
+    }
 
-        let listener = TcpListener::bind(addr.clone())
-            .with_context(|| format!("Failed to bind to {}", &addr))?;
+    /// Load every config named on the command line, labelled with its path
+    pub fn load_configs(&self) -> Result<Vec<(String, ServerInfo)>> {
+        expand_config_paths(&self.config_paths)?
+            .into_iter()
+            .map(|path| Ok((path.display().to_string(), ServerInfo::from_file(&path)?)))
+            .collect()
+    }
+}
 
-        run(serverinfo, listener, self.workers)
-            .context("Failure encountered during server's run()")?
-            .await?;
+impl ServeArgs {
+    /// Start `mockerize-cli <subcommand>` again in the background with these options, once configs are known to load
+    pub fn start_detached(&self, subcommand: &str) -> Result<()> {
+        let pid = detach(subcommand, &self.pid_file, &self.log_file)?;
+        println!(
+            "Started in the background with PID {}, writing output to `{}`.",
+            pid, &self.log_file
+        );
+        Ok(())
+    }
 
-        // Cleanup PID file
-        pid_handle
-            .unlock()
-            .with_context(|| format!("Could not unlock PID file `{}`", &self.pid_file))?;
+    /**
+    Run one mock server per config until they are shut down. Each config is given
+    with a label for where it came from, used if the server has no name. On SIGHUP,
+    configs are loaded again with `reload` and replace the running servers.
+    **/
+    pub async fn serve<F>(&self, configs: Vec<(String, ServerInfo)>, reload: F) -> Result<()>
+    where
+        F: Fn() -> Result<Vec<(String, ServerInfo)>>,
+    {
+        // Removes what we create, even when returning early with an error
+        let mut cleanup = Cleanup::default();
 
-        drop(pid_handle);
-        delete_pid_file(&self.pid_file)?;
+        // While another server holds the PID file, these files are its own
+        if let Ok(ServerStatus::Running(pid)) = read_status(&self.pid_file) {
+            bail!(
+                "A server is already running with PID {}, which holds PID file `{}`",
+                pid,
+                self.pid_file
+            );
+        }
 
-        Ok(())
+        // Files left behind by a previous run must not be mistaken for this one's
+        for path in [&self.ready_file, &self.port_file].into_iter().flatten() {
+            delete_file(path)?;
+        }
+
+        let contract = match &self.openapi {
+            Some(path) => Some(Arc::new(OpenApiContract::from_file(path)?)),
+            None => None,
+        };
+
+        let ca = if self.self_signed {
+            let ca = EphemeralCa::generate()?;
+            fs::write(&self.ca_cert, ca.cert_pem()).with_context(|| {
+                format!("Could not write CA certificate to `{}`", &self.ca_cert)
+            })?;
+            println!("Wrote self-signed CA certificate to `{}`.", &self.ca_cert);
+            Some(ca)
+        } else {
+            None
+        };
+
+        let mut planned = self.plan_all(configs, ca.as_ref())?;
+        let requested = planned
+            .iter()
+            .map(|server| server.bindings.clone())
+            .collect::<Vec<_>>();
+
+        // Bind everything before starting anything, so that no server runs if any fails to bind
+        let mut listeners = vec![];
+        for server in &mut planned {
+            let mut bound = vec![];
+            for binding in &mut server.bindings {
+                let listener = binding
+                    .bind()
+                    .with_context(|| format!("Could not start server `{}`", server.label))?;
+                if let Binding::Unix(path) = binding {
+                    cleanup.remove_later(path);
+                }
+                bound.push(listener);
+            }
+            listeners.push(bound);
+        }
+        let sockets = Sockets {
+            requested,
+            bound: planned
+                .iter()
+                .map(|server| server.bindings.clone())
+                .collect(),
+            listeners,
+        };
+
+        if let Some(path) = &self.port_file {
+            let ports = sockets
+                .bound
+                .iter()
+                .flatten()
+                .filter_map(|binding| match binding {
+                    Binding::Tcp { addr, .. } => Some(format!("{}\n", addr.port())),
+                    Binding::Unix(_) => None,
+                })
+                .collect::<String>();
+            write_atomically(path, &ports)?;
+            cleanup.remove_later(path);
+        }
+
+        // Only claimed once listening, so that a detached parent knows the servers have started
+        cleanup.release_later(create_pid_file(&self.pid_file)?, &self.pid_file);
+
+        let mut signals = Signals::new()?;
+        let mut servers = self.start_all(planned, &sockets, &contract)?;
+
+        // Every socket is bound and its server started, so connections are now accepted
+        if let Some(path) = &self.ready_file {
+            File::create(path)
+                .with_context(|| format!("Could not create ready file `{}`", path))?;
+            cleanup.remove_later(path);
+        }
+
+        loop {
+            let signal = tokio::select! {
+                result = servers.stopped() => {
+                    let stopped = servers.stop(true).await;
+                    return result.and(stopped);
+                }
+                signal = signals.recv() => signal,
+            };
+
+            let mode = match signal {
+                Signal::Hangup => {
+                    servers = self
+                        .reload(servers, &reload, &sockets, ca.as_ref(), &contract)
+                        .await;
+                    continue;
+                }
+                Signal::Interrupt => self.on_sigint,
+                Signal::Terminate => self.on_sigterm,
+                Signal::Quit => ShutdownMode::Immediate,
+            };
+
+            info!("Received {:?}, shutting down", signal);
+            return servers.stop(mode == ShutdownMode::Graceful).await;
+        }
+    }
+
+    // Plan every config, grouping virtual hosts and issuing self-signed certificates
+    fn plan_all(
+        &self,
+        configs: Vec<(String, ServerInfo)>,
+        ca: Option<&EphemeralCa>,
+    ) -> Result<Vec<PlannedServer>> {
+        if self.unix_socket.is_some() && configs.len() > 1 {
+            bail!("--unix-socket can only be used with a single config");
+        }
+
+        let planned = configs
+            .into_iter()
+            .map(|(source, serverinfo)| self.plan(source, serverinfo))
+            .collect::<Result<Vec<_>>>()?;
+        let mut planned = group_virtual_hosts(planned);
+        check_collisions(&planned)?;
+
+        if let Some(ca) = ca {
+            for server in &mut planned {
+                server.tls = Some(issue_self_signed(ca, server)?);
+            }
+        }
+
+        Ok(planned)
+    }
+
+    // Start every planned server on copies of its bound sockets
+    fn start_all(
+        &self,
+        planned: Vec<PlannedServer>,
+        sockets: &Sockets,
+        contract: &Option<Arc<OpenApiContract>>,
+    ) -> Result<ServerSet> {
+        let prefix_labels = planned.len() > 1;
+        let mut running = vec![];
+
+        for (server, listeners) in planned.into_iter().zip(&sockets.listeners) {
+            let listeners = listeners
+                .iter()
+                .map(Listener::try_clone)
+                .collect::<std::io::Result<Vec<_>>>()?;
+            let descriptions = server
+                .bindings
+                .iter()
+                .map(Binding::to_string)
+                .collect::<Vec<_>>();
+            let prefix = if prefix_labels {
+                format!("[{}] ", server.label)
+            } else {
+                String::new()
+            };
+
+            let label = server.label.clone();
+            running.push((label.clone(), self.start(server, listeners, contract)?));
+            println!(
+                "{}Listening on {}. Press CTRL+C to exit.",
+                prefix,
+                descriptions.join(", ")
+            );
+            for address in &descriptions {
+                info!(server = %label, address = %address, "Listening");
+            }
+        }
+
+        Ok(ServerSet::spawn(running))
+    }
+
+    /**
+    Start servers from freshly loaded configs on the same sockets, then gracefully stop the old ones.
+    If the configs cannot be loaded, or would listen elsewhere, the old servers are kept running.
+    **/
+    async fn reload<F>(
+        &self,
+        servers: ServerSet,
+        load: &F,
+        sockets: &Sockets,
+        ca: Option<&EphemeralCa>,
+        contract: &Option<Arc<OpenApiContract>>,
+    ) -> ServerSet
+    where
+        F: Fn() -> Result<Vec<(String, ServerInfo)>>,
+    {
+        info!("Reloading configs");
+        let started = load().and_then(|configs| {
+            let mut planned = self.plan_all(configs, ca)?;
+            let requested = planned
+                .iter()
+                .map(|server| &server.bindings)
+                .collect::<Vec<_>>();
+            if !requested.iter().copied().eq(&sockets.requested) {
+                bail!("Reloaded configs must listen on the same sockets, restart to change them");
+            }
+
+            for (server, bound) in planned.iter_mut().zip(&sockets.bound) {
+                server.bindings = bound.clone();
+            }
+            self.start_all(planned, sockets, contract)
+        });
+
+        match started {
+            Ok(reloaded) => {
+                if let Err(e) = servers.stop(true).await {
+                    warn!("{:#}", e);
+                }
+                println!("Reloaded configs.");
+                reloaded
+            }
+            Err(e) => {
+                error!(
+                    "Could not reload configs, keeping the running servers: {:#}",
+                    e
+                );
+                servers
+            }
+        }
+    }
+
+    // Apply command line overrides to a config and work out where it should listen
+    fn plan(&self, source: String, mut serverinfo: ServerInfo) -> Result<PlannedServer> {
+        let label = if serverinfo.server.name.is_empty() {
+            source
+        } else {
+            serverinfo.server.name.clone()
+        };
+
+        if let (Some(cert), Some(key)) = (&self.tls_cert, &self.tls_key) {
+            let client_auth = serverinfo.server.tls.take().and_then(|tls| tls.client_auth);
+            serverinfo.server.tls = Some(TlsConfig {
+                client_auth,
+                ..TlsConfig::new(cert, key)
+            });
+        }
+
+        let bindings = plan_bindings(&serverinfo, self.self_signed, self.unix_socket.as_ref())
+            .with_context(|| format!("Invalid server `{}`", label))?;
+
+        Ok(PlannedServer {
+            labels: vec![label.clone()],
+            label,
+            servers: vec![serverinfo],
+            tls: None,
+            bindings,
+        })
     }
+
+    fn start(
+        &self,
+        mut server: PlannedServer,
+        mut listeners: Vec<Listener>,
+        contract: &Option<Arc<OpenApiContract>>,
+    ) -> Result<RunningServer> {
+        let context = || format!("Failure encountered during run() of `{}`", server.label);
+
+        // gRPC servers never share their sockets, so are planned alone
+        if server.servers.iter().any(|s| s.server.grpc.is_some()) {
+            let (Some(mut serverinfo), Some(Listener::Cleartext(listener))) =
+                (server.servers.pop(), listeners.pop())
+            else {
+                bail!("gRPC servers only support a single TCP listener");
+            };
+            // The name of a gRPC server is only used to label its logs
+            serverinfo.server.name = server.label.clone();
+            let running = grpc::run(serverinfo, listener).with_context(context)?;
+            return Ok(RunningServer::Grpc(running));
+        }
+
+        let options = RunOptions {
+            workers: self.workers,
+            contract: contract.clone(),
+            tls: server.tls,
+            shutdown_timeout: Some(self.shutdown_timeout),
+            disable_signals: true,
+            journal: None,
+            labels: server.labels,
+        };
+        let running =
+            run_virtual_hosts(server.servers, listeners, options).with_context(context)?;
+        Ok(RunningServer::Http(running))
+    }
+}
+
+// Work out the sockets a server listens on, rejecting unsupported combinations of settings
+fn plan_bindings(
+    serverinfo: &ServerInfo,
+    self_signed: bool,
+    unix_socket: Option<&String>,
+) -> Result<Vec<Binding>> {
+    let server = &serverinfo.server;
+    let https = self_signed || server.tls.is_some();
+    let grpc = server.grpc.is_some();
+    if grpc && https {
+        bail!("gRPC servers only support cleartext HTTP/2 (h2c), not TLS");
+    }
+    if grpc && !server.listeners.is_empty() {
+        bail!("gRPC servers do not support additional listeners");
+    }
+
+    let unix_socket = unix_socket.or(server.unix_socket.as_ref());
+    if unix_socket.is_some() && grpc {
+        bail!("gRPC servers do not support Unix domain sockets");
+    }
+    if unix_socket.is_some() && https {
+        bail!("TLS is not supported over Unix domain sockets");
+    }
+
+    let mut bindings = vec![match unix_socket {
+        Some(path) => Binding::Unix(path.clone()),
+        None => Binding::Tcp {
+            addr: SocketAddr::new(server.address, server.port),
+            https,
+        },
+    }];
+    for extra in &server.listeners {
+        let addr = SocketAddr::new(extra.address, extra.port);
+        let extra_https = extra.tls.unwrap_or(https);
+        if extra_https && !https {
+            bail!(
+                "Listener {} requires TLS, but the server has none configured",
+                addr
+            );
+        }
+        bindings.push(Binding::Tcp {
+            addr,
+            https: extra_https,
+        });
+    }
+
+    Ok(bindings)
+}
+
+/**
+Merge servers which listen on exactly the same sockets, to be served together as virtual hosts.
+Only one server of each group may leave `hosts` empty, so any others are left to collide.
+**/
+fn group_virtual_hosts(planned: Vec<PlannedServer>) -> Vec<PlannedServer> {
+    let mut grouped: Vec<PlannedServer> = vec![];
+
+    for server in planned {
+        match grouped
+            .iter_mut()
+            .find(|group| group.can_share_with(&server))
+        {
+            Some(group) => {
+                group.label = format!("{}, {}", group.label, server.label);
+                group.servers.extend(server.servers);
+                group.labels.extend(server.labels);
+            }
+            None => grouped.push(server),
+        }
+    }
+
+    grouped
+}
+
+// Issue a certificate valid for `localhost`, every address the servers listen on and all of their hosts
+fn issue_self_signed(ca: &EphemeralCa, planned: &PlannedServer) -> Result<rustls::ServerConfig> {
+    let mut hosts = vec!["localhost".to_string()];
+    let addresses = planned.bindings.iter().filter_map(|binding| match binding {
+        Binding::Tcp { addr, .. } => Some(addr.ip().to_string()),
+        Binding::Unix(_) => None,
+    });
+    let names = planned.servers.iter().flat_map(|s| s.server.hosts.clone());
+    for host in addresses.chain(names) {
+        if !hosts.contains(&host) {
+            hosts.push(host);
+        }
+    }
+
+    let client_auth = shared_client_auth(planned.servers.iter().map(|s| {
+        s.server
+            .tls
+            .as_ref()
+            .and_then(|tls| tls.client_auth.as_ref())
+    }))?;
+    ca.server_config(&hosts, client_auth)
+}
+
+// Fail if any two servers, or two listeners of one server, would bind the same socket
+fn check_collisions(servers: &[PlannedServer]) -> Result<()> {
+    let bindings = servers
+        .iter()
+        .flat_map(|server| server.bindings.iter().map(move |b| (&server.label, b)))
+        .collect::<Vec<_>>();
+
+    for (i, (label, binding)) in bindings.iter().enumerate() {
+        for (other_label, other) in &bindings[i + 1..] {
+            if binding.collides_with(other) {
+                bail!(
+                    "Servers `{}` and `{}` both listen on {}",
+                    label,
+                    other_label,
+                    binding
+                );
+            }
+        }
+    }
+
+    Ok(())
+}
+
+/**
+Resolve config paths given on the command line. Directories expand to the `.json` files
+within them, and glob patterns to the files they match, each in name order.
+**/
+pub(crate) fn expand_config_paths(paths: &[String]) -> Result<Vec<PathBuf>> {
+    let mut expanded = vec![];
+
+    for path in paths {
+        let as_path = Path::new(path);
+        if as_path.is_dir() {
+            let mut entries = fs::read_dir(as_path)
+                .with_context(|| format!("Could not read directory `{}`", path))?
+                .filter_map(|entry| entry.ok().map(|entry| entry.path()))
+                .filter(|path| path.is_file() && path.extension().is_some_and(|ext| ext == "json"))
+                .collect::<Vec<_>>();
+            if entries.is_empty() {
+                bail!("Directory `{}` does not contain any `.json` configs", path);
+            }
+            entries.sort();
+            expanded.extend(entries);
+        } else if !as_path.exists() && path.contains(['*', '?', '[']) {
+            let mut matches = glob::glob(path)
+                .with_context(|| format!("Invalid glob pattern `{}`", path))?
+                .collect::<Result<Vec<_>, _>>()?;
+            if matches.is_empty() {
+                bail!("No configs match `{}`", path);
+            }
+            matches.sort();
+            expanded.extend(matches);
+        } else {
+            expanded.push(as_path.to_path_buf());
+        }
+    }
+
+    Ok(expanded)
 }
 
 fn create_pid_file<P: AsRef<Path>>(path: P) -> Result<File> {
@@ -67,10 +692,186 @@ fn create_pid_file<P: AsRef<Path>>(path: P) -> Result<File> {
     Ok(file)
 }
 
-fn delete_pid_file<P: AsRef<Path>>(path: P) -> Result<()> {
+// Write a file such that readers never see it partially written
+fn write_atomically(path: &str, contents: &str) -> Result<()> {
+    let partial = format!("{}.partial", path);
+    fs::write(&partial, contents).with_context(|| format!("Could not write file `{}`", path))?;
+    fs::rename(&partial, path).with_context(|| format!("Could not write file `{}`", path))
+}
+
+pub(crate) fn delete_file<P: AsRef<Path>>(path: P) -> Result<()> {
     let path = path.as_ref();
     if Path::new(path).exists() {
         remove_file(path).with_context(|| format!("Could not remove_file `{}`", path.display()))?;
     }
     Ok(())
 }
+
+// Bind a Unix domain socket, replacing any stale socket left behind by a previous run
+#[cfg(unix)]
+fn bind_unix_socket(path: &str) -> Result<Listener> {
+    use std::io::ErrorKind;
+    use std::os::unix::fs::FileTypeExt;
+    use std::os::unix::net::{UnixListener, UnixStream};
+
+    if fs::symlink_metadata(path).is_ok_and(|meta| meta.file_type().is_socket()) {
+        // Only a socket nothing listens on any more is stale
+        match UnixStream::connect(path) {
+            Ok(_) => bail!("Unix socket `{}` is already in use", path),
+            Err(e) if e.kind() == ErrorKind::ConnectionRefused => {
+                remove_file(path)
+                    .with_context(|| format!("Could not remove stale socket `{}`", path))?;
+            }
+            Err(e) => {
+                return Err(e).with_context(|| format!("Could not check socket `{}`", path));
+            }
+        }
+    }
+
+    let listener =
+        UnixListener::bind(path).with_context(|| format!("Failed to bind to unix:{}", path))?;
+    Ok(Listener::Unix(listener))
+}
+
+#[cfg(not(unix))]
+fn bind_unix_socket(_path: &str) -> Result<Listener> {
+    bail!("Unix domain sockets are not supported on this platform")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::http::{Router, Server, ServerListener};
+
+    fn make_planned(label: &str, address: &str, port: u16) -> PlannedServer {
+        let router = Router::new(None);
+        let server = Server::new(router.id, address, port).unwrap();
+        let serverinfo = ServerInfo {
+            server,
+            router,
+            scenarios: vec![],
+        };
+        let bindings = plan_bindings(&serverinfo, false, None).unwrap();
+
+        PlannedServer {
+            label: label.to_string(),
+            labels: vec![label.to_string()],
+            servers: vec![serverinfo],
+            tls: None,
+            bindings,
+        }
+    }
+
+    #[test]
+    fn detects_port_collisions_between_servers() {
+        let servers = [
+            make_planned("users", "127.0.0.1", 8080),
+            make_planned("orders", "127.0.0.1", 8081),
+        ];
+        assert!(check_collisions(&servers).is_ok());
+
+        let servers = [
+            make_planned("users", "127.0.0.1", 8080),
+            make_planned("orders", "0.0.0.0", 8080),
+        ];
+        let err = check_collisions(&servers).unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "Servers `users` and `orders` both listen on 127.0.0.1:8080"
+        );
+
+        // Ephemeral ports never collide
+        let servers = [
+            make_planned("users", "127.0.0.1", 0),
+            make_planned("orders", "127.0.0.1", 0),
+        ];
+        assert!(check_collisions(&servers).is_ok());
+    }
+
+    #[test]
+    fn groups_virtual_hosts_sharing_a_port() {
+        let mut api = make_planned("api", "127.0.0.1", 8443);
+        api.servers[0].server.hosts = vec!["api.example.com".to_string()];
+        let servers = vec![
+            api,
+            make_planned("default", "127.0.0.1", 8443),
+            make_planned("other", "127.0.0.1", 8080),
+        ];
+
+        let grouped = group_virtual_hosts(servers);
+        assert_eq!(grouped.len(), 2);
+        assert_eq!(grouped[0].label, "api, default");
+        assert_eq!(grouped[0].labels, ["api", "default"]);
+        assert_eq!(grouped[0].servers.len(), 2);
+        assert!(check_collisions(&grouped).is_ok());
+
+        // Only one server sharing a port can go without hosts
+        let servers = vec![
+            make_planned("users", "127.0.0.1", 8443),
+            make_planned("orders", "127.0.0.1", 8443),
+        ];
+        let grouped = group_virtual_hosts(servers);
+        assert_eq!(grouped.len(), 2);
+        assert!(check_collisions(&grouped).is_err());
+    }
+
+    #[test]
+    fn plans_additional_listeners() {
+        let mut planned = make_planned("users", "127.0.0.1", 8443);
+        planned.servers[0].server.listeners = vec![
+            ServerListener {
+                address: "::1".parse().unwrap(),
+                port: 8443,
+                tls: None,
+            },
+            ServerListener {
+                address: "127.0.0.1".parse().unwrap(),
+                port: 8080,
+                tls: Some(false),
+            },
+        ];
+
+        let bindings = plan_bindings(&planned.servers[0], true, None).unwrap();
+        let described = bindings.iter().map(Binding::to_string).collect::<Vec<_>>();
+        assert_eq!(
+            described,
+            [
+                "https://127.0.0.1:8443",
+                "https://[::1]:8443",
+                "127.0.0.1:8080"
+            ]
+        );
+
+        // An HTTPS listener needs the server to have TLS configured
+        planned.servers[0].server.listeners[1].tls = Some(true);
+        assert!(plan_bindings(&planned.servers[0], false, None).is_err());
+    }
+
+    #[test]
+    fn expands_directories_and_globs() {
+        let paths = expand_config_paths(&["tests/data/*.server.json".to_string()]).unwrap();
+        assert!(paths.len() > 1);
+        assert!(paths.contains(&PathBuf::from("tests/data/example.server.json")));
+
+        let paths = expand_config_paths(&["tests/data".to_string()]).unwrap();
+        assert!(paths.contains(&PathBuf::from("tests/data/example.server.json")));
+
+        assert!(expand_config_paths(&["tests/data/*.nothing".to_string()]).is_err());
+    }
+
+    #[test]
+    #[cfg(unix)]
+    fn unix_sockets_in_use_are_not_replaced() {
+        let path = std::env::temp_dir().join(format!("mockerize-{}.sock", uuid::Uuid::new_v4()));
+        let path = path.to_string_lossy().to_string();
+
+        let listener = std::os::unix::net::UnixListener::bind(&path).unwrap();
+        let error = bind_unix_socket(&path).err().unwrap();
+        assert!(error.to_string().contains("already in use"), "{}", error);
+
+        // Once nothing listens on it, the socket file left behind is stale
+        drop(listener);
+        assert!(bind_unix_socket(&path).is_ok());
+        remove_file(&path).unwrap();
+    }
+}
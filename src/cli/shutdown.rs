@@ -0,0 +1,196 @@
+use actix_web::dev::ServerHandle;
+use anyhow::{anyhow, Context, Result};
+use clap::ValueEnum;
+use fs2::FileExt;
+use futures_util::future::join_all;
+use std::fs::File;
+use tokio::sync::watch;
+use tokio::task::JoinSet;
+use tracing::warn;
+
+use super::run::delete_file;
+use crate::grpc;
+
+/// How to shut down in response to a signal
+#[derive(Debug, Clone, Copy, PartialEq, ValueEnum)]
+pub enum ShutdownMode {
+    /// Stop accepting connections, and give in-flight requests until the shutdown timeout to finish
+    Graceful,
+    /// Stop at once, dropping in-flight requests
+    Immediate,
+}
+
+/// A started server, which runs until stopped
+pub(crate) enum RunningServer {
+    Http(actix_web::dev::Server),
+    Grpc(grpc::GrpcServer),
+}
+
+/**
+Servers started together, which are stopped together. Actix servers are stopped through their
+handles, while gRPC servers, which cannot drain in-flight requests, are simply dropped.
+**/
+pub(crate) struct ServerSet {
+    handles: Vec<ServerHandle>,
+    stop_grpc: watch::Sender<bool>,
+    tasks: JoinSet<Result<()>>,
+}
+
+impl ServerSet {
+    pub fn spawn(servers: Vec<(String, RunningServer)>) -> Self {
+        let (stop_grpc, grpc_stopped) = watch::channel(false);
+        let mut handles = vec![];
+        let mut tasks = JoinSet::new();
+
+        for (label, server) in servers {
+            let context = move || format!("Server `{}` stopped unexpectedly", label);
+            match server {
+                RunningServer::Http(server) => {
+                    handles.push(server.handle());
+                    tasks.spawn(async move { server.await.with_context(context) });
+                }
+                RunningServer::Grpc(server) => {
+                    let mut stopped = grpc_stopped.clone();
+                    tasks.spawn(async move {
+                        tokio::select! {
+                            result = server => result.with_context(context),
+                            _ = stopped.wait_for(|stopped| *stopped) => Ok(()),
+                        }
+                    });
+                }
+            }
+        }
+
+        ServerSet {
+            handles,
+            stop_grpc,
+            tasks,
+        }
+    }
+
+    /// Wait for any one of the servers to stop of its own accord, such as after an error
+    pub async fn stopped(&mut self) -> Result<()> {
+        match self.tasks.join_next().await {
+            Some(result) => result?,
+            None => std::future::pending().await,
+        }
+    }
+
+    /// Stop every server, waiting for in-flight requests to finish if `graceful`
+    pub async fn stop(mut self, graceful: bool) -> Result<()> {
+        self.stop_grpc.send_replace(true);
+        join_all(self.handles.iter().map(|handle| handle.stop(graceful))).await;
+
+        let mut errors = vec![];
+        while let Some(result) = self.tasks.join_next().await {
+            if let Err(e) = result.map_err(Into::into).and_then(|result| result) {
+                errors.push(format!("{:#}", e));
+            }
+        }
+        if !errors.is_empty() {
+            return Err(anyhow!(errors.join("\n")));
+        }
+
+        Ok(())
+    }
+}
+
+/// A signal asking the process to stop or reload
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub(crate) enum Signal {
+    Interrupt,
+    Terminate,
+    Hangup,
+    /// Always stops at once
+    #[cfg_attr(not(unix), allow(dead_code))]
+    Quit,
+}
+
+/// Listens for the signals the CLI responds to. Only CTRL+C is supported on non-Unix platforms.
+pub(crate) struct Signals {
+    #[cfg(unix)]
+    interrupt: tokio::signal::unix::Signal,
+    #[cfg(unix)]
+    terminate: tokio::signal::unix::Signal,
+    #[cfg(unix)]
+    hangup: tokio::signal::unix::Signal,
+    #[cfg(unix)]
+    quit: tokio::signal::unix::Signal,
+}
+
+impl Signals {
+    #[cfg(unix)]
+    pub fn new() -> Result<Self> {
+        use tokio::signal::unix::{signal, SignalKind};
+
+        Ok(Signals {
+            interrupt: signal(SignalKind::interrupt())?,
+            terminate: signal(SignalKind::terminate())?,
+            hangup: signal(SignalKind::hangup())?,
+            quit: signal(SignalKind::quit())?,
+        })
+    }
+
+    #[cfg(not(unix))]
+    pub fn new() -> Result<Self> {
+        Ok(Signals {})
+    }
+
+    #[cfg(unix)]
+    pub async fn recv(&mut self) -> Signal {
+        tokio::select! {
+            _ = self.interrupt.recv() => Signal::Interrupt,
+            _ = self.terminate.recv() => Signal::Terminate,
+            _ = self.hangup.recv() => Signal::Hangup,
+            _ = self.quit.recv() => Signal::Quit,
+        }
+    }
+
+    #[cfg(not(unix))]
+    pub async fn recv(&mut self) -> Signal {
+        let _ = tokio::signal::ctrl_c().await;
+        Signal::Interrupt
+    }
+}
+
+/**
+Files created while serving, which are removed however serving ends, whether by a signal or an error.
+The PID file is removed first, while still locked, so that no other server can claim it in between.
+It is then unlocked, and other files are removed in the reverse of the order they were added.
+**/
+#[derive(Default)]
+pub(crate) struct Cleanup {
+    pid_file: Option<(File, String)>,
+    paths: Vec<String>,
+}
+
+impl Cleanup {
+    /// Remove the file at `path` when serving ends
+    pub fn remove_later(&mut self, path: &str) {
+        self.paths.push(path.to_string());
+    }
+
+    /// Remove and unlock the locked PID file at `path` when serving ends
+    pub fn release_later(&mut self, pid_file: File, path: &str) {
+        self.pid_file = Some((pid_file, path.to_string()));
+    }
+}
+
+impl Drop for Cleanup {
+    fn drop(&mut self) {
+        if let Some((pid_file, path)) = self.pid_file.take() {
+            if let Err(e) = delete_file(&path) {
+                warn!("{:#}", e);
+            }
+            if let Err(e) = FileExt::unlock(&pid_file) {
+                warn!("Could not unlock PID file: {}", e);
+            }
+        }
+
+        for path in self.paths.iter().rev() {
+            if let Err(e) = delete_file(path) {
+                warn!("{:#}", e);
+            }
+        }
+    }
+}
@@ -0,0 +1,28 @@
+use anyhow::Result;
+use clap::Parser;
+
+use super::{read_status, ServerStatus};
+
+/// Check whether a server is running
+#[derive(Parser, Debug)]
+pub struct StatusCommand {
+    /// PID file written by the server to check
+    #[arg(short, long, default_value = "mockerize-cli.pid")]
+    pub pid_file: String,
+}
+
+impl StatusCommand {
+    /// Handles `mockerize-cli status` - report whether the server recorded in the PID file is running
+    pub fn handle(&self) -> Result<ServerStatus> {
+        let status = read_status(&self.pid_file)?;
+        match status {
+            ServerStatus::Running(pid) => println!("Running with PID {}.", pid),
+            ServerStatus::Stale(pid) => println!(
+                "Not running, but PID file `{}` names PID {}.",
+                &self.pid_file, pid
+            ),
+            ServerStatus::NotRunning => println!("Not running."),
+        }
+        Ok(status)
+    }
+}
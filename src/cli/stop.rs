@@ -0,0 +1,29 @@
+use anyhow::Result;
+use clap::Parser;
+use std::time::Duration;
+
+use super::{parse_duration, stop};
+
+/// Stop a server running in the background
+#[derive(Parser, Debug)]
+pub struct StopCommand {
+    /// PID file written by the server to stop
+    #[arg(short, long, default_value = "mockerize-cli.pid")]
+    pub pid_file: String,
+
+    /// How long to wait for the server to shut down. The default outlasts the default
+    /// `--shutdown-timeout` of `run`, so that in-flight requests can finish.
+    #[arg(long, default_value = "35s", value_parser = parse_duration)]
+    pub timeout: Duration,
+}
+
+impl StopCommand {
+    /// Handles `mockerize-cli stop` - signal the server recorded in the PID file, and wait for it to exit
+    pub fn handle(&self) -> Result<()> {
+        match stop(&self.pid_file, self.timeout)? {
+            Some(pid) => println!("Stopped server with PID {}.", pid),
+            None => println!("No server is running."),
+        }
+        Ok(())
+    }
+}
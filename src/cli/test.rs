@@ -1,21 +1,48 @@
 use std::path::Path;
 
-use anyhow::{Ok, Result};
+use anyhow::{bail, Ok, Result};
 use clap::Parser;
 
+use crate::graphql::compile_graphql;
+use crate::grpc::check_grpc;
 use crate::http::ServerInfo;
+use crate::openapi::OpenApiContract;
+use crate::sse::check_event_streams;
+use crate::validator::compile_validators;
 
 /// Test if a server config is parsable and meets all requirements
 #[derive(Parser, Debug)]
 pub struct TestCommand {
     /// Path to output new config file to
     pub config_file: String,
+
+    /// OpenAPI document (JSON or YAML) which every configured response must conform to
+    #[arg(long)]
+    pub openapi: Option<String>,
 }
 
 impl TestCommand {
     /// Handles `mockerize-cli test <FILENAME>` - Tests config file to see if it contains errors
     pub fn handle(&self) -> Result<()> {
-        let _serverinfo = ServerInfo::from_file(Path::new(&self.config_file))?;
+        let serverinfo = ServerInfo::from_file(Path::new(&self.config_file))?;
+        compile_validators(&serverinfo)?;
+        check_event_streams(&serverinfo)?;
+        check_grpc(&serverinfo)?;
+        compile_graphql(&serverinfo)?;
+
+        if let Some(path) = &self.openapi {
+            let contract = OpenApiContract::from_file(path)?;
+            let diagnostics = contract.check_serverinfo(&serverinfo);
+            if !diagnostics.is_empty() {
+                bail!(
+                    "{} response(s) violate OpenAPI document `{}`:\n{}",
+                    diagnostics.len(),
+                    path,
+                    diagnostics.join("\n")
+                );
+            }
+        }
+
         Ok(())
     }
 }
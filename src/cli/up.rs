@@ -0,0 +1,36 @@
+use anyhow::Result;
+use clap::Parser;
+use std::path::Path;
+
+use super::ServeArgs;
+use crate::http::{ServerInfo, Workspace};
+
+/// Start every server of a workspace file
+#[derive(Parser, Debug)]
+pub struct UpCommand {
+    /// Workspace file listing the server configs to run
+    pub workspace: String,
+
+    #[command(flatten)]
+    pub serve: ServeArgs,
+}
+
+impl UpCommand {
+    /// Handles `mockerize-cli up <FILENAME>` - run every server of a workspace together
+    pub async fn handle(&self) -> Result<()> {
+        let configs = self.load_configs()?;
+        if self.serve.detach {
+            return self.serve.start_detached("up");
+        }
+
+        self.serve.serve(configs, || self.load_configs()).await
+    }
+
+    /// Load every server of the workspace file, which is read again on each reload
+    fn load_configs(&self) -> Result<Vec<(String, ServerInfo)>> {
+        let path = Path::new(&self.workspace);
+        let workspace = Workspace::from_file(path)?;
+        let base_dir = path.parent().unwrap_or(Path::new("."));
+        workspace.load_servers(base_dir)
+    }
+}
@@ -0,0 +1,246 @@
+use actix_web::web::Bytes;
+use anyhow::{anyhow, bail, Context, Result};
+use clap::Parser;
+use http_body_util::Empty;
+use hyper::client::conn::http2;
+use hyper_util::rt::{TokioExecutor, TokioIo};
+use rustls::pki_types::ServerName;
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr};
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::io::{AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt};
+use tokio::net::TcpStream;
+use tokio::time::{sleep, timeout, Instant};
+use tokio_rustls::TlsConnector;
+
+use super::{expand_config_paths, parse_duration};
+use crate::admin::{ADMIN_PATH, HEALTH_PATH};
+use crate::http::ServerInfo;
+use crate::tls::insecure_client_config;
+
+const POLL_INTERVAL: Duration = Duration::from_millis(100);
+
+/// Wait until servers are answering requests
+#[derive(Parser, Debug)]
+pub struct WaitCommand {
+    /// Server config of a server to wait for, or a directory or glob pattern matching them. May be given more than once.
+    #[arg(short, long, required = true)]
+    pub config: Vec<String>,
+
+    /// How long to wait for every server to answer, such as `30s`
+    #[arg(long, default_value = "30s", value_parser = parse_duration)]
+    pub timeout: Duration,
+}
+
+// How to tell whether a server is answering
+#[derive(Debug, PartialEq)]
+enum Probe {
+    /// Request the health endpoint over cleartext HTTP/1.1
+    Health(SocketAddr),
+    /// Request the health endpoint over HTTPS, without verifying the server's certificate. Servers
+    /// requiring client certificates reject the request, so any response shows they are answering.
+    Tls {
+        addr: SocketAddr,
+        client_cert_required: bool,
+    },
+    /// Send an h2c request, which a gRPC server answers even if it has no such method
+    Grpc(SocketAddr),
+    /// Request the health endpoint over a Unix domain socket
+    #[cfg_attr(not(unix), allow(dead_code))]
+    Unix(String),
+}
+
+impl WaitCommand {
+    /// Handles `mockerize-cli wait --config <FILENAME>` - block until each server answers, or the timeout passes
+    pub async fn handle(&self) -> Result<()> {
+        let probes = expand_config_paths(&self.config)?
+            .into_iter()
+            .map(|path| {
+                let serverinfo = ServerInfo::from_file(&path)?;
+                let label = path.display().to_string();
+                let probe = plan_probe(&serverinfo)
+                    .with_context(|| format!("Cannot wait for server `{}`", label))?;
+                Ok((label, probe))
+            })
+            .collect::<Result<Vec<_>>>()?;
+
+        let deadline = Instant::now() + self.timeout;
+        for (label, probe) in probes {
+            loop {
+                let remaining = deadline.saturating_duration_since(Instant::now());
+                let result = timeout(remaining, probe.check())
+                    .await
+                    .unwrap_or_else(|_| Err(anyhow!("Timed out")));
+
+                match result {
+                    Ok(()) => break,
+                    Err(e) if Instant::now() >= deadline => {
+                        return Err(e).with_context(|| {
+                            format!("Server `{}` was not ready within {:?}", label, self.timeout)
+                        });
+                    }
+                    Err(_) => sleep(POLL_INTERVAL.min(remaining)).await,
+                }
+            }
+            println!("Server `{}` is ready.", label);
+        }
+
+        Ok(())
+    }
+}
+
+// Work out how to reach a server from its config
+fn plan_probe(serverinfo: &ServerInfo) -> Result<Probe> {
+    let server = &serverinfo.server;
+    if let Some(path) = &server.unix_socket {
+        return Ok(Probe::Unix(path.clone()));
+    }
+    if server.port == 0 {
+        bail!("It listens on a port chosen by the OS");
+    }
+
+    // Servers listening on every interface can be reached on loopback
+    let address = match server.address {
+        IpAddr::V4(ip) if ip.is_unspecified() => IpAddr::V4(Ipv4Addr::LOCALHOST),
+        IpAddr::V6(ip) if ip.is_unspecified() => IpAddr::V6(Ipv6Addr::LOCALHOST),
+        ip => ip,
+    };
+    let addr = SocketAddr::new(address, server.port);
+
+    if server.grpc.is_some() {
+        return Ok(Probe::Grpc(addr));
+    }
+    match &server.tls {
+        Some(tls) => Ok(Probe::Tls {
+            addr,
+            client_cert_required: tls.client_auth.as_ref().is_some_and(|auth| auth.required),
+        }),
+        None => Ok(Probe::Health(addr)),
+    }
+}
+
+impl Probe {
+    async fn check(&self) -> Result<()> {
+        match self {
+            Probe::Health(addr) => {
+                let status = request_health(TcpStream::connect(addr).await?).await?;
+                expect_ok(&status)
+            }
+            Probe::Tls {
+                addr,
+                client_cert_required,
+            } => {
+                let stream = TcpStream::connect(addr).await?;
+                let stream = TlsConnector::from(Arc::new(insecure_client_config()?))
+                    .connect(ServerName::IpAddress(addr.ip().into()), stream)
+                    .await?;
+                let status = request_health(stream).await?;
+                if *client_cert_required {
+                    Ok(())
+                } else {
+                    expect_ok(&status)
+                }
+            }
+            Probe::Grpc(addr) => {
+                let stream = TokioIo::new(TcpStream::connect(addr).await?);
+                let (mut sender, connection) =
+                    http2::handshake(TokioExecutor::new(), stream).await?;
+                tokio::spawn(connection);
+
+                let request =
+                    hyper::Request::post(format!("http://{}{}{}", addr, ADMIN_PATH, HEALTH_PATH))
+                        .header("content-type", "application/grpc")
+                        .body(Empty::<Bytes>::new())?;
+                sender.send_request(request).await?;
+                Ok(())
+            }
+            #[cfg(unix)]
+            Probe::Unix(path) => {
+                let status = request_health(tokio::net::UnixStream::connect(path).await?).await?;
+                expect_ok(&status)
+            }
+            #[cfg(not(unix))]
+            Probe::Unix(_) => bail!("Unix domain sockets are not supported on this platform"),
+        }
+    }
+}
+
+// Request the health endpoint over HTTP/1.1, returning the status line of the response
+async fn request_health<S: AsyncRead + AsyncWrite + Unpin>(mut stream: S) -> Result<String> {
+    let request = format!(
+        "GET {}{} HTTP/1.1\r\nHost: localhost\r\nConnection: close\r\n\r\n",
+        ADMIN_PATH, HEALTH_PATH
+    );
+    stream.write_all(request.as_bytes()).await?;
+
+    let mut response = vec![];
+    stream.read_to_end(&mut response).await?;
+    let response = String::from_utf8_lossy(&response);
+    let status_line = response.lines().next().unwrap_or_default();
+    if !status_line.starts_with("HTTP/1.1 ") {
+        bail!("Health check was not answered");
+    }
+
+    Ok(status_line.to_string())
+}
+
+fn expect_ok(status_line: &str) -> Result<()> {
+    if !status_line.starts_with("HTTP/1.1 200") {
+        bail!("Health check responded with `{}`", status_line);
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::http::{ClientAuth, GrpcConfig, Router, Server, TlsConfig};
+
+    fn make_serverinfo(address: &str, port: u16) -> ServerInfo {
+        let router = Router::new(None);
+        let server = Server::new(router.id, address, port).unwrap();
+        ServerInfo {
+            server,
+            router,
+            scenarios: vec![],
+        }
+    }
+
+    #[test]
+    fn probes_health_endpoint_of_cleartext_servers() {
+        let serverinfo = make_serverinfo("0.0.0.0", 8080);
+        assert_eq!(
+            plan_probe(&serverinfo).unwrap(),
+            Probe::Health("127.0.0.1:8080".parse().unwrap())
+        );
+
+        let mut serverinfo = make_serverinfo("::1", 50051);
+        serverinfo.server.grpc = Some(GrpcConfig::new("greeter.proto"));
+        assert_eq!(
+            plan_probe(&serverinfo).unwrap(),
+            Probe::Grpc("[::1]:50051".parse().unwrap())
+        );
+
+        let mut serverinfo = make_serverinfo("127.0.0.1", 8443);
+        let mut tls = TlsConfig::new("cert.pem", "key.pem");
+        serverinfo.server.tls = Some(tls.clone());
+        assert_eq!(
+            plan_probe(&serverinfo).unwrap(),
+            Probe::Tls {
+                addr: "127.0.0.1:8443".parse().unwrap(),
+                client_cert_required: false
+            }
+        );
+        tls.client_auth = Some(ClientAuth::new("ca.pem"));
+        serverinfo.server.tls = Some(tls);
+        assert_eq!(
+            plan_probe(&serverinfo).unwrap(),
+            Probe::Tls {
+                addr: "127.0.0.1:8443".parse().unwrap(),
+                client_cert_required: true
+            }
+        );
+
+        assert!(plan_probe(&make_serverinfo("127.0.0.1", 0)).is_err());
+    }
+}
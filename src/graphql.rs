@@ -0,0 +1,680 @@
+/*!
+Parsing of incoming GraphQL requests, and generation of mock data from a schema.
+
+Generated data follows the shape of the operation's selections: `Int` fields are `42`,
+`Float` fields `1.5`, `Boolean` fields `true`, `ID` fields a fresh UUID, and `String`
+(or any custom scalar) fields `"Hello World"`. Enums take their first value, lists
+contain two items, and interfaces and unions resolve to their first implementing type.
+*/
+
+use anyhow::{anyhow, bail, Context, Result};
+use graphql_parser::query::{
+    self, Definition, FragmentDefinition, OperationDefinition, Selection, SelectionSet,
+    TypeCondition,
+};
+use graphql_parser::schema::{self, Type, TypeDefinition};
+use serde_json::{json, Map, Value};
+use std::collections::HashMap;
+use std::fs;
+use std::sync::Arc;
+use uuid::Uuid;
+
+use crate::http::{ClientIdentity, Response, Route, ScenarioTransition, ServerInfo};
+use crate::template::RequestData;
+
+const MOCK_LIST_LENGTH: usize = 2;
+
+type Fragments<'a> = HashMap<&'a str, &'a FragmentDefinition<'static, String>>;
+
+/// A GraphQL request, with the operation to execute already picked out of its document
+pub struct GraphqlRequest {
+    pub operation_name: Option<String>,
+    pub variables: Value,
+    document: query::Document<'static, String>,
+    operation: usize,
+}
+
+impl GraphqlRequest {
+    /**
+    Read a GraphQL request from a JSON body of `query`, `operationName` and `variables`,
+    falling back to query params of the same names (with `variables` JSON encoded).
+    **/
+    pub fn from_request(request: &RequestData) -> Result<Self> {
+        let (query, operation_name, variables) = match request.json() {
+            Some(body) if body.get("query").is_some() => (
+                body["query"].as_str().map(str::to_string),
+                body.get("operationName")
+                    .and_then(Value::as_str)
+                    .map(str::to_string),
+                body.get("variables").cloned(),
+            ),
+            _ => (
+                request.query.get("query").cloned(),
+                request.query.get("operationName").cloned(),
+                request
+                    .query
+                    .get("variables")
+                    .map(|variables| serde_json::from_str(variables))
+                    .transpose()
+                    .context("Variables are not valid JSON")?,
+            ),
+        };
+
+        let query = query.ok_or_else(|| anyhow!("Must provide a query string"))?;
+        let document = query::parse_query::<String>(&query)
+            .map_err(|e| anyhow!("{}", e.to_string().trim()))?
+            .into_static();
+
+        let operations = document
+            .definitions
+            .iter()
+            .enumerate()
+            .filter_map(|(i, definition)| match definition {
+                Definition::Operation(op) => Some((i, operation_name_of(op))),
+                Definition::Fragment(_) => None,
+            })
+            .collect::<Vec<_>>();
+
+        let (operation, name) = match (&operation_name, operations.as_slice()) {
+            (Some(wanted), _) => operations
+                .iter()
+                .find(|(_, name)| name.as_deref() == Some(wanted))
+                .cloned()
+                .ok_or_else(|| anyhow!("Unknown operation named `{}`", wanted))?,
+            (None, [only]) => only.clone(),
+            (None, []) => bail!("Document does not contain an operation"),
+            (None, _) => bail!("Must provide operation name if query contains multiple operations"),
+        };
+
+        Ok(GraphqlRequest {
+            operation_name: name,
+            variables: variables.unwrap_or(Value::Null),
+            document,
+            operation,
+        })
+    }
+
+    fn operation(&self) -> &OperationDefinition<'static, String> {
+        match &self.document.definitions[self.operation] {
+            Definition::Operation(op) => op,
+            Definition::Fragment(_) => {
+                unreachable!("operation index always refers to an operation")
+            }
+        }
+    }
+
+    fn fragments(&self) -> Fragments<'_> {
+        self.document
+            .definitions
+            .iter()
+            .filter_map(|definition| match definition {
+                Definition::Fragment(fragment) => Some((fragment.name.as_str(), fragment)),
+                Definition::Operation(_) => None,
+            })
+            .collect()
+    }
+}
+
+fn operation_name_of(op: &OperationDefinition<'static, String>) -> Option<String> {
+    match op {
+        OperationDefinition::SelectionSet(_) => None,
+        OperationDefinition::Query(q) => q.name.clone(),
+        OperationDefinition::Mutation(m) => m.name.clone(),
+        OperationDefinition::Subscription(s) => s.name.clone(),
+    }
+}
+
+/// A GraphQL schema, from which mock responses are generated
+pub struct GraphqlSchema {
+    types: HashMap<String, TypeDefinition<'static, String>>,
+    query: String,
+    mutation: String,
+    subscription: String,
+}
+
+impl GraphqlSchema {
+    /// Parse a schema from its SDL
+    pub fn parse(sdl: &str) -> Result<Self> {
+        let document = schema::parse_schema::<String>(sdl)
+            .map_err(|e| anyhow!("{}", e.to_string().trim()))?
+            .into_static();
+
+        let mut schema = GraphqlSchema {
+            types: HashMap::new(),
+            query: "Query".to_string(),
+            mutation: "Mutation".to_string(),
+            subscription: "Subscription".to_string(),
+        };
+
+        for definition in document.definitions {
+            match definition {
+                schema::Definition::SchemaDefinition(roots) => {
+                    if let Some(query) = roots.query {
+                        schema.query = query;
+                    }
+                    if let Some(mutation) = roots.mutation {
+                        schema.mutation = mutation;
+                    }
+                    if let Some(subscription) = roots.subscription {
+                        schema.subscription = subscription;
+                    }
+                }
+                schema::Definition::TypeDefinition(definition) => {
+                    schema
+                        .types
+                        .insert(type_name(&definition).to_string(), definition);
+                }
+                _ => {}
+            }
+        }
+
+        if !schema.types.contains_key(&schema.query) {
+            bail!("Schema does not define a `{}` type", schema.query);
+        }
+
+        Ok(schema)
+    }
+
+    /// Read and parse an SDL file
+    pub fn load(path: &str) -> Result<Self> {
+        let sdl = fs::read_to_string(path)
+            .with_context(|| format!("Failed to read GraphQL schema `{}`", path))?;
+        Self::parse(&sdl).with_context(|| format!("Invalid GraphQL schema `{}`", path))
+    }
+
+    /// Generate a response to `request`, as `{"data": ...}`
+    pub fn mock(&self, request: &GraphqlRequest) -> Result<Value> {
+        let (root, selection_set) = match request.operation() {
+            OperationDefinition::SelectionSet(set) => (&self.query, set),
+            OperationDefinition::Query(q) => (&self.query, &q.selection_set),
+            OperationDefinition::Mutation(m) => (&self.mutation, &m.selection_set),
+            OperationDefinition::Subscription(s) => (&self.subscription, &s.selection_set),
+        };
+        if !self.types.contains_key(root) {
+            bail!("Schema does not define a `{}` type", root);
+        }
+
+        let data = self.mock_selection(root, selection_set, &request.fragments(), &mut vec![])?;
+        Ok(json!({ "data": data }))
+    }
+
+    fn mock_selection<'a>(
+        &self,
+        type_name: &str,
+        selection_set: &SelectionSet<'static, String>,
+        fragments: &Fragments<'a>,
+        spreads: &mut Vec<&'a str>,
+    ) -> Result<Value> {
+        let mut object = Map::new();
+        self.mock_fields(type_name, selection_set, fragments, spreads, &mut object)?;
+        Ok(Value::Object(object))
+    }
+
+    // `spreads` holds the fragments spread on the path to this selection, to reject cycles
+    fn mock_fields<'a>(
+        &self,
+        type_name: &str,
+        selection_set: &SelectionSet<'static, String>,
+        fragments: &Fragments<'a>,
+        spreads: &mut Vec<&'a str>,
+        object: &mut Map<String, Value>,
+    ) -> Result<()> {
+        for selection in &selection_set.items {
+            match selection {
+                Selection::Field(field) => {
+                    let key = field.alias.as_ref().unwrap_or(&field.name).clone();
+                    if field.name == "__typename" {
+                        object.insert(key, Value::String(type_name.to_string()));
+                        continue;
+                    }
+
+                    let field_type = self.field_type(type_name, &field.name).ok_or_else(|| {
+                        anyhow!(
+                            "Cannot query field `{}` on type `{}`",
+                            field.name,
+                            type_name
+                        )
+                    })?;
+                    let value =
+                        self.mock_type(field_type, &field.selection_set, fragments, spreads)?;
+                    match object.get_mut(&key) {
+                        Some(existing) => merge_selections(existing, value),
+                        None => {
+                            object.insert(key, value);
+                        }
+                    }
+                }
+                Selection::FragmentSpread(spread) => {
+                    let fragment = fragments
+                        .get(spread.fragment_name.as_str())
+                        .ok_or_else(|| anyhow!("Unknown fragment `{}`", spread.fragment_name))?;
+                    if spreads.contains(&fragment.name.as_str()) {
+                        bail!("Fragment `{}` spreads itself", fragment.name);
+                    }
+                    let TypeCondition::On(condition) = &fragment.type_condition;
+                    if self.satisfies(type_name, condition) {
+                        spreads.push(&fragment.name);
+                        let selection_set = &fragment.selection_set;
+                        self.mock_fields(type_name, selection_set, fragments, spreads, object)?;
+                        spreads.pop();
+                    }
+                }
+                Selection::InlineFragment(inline) => {
+                    let applies = match &inline.type_condition {
+                        Some(TypeCondition::On(condition)) => self.satisfies(type_name, condition),
+                        None => true,
+                    };
+                    if applies {
+                        let selection_set = &inline.selection_set;
+                        self.mock_fields(type_name, selection_set, fragments, spreads, object)?;
+                    }
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    fn mock_type<'a>(
+        &self,
+        field_type: &Type<'static, String>,
+        selection_set: &SelectionSet<'static, String>,
+        fragments: &Fragments<'a>,
+        spreads: &mut Vec<&'a str>,
+    ) -> Result<Value> {
+        match field_type {
+            Type::NonNullType(inner) => self.mock_type(inner, selection_set, fragments, spreads),
+            Type::ListType(inner) => (0..MOCK_LIST_LENGTH)
+                .map(|_| self.mock_type(inner, selection_set, fragments, spreads))
+                .collect::<Result<Vec<_>>>()
+                .map(Value::Array),
+            Type::NamedType(name) => self.mock_named(name, selection_set, fragments, spreads),
+        }
+    }
+
+    fn mock_named<'a>(
+        &self,
+        name: &str,
+        selection_set: &SelectionSet<'static, String>,
+        fragments: &Fragments<'a>,
+        spreads: &mut Vec<&'a str>,
+    ) -> Result<Value> {
+        match name {
+            "Int" => return Ok(json!(42)),
+            "Float" => return Ok(json!(1.5)),
+            "Boolean" => return Ok(json!(true)),
+            "ID" => return Ok(json!(Uuid::new_v4().to_string())),
+            "String" => return Ok(json!("Hello World")),
+            _ => {}
+        }
+
+        let definition = self
+            .types
+            .get(name)
+            .ok_or_else(|| anyhow!("Schema does not define a `{}` type", name))?;
+
+        match definition {
+            TypeDefinition::Scalar(_) => Ok(json!("Hello World")),
+            TypeDefinition::Enum(e) => Ok(e
+                .values
+                .first()
+                .map(|value| json!(value.name))
+                .unwrap_or(Value::Null)),
+            TypeDefinition::Object(_) => {
+                self.mock_selection(name, selection_set, fragments, spreads)
+            }
+            TypeDefinition::Interface(_) | TypeDefinition::Union(_) => {
+                let concrete = self.concrete_type(definition).unwrap_or(name);
+                self.mock_selection(concrete, selection_set, fragments, spreads)
+            }
+            TypeDefinition::InputObject(_) => Ok(Value::Null),
+        }
+    }
+
+    // The type of field `name` on object or interface `type_name`
+    fn field_type(&self, type_name: &str, name: &str) -> Option<&Type<'static, String>> {
+        let fields = match self.types.get(type_name)? {
+            TypeDefinition::Object(object) => &object.fields,
+            TypeDefinition::Interface(interface) => &interface.fields,
+            _ => return None,
+        };
+        fields
+            .iter()
+            .find(|field| field.name == name)
+            .map(|field| &field.field_type)
+    }
+
+    // The object type used to mock an interface or union: its first implementation or member
+    fn concrete_type<'a>(
+        &'a self,
+        definition: &'a TypeDefinition<'static, String>,
+    ) -> Option<&'a str> {
+        match definition {
+            TypeDefinition::Union(union) => union.types.first().map(String::as_str),
+            TypeDefinition::Interface(interface) => {
+                let mut implementations = self
+                    .types
+                    .values()
+                    .filter_map(|definition| match definition {
+                        TypeDefinition::Object(object)
+                            if object.implements_interfaces.contains(&interface.name) =>
+                        {
+                            Some(object.name.as_str())
+                        }
+                        _ => None,
+                    })
+                    .collect::<Vec<_>>();
+                // Types are stored unordered, so sort for a consistent choice
+                implementations.sort();
+                implementations.first().copied()
+            }
+            _ => None,
+        }
+    }
+
+    // Whether a fragment on `condition` applies to values of object type `type_name`
+    fn satisfies(&self, type_name: &str, condition: &str) -> bool {
+        if type_name == condition {
+            return true;
+        }
+
+        match self.types.get(condition) {
+            Some(TypeDefinition::Interface(_)) => matches!(
+                self.types.get(type_name),
+                Some(TypeDefinition::Object(object))
+                    if object.implements_interfaces.iter().any(|i| i == condition)
+            ),
+            Some(TypeDefinition::Union(union)) => union.types.iter().any(|t| t == type_name),
+            _ => false,
+        }
+    }
+}
+
+// A field selected more than once is answered with the union of its sub-selections
+fn merge_selections(existing: &mut Value, value: Value) {
+    match (existing, value) {
+        (Value::Object(existing), Value::Object(value)) => {
+            for (key, value) in value {
+                match existing.get_mut(&key) {
+                    Some(existing) => merge_selections(existing, value),
+                    None => {
+                        existing.insert(key, value);
+                    }
+                }
+            }
+        }
+        (Value::Array(existing), Value::Array(value)) => {
+            for (existing, value) in existing.iter_mut().zip(value) {
+                merge_selections(existing, value);
+            }
+        }
+        // Scalars are kept as first generated, so that e.g. an `id` selected twice is stable
+        _ => {}
+    }
+}
+
+fn type_name<'a>(definition: &'a TypeDefinition<'static, String>) -> &'a str {
+    match definition {
+        TypeDefinition::Scalar(t) => &t.name,
+        TypeDefinition::Object(t) => &t.name,
+        TypeDefinition::Interface(t) => &t.name,
+        TypeDefinition::Union(t) => &t.name,
+        TypeDefinition::Enum(t) => &t.name,
+        TypeDefinition::InputObject(t) => &t.name,
+    }
+}
+
+/// How a GraphQL request is to be answered
+pub enum Resolved<'a> {
+    /// Serve a configured response
+    Response(&'a Response),
+    /// Serve data generated from the route's schema
+    Mock(Value),
+}
+
+/**
+Decide how to answer a request to GraphQL `route`. Responses whose rules are all met take
+priority, as on any other route, then data generated from `schema`, if there is one, and
+finally the route's active response. Errors if the request is not valid GraphQL.
+**/
+pub fn resolve<'a, F>(
+    route: &'a Route,
+    schema: Option<&GraphqlSchema>,
+    request: &RequestData,
+    matches: F,
+    client: Option<&ClientIdentity>,
+) -> Result<Resolved<'a>>
+where
+    F: Fn(&ScenarioTransition) -> bool,
+{
+    let request = GraphqlRequest::from_request(request)?;
+
+    let selected = route.select_graphql_response(
+        request.operation_name.as_deref(),
+        &request.variables,
+        matches,
+        client,
+    );
+    if let Some(response) = selected {
+        return Ok(Resolved::Response(response));
+    }
+
+    if let Some(schema) = schema {
+        return schema.mock(&request).map(Resolved::Mock);
+    }
+
+    route
+        .get_active_response()
+        .map(Resolved::Response)
+        .ok_or_else(|| anyhow!("No response matches this operation"))
+}
+
+/// Load the schema of every GraphQL route in `serverinfo` which has one, keyed by route ID
+pub fn compile_graphql(serverinfo: &ServerInfo) -> Result<HashMap<Uuid, Arc<GraphqlSchema>>> {
+    let mut schemas = HashMap::new();
+
+    for route in &serverinfo.router.routes {
+        let Some(path) = route.graphql.as_ref().and_then(|g| g.schema.as_ref()) else {
+            continue;
+        };
+        let schema = GraphqlSchema::load(path)
+            .with_context(|| format!("Invalid GraphQL route {} {}", route.method, route.path))?;
+        schemas.insert(route.id, Arc::new(schema));
+    }
+
+    Ok(schemas)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::http::{ClientCertMatch, GraphqlConfig, GraphqlMatch, Method, ResponseType};
+
+    const SCHEMA: &str = r#"
+        type Query {
+            user(id: ID!): User
+            search: [SearchResult!]!
+            node: Node
+        }
+
+        interface Node { id: ID! }
+
+        type User implements Node {
+            id: ID!
+            name: String!
+            age: Int
+            role: Role!
+            friends: [User!]!
+        }
+
+        type Post implements Node {
+            id: ID!
+            title: String!
+        }
+
+        union SearchResult = Post | User
+
+        enum Role { ADMIN USER }
+    "#;
+
+    fn graphql_request(body: Value) -> RequestData {
+        RequestData {
+            body: body.to_string(),
+            ..Default::default()
+        }
+    }
+
+    fn mock(query: &str) -> Result<Value> {
+        let schema = GraphqlSchema::parse(SCHEMA).unwrap();
+        let request = GraphqlRequest::from_request(&graphql_request(json!({ "query": query })))?;
+        schema.mock(&request)
+    }
+
+    #[test]
+    fn picks_the_operation_to_execute() {
+        let query = "query A { user(id: 1) { name } } query B { search { __typename } }";
+
+        let request = graphql_request(json!({ "query": query, "operationName": "B" }));
+        let request = GraphqlRequest::from_request(&request).unwrap();
+        assert_eq!(request.operation_name.as_deref(), Some("B"));
+
+        let request = graphql_request(json!({ "query": query }));
+        assert!(GraphqlRequest::from_request(&request).is_err());
+
+        let request = RequestData {
+            query: HashMap::from([
+                ("query".to_string(), "{ user(id: 1) { name } }".to_string()),
+                ("variables".to_string(), r#"{"id": 1}"#.to_string()),
+            ]),
+            ..Default::default()
+        };
+        let request = GraphqlRequest::from_request(&request).unwrap();
+        assert_eq!(request.operation_name, None);
+        assert_eq!(request.variables, json!({ "id": 1 }));
+
+        let request = graphql_request(json!({ "query": "{ user(id: " }));
+        assert!(GraphqlRequest::from_request(&request).is_err());
+    }
+
+    #[test]
+    fn mocks_data_following_the_selections() {
+        let data = mock(
+            "query { u: user(id: 1) { __typename name age role friends { id } ...Extra } }
+             fragment Extra on User { friends { name } }",
+        )
+        .unwrap();
+
+        let user = &data["data"]["u"];
+        assert_eq!(user["__typename"], "User");
+        assert_eq!(user["name"], "Hello World");
+        assert_eq!(user["age"], 42);
+        assert_eq!(user["role"], "ADMIN");
+        assert_eq!(user["friends"].as_array().unwrap().len(), 2);
+        assert!(user["friends"][0]["id"].is_string());
+        assert_eq!(user["friends"][0]["name"], "Hello World");
+    }
+
+    #[test]
+    fn rejects_fragment_cycles() {
+        let error = mock("{ user(id: 1) { ...A } } fragment A on User { ...A }").unwrap_err();
+        assert!(
+            error.to_string().contains("Fragment `A` spreads itself"),
+            "{}",
+            error
+        );
+
+        let cycle = "{ user(id: 1) { ...A } }
+             fragment A on User { friends { ...B } }
+             fragment B on User { name ...A }";
+        assert!(mock(cycle).is_err());
+
+        // Spreading the same fragment twice, but not within itself, is fine
+        let data =
+            mock("{ user(id: 1) { ...A friends { ...A } } } fragment A on User { name }").unwrap();
+        assert_eq!(data["data"]["user"]["friends"][0]["name"], "Hello World");
+    }
+
+    #[test]
+    fn mocks_abstract_types_with_their_first_implementation() {
+        let data = mock(
+            "{ search { __typename ... on Post { title } ... on User { name } } node { id __typename } }",
+        )
+        .unwrap();
+
+        assert_eq!(data["data"]["search"][0]["__typename"], "Post");
+        assert_eq!(data["data"]["search"][0]["title"], "Hello World");
+        assert!(data["data"]["search"][0].get("name").is_none());
+        assert_eq!(data["data"]["node"]["__typename"], "Post");
+
+        assert!(mock("{ user(id: 1) { email } }").is_err());
+        assert!(mock("mutation { doThing }").is_err());
+    }
+
+    #[test]
+    fn explicit_responses_take_priority_over_mocks() {
+        let mut matched = Response::new("Alice", 200, ResponseType::Json, r#"{"data": {}}"#);
+        matched.graphql = Some(GraphqlMatch {
+            operation_name: Some("GetUser".into()),
+            variables: Some(json!({ "id": "1" })),
+        });
+        let matched_id = matched.id;
+
+        let mut route = Route::new("/graphql", Method::POST);
+        route.graphql = Some(GraphqlConfig::default());
+        route.add_response(matched);
+
+        let schema = GraphqlSchema::parse(SCHEMA).unwrap();
+        let query = "query GetUser($id: ID!) { user(id: $id) { name } }";
+        let resolve_with = |variables: Value, schema: Option<&GraphqlSchema>| {
+            let request = graphql_request(json!({ "query": query, "variables": variables }));
+            resolve(&route, schema, &request, |_| true, None)
+        };
+
+        let resolved = resolve_with(json!({ "id": "1" }), Some(&schema)).unwrap();
+        assert!(matches!(resolved, Resolved::Response(r) if r.id == matched_id));
+
+        let resolved = resolve_with(json!({ "id": "2" }), Some(&schema)).unwrap();
+        assert!(matches!(resolved, Resolved::Mock(_)));
+
+        // Without a schema, the active response is served instead
+        let resolved = resolve_with(json!({ "id": "2" }), None).unwrap();
+        assert!(matches!(resolved, Resolved::Response(r) if r.id == matched_id));
+    }
+
+    #[test]
+    fn responses_selected_by_scenario_or_client_cert_take_priority_over_mocks() {
+        let mut paid = Response::new("Paid", 200, ResponseType::Json, r#"{"data": {}}"#);
+        paid.scenario.required_state = Some("paid".into());
+        let paid_id = paid.id;
+        let mut tenant = Response::new("Tenant", 200, ResponseType::Json, r#"{"data": {}}"#);
+        tenant.client_cert = Some(ClientCertMatch {
+            subject: Some("tenant-a".into()),
+            san: None,
+        });
+        let tenant_id = tenant.id;
+
+        let mut route = Route::new("/graphql", Method::POST);
+        route.graphql = Some(GraphqlConfig::default());
+        route.scenario.scenario = Some("order".into());
+        route.add_response(paid);
+        route.add_response(tenant);
+
+        let schema = GraphqlSchema::parse(SCHEMA).unwrap();
+        let request = graphql_request(json!({ "query": "{ user(id: 1) { name } }" }));
+        let identity = ClientIdentity {
+            subject: "CN=tenant-a".into(),
+            common_name: Some("tenant-a".into()),
+            sans: vec![],
+        };
+
+        let resolved = resolve(&route, Some(&schema), &request, |_| true, None).unwrap();
+        assert!(matches!(resolved, Resolved::Response(r) if r.id == paid_id));
+
+        let resolved =
+            resolve(&route, Some(&schema), &request, |_| false, Some(&identity)).unwrap();
+        assert!(matches!(resolved, Resolved::Response(r) if r.id == tenant_id));
+
+        let resolved = resolve(&route, Some(&schema), &request, |_| false, None).unwrap();
+        assert!(matches!(resolved, Resolved::Mock(_)));
+    }
+}
@@ -0,0 +1,427 @@
+use actix_web::web::Bytes;
+use anyhow::{anyhow, bail, Context, Result};
+use futures_util::stream;
+use http_body_util::{BodyExt, StreamBody};
+use hyper::body::{Frame, Incoming};
+use hyper::header::{HeaderMap, HeaderName, HeaderValue};
+use hyper::server::conn::http2;
+use hyper::service::service_fn;
+use hyper_util::rt::{TokioExecutor, TokioIo};
+use prost_reflect::prost::Message;
+use prost_reflect::{DescriptorPool, DynamicMessage, MethodDescriptor};
+use serde_json::Value;
+use std::collections::HashMap;
+use std::convert::Infallible;
+use std::future::Future;
+use std::net::TcpListener;
+use std::path::Path;
+use std::pin::Pin;
+use std::sync::Arc;
+use tracing::{debug, info, info_span, warn};
+use uuid::Uuid;
+
+use crate::http::{GrpcConfig, GrpcStatus, Header, Route, ServerInfo};
+use crate::startup::{merge_headers, prepare_body};
+use crate::state::{ScenarioStore, StateStore};
+use crate::template::RequestData;
+
+const STATUS_INTERNAL: i32 = 13;
+const STATUS_UNIMPLEMENTED: i32 = 12;
+
+type GrpcBody = StreamBody<stream::Iter<std::vec::IntoIter<Result<Frame<Bytes>, Infallible>>>>;
+
+/// A running gRPC server. Resolves when the listener fails.
+pub type GrpcServer = Pin<Box<dyn Future<Output = std::io::Result<()>> + Send>>;
+
+/// Compile the `.proto` file of `config`, along with its imports
+pub fn load_descriptors(config: &GrpcConfig) -> Result<DescriptorPool> {
+    let proto = Path::new(&config.proto);
+    let includes = if config.includes.is_empty() {
+        let parent = proto.parent().unwrap_or(Path::new("."));
+        vec![if parent.as_os_str().is_empty() {
+            Path::new(".")
+        } else {
+            parent
+        }]
+    } else {
+        config.includes.iter().map(Path::new).collect()
+    };
+
+    let descriptors = protox::compile([proto], includes)
+        .with_context(|| format!("Failed to compile `{}`", &config.proto))?;
+    DescriptorPool::from_file_descriptor_set(descriptors)
+        .with_context(|| format!("Invalid descriptors in `{}`", &config.proto))
+}
+
+/// Check that every route of a gRPC server is a method of its `.proto` file, and that
+/// every response which is not templated encodes to that method's output message
+pub fn check_grpc(serverinfo: &ServerInfo) -> Result<()> {
+    if serverinfo.server.grpc.is_some() {
+        GrpcService::new(serverinfo)?;
+    }
+    Ok(())
+}
+
+/// Starts a new gRPC server, accepting cleartext HTTP/2 (h2c) connections on the given `TcpListener`
+pub fn run(serverinfo: ServerInfo, listener: TcpListener) -> std::io::Result<GrpcServer> {
+    let server_span = info_span!("Starting server", server = %serverinfo.server.name);
+    let _server_span_guard = server_span.enter();
+
+    let service = GrpcService::new(&serverinfo)
+        .map_err(|e| std::io::Error::new(std::io::ErrorKind::InvalidData, format!("{:#}", e)))?;
+    for path in service.methods.keys() {
+        info!("Registering gRPC method {}", path);
+    }
+    let service = Arc::new(service);
+    listener.set_nonblocking(true)?;
+
+    Ok(Box::pin(async move {
+        let listener = tokio::net::TcpListener::from_std(listener)?;
+        loop {
+            let (stream, _) = listener.accept().await?;
+            let service = service.clone();
+
+            tokio::spawn(async move {
+                let handler = service_fn(move |req| handle(service.clone(), req));
+                let connection = http2::Builder::new(TokioExecutor::new())
+                    .serve_connection(TokioIo::new(stream), handler);
+                if let Err(e) = connection.await {
+                    debug!("gRPC connection closed with error: {}", e);
+                }
+            });
+        }
+    }))
+}
+
+struct GrpcMethod {
+    route: Route,
+    descriptor: MethodDescriptor,
+}
+
+struct GrpcService {
+    server_name: String,
+    methods: HashMap<String, GrpcMethod>,
+    server_headers: Vec<Header>,
+    state: StateStore,
+    scenarios: ScenarioStore,
+}
+
+impl GrpcService {
+    fn new(serverinfo: &ServerInfo) -> Result<Self> {
+        let config = serverinfo
+            .server
+            .grpc
+            .as_ref()
+            .ok_or_else(|| anyhow!("Server is not configured for gRPC"))?;
+        let pool = load_descriptors(config)?;
+
+        let mut methods = HashMap::new();
+        for route in &serverinfo.router.routes {
+            let path = method_path(&route.path);
+            let descriptor = find_method(&pool, &path)
+                .ok_or_else(|| anyhow!("Method `{}` is not defined in `{}`", path, config.proto))?;
+
+            for response in &route.responses {
+                if !response.templated {
+                    encode_messages(&descriptor, &response.get_response_body()).with_context(
+                        || format!("Invalid response `{}` for method `{}`", response.name, path),
+                    )?;
+                }
+            }
+
+            if route.get_active_response().is_some() {
+                let route = route.clone();
+                methods.insert(path, GrpcMethod { route, descriptor });
+            }
+        }
+
+        Ok(GrpcService {
+            server_name: serverinfo.server.name.clone(),
+            methods,
+            server_headers: serverinfo.server.headers.clone(),
+            state: StateStore::new(),
+            scenarios: ScenarioStore::new(serverinfo.initial_scenario_states()),
+        })
+    }
+}
+
+async fn handle(
+    service: Arc<GrpcService>,
+    req: hyper::Request<Incoming>,
+) -> Result<hyper::Response<GrpcBody>, Infallible> {
+    let path = req.uri().path().to_string();
+    let Some(method) = service.methods.get(&path) else {
+        warn!("Client called unknown gRPC method {}", &path);
+        let status = error_status(
+            STATUS_UNIMPLEMENTED,
+            format!("Method {} is not mocked", path),
+        );
+        return Ok(grpc_response(vec![], vec![], &status));
+    };
+    let route = &method.route;
+
+    let headers = req
+        .headers()
+        .iter()
+        .filter_map(|(k, v)| Some((k.as_str().to_lowercase(), v.to_str().ok()?.to_string())))
+        .collect();
+
+    let body = match req.into_body().collect().await {
+        Ok(body) => body.to_bytes(),
+        Err(e) => {
+            let status = error_status(STATUS_INTERNAL, format!("Failed to read request: {}", e));
+            return Ok(grpc_response(vec![], vec![], &status));
+        }
+    };
+    let body = match decode_request(&method.descriptor, &body) {
+        Ok(body) => body,
+        Err(e) => {
+            warn!("Invalid gRPC request: {:#}", e);
+            let status = error_status(STATUS_INTERNAL, format!("{:#}", e));
+            return Ok(grpc_response(vec![], vec![], &status));
+        }
+    };
+    let request = RequestData {
+        headers,
+        body: body.to_string(),
+        ..Default::default()
+    };
+
+    // Choose the response and apply its transitions under one lock, so concurrent calls
+    // can't both act on the same scenario state
+    let mut scenarios = service.scenarios.lock();
+    // Safe to unwrap; only routes with responses are registered
+    let response = route
+        .select_response(|transition| scenarios.matches(transition), None)
+        .unwrap();
+    scenarios.advance(&route.scenario);
+    scenarios.advance(&response.scenario.inherit(&route.scenario));
+    drop(scenarios);
+
+    let request_id = Uuid::new_v4();
+    let request_span = info_span!(
+        "Client called mock gRPC method",
+        %request_id,
+        server = %service.server_name,
+        method = %path,
+        response_id = %response.id
+    );
+    let _request_span_guard = request_span.enter();
+
+    let body = prepare_body(response, &request, &service.state);
+    let headers = merge_headers(&service.server_headers, &route.headers, &response.headers);
+    let status = response.grpc.clone().unwrap_or_default();
+
+    let http_response = match encode_messages(&method.descriptor, &body) {
+        Ok(messages) => {
+            debug!(
+                "Responding with {} message(s), gRPC status {}",
+                messages.len(),
+                status.code
+            );
+            grpc_response(headers, messages, &status)
+        }
+        Err(e) => {
+            warn!("Could not encode gRPC response: {:#}", e);
+            let status = error_status(STATUS_INTERNAL, format!("{:#}", e));
+            grpc_response(headers, vec![], &status)
+        }
+    };
+
+    Ok(http_response)
+}
+
+// Routes may be written with or without a leading slash; gRPC request paths always have one
+fn method_path(path: &str) -> String {
+    format!("/{}", path.trim_start_matches('/'))
+}
+
+fn find_method(pool: &DescriptorPool, path: &str) -> Option<MethodDescriptor> {
+    let (service, method) = path.trim_start_matches('/').split_once('/')?;
+    pool.get_service_by_name(service)?
+        .methods()
+        .find(|m| m.name() == method)
+}
+
+fn error_status(code: i32, message: String) -> GrpcStatus {
+    GrpcStatus {
+        code,
+        message,
+        trailers: vec![],
+    }
+}
+
+/*
+Encode a response body as length-prefixed messages. The body is a single JSON message,
+or a JSON list of messages for server streaming methods. An empty body sends no messages.
+*/
+fn encode_messages(method: &MethodDescriptor, body: &str) -> Result<Vec<Bytes>> {
+    if body.trim().is_empty() {
+        return Ok(vec![]);
+    }
+
+    let value: Value = serde_json::from_str(body).context("Response body is not valid JSON")?;
+    let values = match value {
+        Value::Array(values) if method.is_server_streaming() => values,
+        value if !method.is_server_streaming() => vec![value],
+        _ => bail!("Responses of server streaming methods must be a JSON list of messages"),
+    };
+
+    values
+        .into_iter()
+        .map(|value| {
+            let message =
+                DynamicMessage::deserialize(method.output(), value).with_context(|| {
+                    format!("Response is not a valid `{}`", method.output().full_name())
+                })?;
+            Ok(frame(&message.encode_to_vec()))
+        })
+        .collect()
+}
+
+/*
+Decode the length-prefixed messages of a request body into JSON. Client streaming
+methods receive a JSON list of every message sent, others the single message.
+*/
+fn decode_request(method: &MethodDescriptor, mut body: &[u8]) -> Result<Value> {
+    let mut messages = vec![];
+    while !body.is_empty() {
+        if body.len() < 5 {
+            bail!("Truncated gRPC message");
+        }
+        if body[0] != 0 {
+            bail!("Compressed gRPC messages are not supported");
+        }
+
+        let len = u32::from_be_bytes([body[1], body[2], body[3], body[4]]) as usize;
+        let data = body
+            .get(5..5 + len)
+            .ok_or_else(|| anyhow!("Truncated gRPC message"))?;
+        let message = DynamicMessage::decode(method.input(), data)
+            .with_context(|| format!("Request is not a valid `{}`", method.input().full_name()))?;
+        messages.push(serde_json::to_value(&message)?);
+        body = &body[5 + len..];
+    }
+
+    if method.is_client_streaming() {
+        Ok(Value::Array(messages))
+    } else {
+        Ok(messages
+            .into_iter()
+            .next()
+            .unwrap_or(Value::Object(Default::default())))
+    }
+}
+
+fn frame(message: &[u8]) -> Bytes {
+    let mut framed = Vec::with_capacity(message.len() + 5);
+    framed.push(0);
+    framed.extend_from_slice(&(message.len() as u32).to_be_bytes());
+    framed.extend_from_slice(message);
+    Bytes::from(framed)
+}
+
+fn grpc_response(
+    headers: Vec<Header>,
+    messages: Vec<Bytes>,
+    status: &GrpcStatus,
+) -> hyper::Response<GrpcBody> {
+    let mut response_headers = HeaderMap::new();
+    response_headers.insert("content-type", HeaderValue::from_static("application/grpc"));
+    append_headers(&mut response_headers, &headers);
+
+    let mut trailers = HeaderMap::new();
+    trailers.insert("grpc-status", HeaderValue::from(status.code));
+    if !status.message.is_empty() {
+        if let Ok(message) = HeaderValue::from_str(&percent_encode(&status.message)) {
+            trailers.insert("grpc-message", message);
+        }
+    }
+    append_headers(&mut trailers, &status.trailers);
+
+    let frames: Vec<_> = messages
+        .into_iter()
+        .map(Frame::data)
+        .chain([Frame::trailers(trailers)])
+        .map(Ok)
+        .collect();
+
+    let mut response = hyper::Response::new(StreamBody::new(stream::iter(frames)));
+    *response.headers_mut() = response_headers;
+    response
+}
+
+fn append_headers(map: &mut HeaderMap, headers: &[Header]) {
+    for header in headers {
+        match (
+            HeaderName::from_bytes(header.key.as_bytes()),
+            HeaderValue::from_str(&header.value),
+        ) {
+            (Ok(key), Ok(value)) => {
+                map.append(key, value);
+            }
+            _ => warn!("Ignoring invalid gRPC metadata `{}`", &header.key),
+        }
+    }
+}
+
+// grpc-message is percent-encoded, as described by the gRPC HTTP/2 protocol spec
+fn percent_encode(message: &str) -> String {
+    message
+        .bytes()
+        .map(|b| match b {
+            b' '..=b'~' if b != b'%' => (b as char).to_string(),
+            _ => format!("%{:02X}", b),
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn make_pool() -> DescriptorPool {
+        load_descriptors(&GrpcConfig::new("tests/data/greeter.proto")).unwrap()
+    }
+
+    #[test]
+    fn methods_are_found_with_or_without_leading_slash() {
+        let pool = make_pool();
+        let path = method_path("helloworld.Greeter/SayHello");
+        assert_eq!(path, "/helloworld.Greeter/SayHello");
+        assert!(find_method(&pool, &path).is_some());
+        assert!(find_method(&pool, "/helloworld.Greeter/Missing").is_none());
+    }
+
+    #[test]
+    fn messages_round_trip_through_json() {
+        let pool = make_pool();
+        let method = find_method(&pool, "/helloworld.Greeter/SayHello").unwrap();
+
+        let encoded = encode_messages(&method, r#"{"message": "Hello"}"#).unwrap();
+        assert_eq!(encoded.len(), 1);
+        assert!(encode_messages(&method, r#"{"unknown": 1}"#).is_err());
+
+        let request =
+            DynamicMessage::deserialize(method.input(), json!({"name": "World"})).unwrap();
+        let decoded = decode_request(&method, &frame(&request.encode_to_vec())).unwrap();
+        assert_eq!(decoded, json!({"name": "World"}));
+    }
+
+    #[test]
+    fn server_streaming_responses_must_be_lists() {
+        let pool = make_pool();
+        let method = find_method(&pool, "/helloworld.Greeter/SayHellos").unwrap();
+
+        let encoded = encode_messages(&method, r#"[{"message": "a"}, {"message": "b"}]"#).unwrap();
+        assert_eq!(encoded.len(), 2);
+        assert!(encode_messages(&method, r#"{"message": "a"}"#).is_err());
+    }
+
+    #[test]
+    fn grpc_messages_are_percent_encoded() {
+        assert_eq!(percent_encode("Not found: 100%"), "Not found: 100%25");
+        assert_eq!(percent_encode("línea"), "l%C3%ADnea");
+    }
+}
@@ -0,0 +1,468 @@
+use actix_web::http::header::{HeaderName, HeaderValue};
+use actix_web::http::StatusCode;
+use serde_json::Value;
+use std::fmt;
+use std::net::IpAddr;
+
+use super::{Header, Method, Response, ResponseType, Route, Router, Server, ServerInfo};
+
+/// Why a `ServerInfo`, `Route` or `Response` could not be built
+#[derive(Debug, Clone, PartialEq)]
+pub enum BuildError {
+    /// Not a status code an HTTP response can be sent with
+    InvalidStatus(u16),
+    InvalidHeaderName(String),
+    InvalidHeaderValue {
+        key: String,
+        value: String,
+    },
+    InvalidAddress(String),
+    /// Route paths must start with `/`
+    InvalidPath(String),
+    /// The route has no response with this name to make active
+    UnknownResponse {
+        path: String,
+        name: String,
+    },
+    /// Two responses of a route share a name, so cannot be told apart
+    DuplicateResponse {
+        path: String,
+        name: String,
+    },
+    /// Two routes have the same method and path, so only the first would ever be served
+    DuplicateRoute {
+        method: Method,
+        path: String,
+    },
+}
+
+impl fmt::Display for BuildError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            BuildError::InvalidStatus(status) => {
+                write!(f, "`{}` is not a valid HTTP status", status)
+            }
+            BuildError::InvalidHeaderName(key) => write!(f, "`{}` is not a valid header name", key),
+            BuildError::InvalidHeaderValue { key, value } => {
+                write!(f, "`{}` is not a valid value for header `{}`", value, key)
+            }
+            BuildError::InvalidAddress(address) => {
+                write!(f, "`{}` is not a valid IP address", address)
+            }
+            BuildError::InvalidPath(path) => {
+                write!(f, "Route path `{}` does not start with `/`", path)
+            }
+            BuildError::UnknownResponse { path, name } => {
+                write!(f, "Route `{}` has no response named `{}`", path, name)
+            }
+            BuildError::DuplicateResponse { path, name } => {
+                write!(
+                    f,
+                    "Route `{}` has more than one response named `{}`",
+                    path, name
+                )
+            }
+            BuildError::DuplicateRoute { method, path } => {
+                write!(f, "Route {} `{}` is declared more than once", method, path)
+            }
+        }
+    }
+}
+
+impl std::error::Error for BuildError {}
+
+impl Header {
+    /// As `new`, but checking that `key: value` could be sent as an HTTP header
+    pub fn try_new(key: &str, value: &str) -> Result<Self, BuildError> {
+        HeaderName::try_from(key).map_err(|_| BuildError::InvalidHeaderName(key.to_string()))?;
+        HeaderValue::try_from(value).map_err(|_| BuildError::InvalidHeaderValue {
+            key: key.to_string(),
+            value: value.to_string(),
+        })?;
+        Ok(Header::new(key, value))
+    }
+}
+
+/**
+Builds a `Response`, checking each setting as it is given. The first invalid setting
+is reported by `build`, and any settings after it are ignored.
+**/
+#[derive(Debug)]
+pub struct ResponseBuilder {
+    name: String,
+    status: u16,
+    response_type: ResponseType,
+    body: String,
+    headers: Vec<Header>,
+    templated: bool,
+    error: Option<BuildError>,
+}
+
+#[allow(dead_code)]
+impl ResponseBuilder {
+    /// A response named `name`, with status 200 and an empty text body
+    pub fn new(name: &str) -> Self {
+        ResponseBuilder {
+            name: name.to_string(),
+            status: 200,
+            response_type: ResponseType::Text,
+            body: String::new(),
+            headers: vec![],
+            templated: false,
+            error: None,
+        }
+    }
+
+    pub fn status(mut self, status: u16) -> Self {
+        if StatusCode::from_u16(status).is_err() {
+            return self.fail(BuildError::InvalidStatus(status));
+        }
+        self.status = status;
+        self
+    }
+
+    pub fn body(mut self, body: &str) -> Self {
+        self.body = body.to_string();
+        self
+    }
+
+    /// Respond with `value` as a JSON body. No `Content-Type` header is added.
+    pub fn json(mut self, value: &Value) -> Self {
+        self.response_type = ResponseType::Json;
+        self.body = value.to_string();
+        self
+    }
+
+    pub fn response_type(mut self, response_type: ResponseType) -> Self {
+        self.response_type = response_type;
+        self
+    }
+
+    pub fn header(mut self, key: &str, value: &str) -> Self {
+        match Header::try_new(key, value) {
+            Ok(header) => self.headers.push(header),
+            Err(e) => return self.fail(e),
+        }
+        self
+    }
+
+    /// Render the body as a template (see `crate::template`) before serving it
+    pub fn templated(mut self, templated: bool) -> Self {
+        self.templated = templated;
+        self
+    }
+
+    pub fn build(self) -> Result<Response, BuildError> {
+        if let Some(e) = self.error {
+            return Err(e);
+        }
+
+        let mut response = Response::new(&self.name, self.status, self.response_type, &self.body);
+        response.headers = self.headers;
+        response.templated = self.templated;
+        Ok(response)
+    }
+
+    fn fail(mut self, e: BuildError) -> Self {
+        self.error.get_or_insert(e);
+        self
+    }
+}
+
+/**
+Builds a `Route`, checking each setting as it is given. Responses are referred to by name,
+and the first response is active unless another is chosen with `active`.
+**/
+#[derive(Debug)]
+pub struct RouteBuilder {
+    route: Route,
+    active: Option<String>,
+    error: Option<BuildError>,
+}
+
+#[allow(dead_code)]
+impl RouteBuilder {
+    pub fn new(method: Method, path: &str) -> Self {
+        let builder = RouteBuilder {
+            route: Route::new(path, method),
+            active: None,
+            error: None,
+        };
+
+        if path.starts_with('/') {
+            builder
+        } else {
+            builder.fail(BuildError::InvalidPath(path.to_string()))
+        }
+    }
+
+    /// Send `key: value` with every response of this route, unless the response sets the same header
+    pub fn header(mut self, key: &str, value: &str) -> Self {
+        match Header::try_new(key, value) {
+            Ok(header) => self.route.add_header(header),
+            Err(e) => return self.fail(e),
+        }
+        self
+    }
+
+    pub fn response(mut self, response: ResponseBuilder) -> Self {
+        let response = match response.build() {
+            Ok(response) => response,
+            Err(e) => return self.fail(e),
+        };
+        if self.route.responses.iter().any(|r| r.name == response.name) {
+            let e = BuildError::DuplicateResponse {
+                path: self.route.path.clone(),
+                name: response.name,
+            };
+            return self.fail(e);
+        }
+
+        self.route.add_response(response);
+        self
+    }
+
+    /// Serve the response named `name`, which may be added before or after calling this
+    pub fn active(mut self, name: &str) -> Self {
+        self.active = Some(name.to_string());
+        self
+    }
+
+    pub fn build(self) -> Result<Route, BuildError> {
+        if let Some(e) = self.error {
+            return Err(e);
+        }
+
+        let mut route = self.route;
+        let active = match &self.active {
+            Some(name) => route.responses.iter().find(|r| &r.name == name),
+            None => route.responses.first(),
+        };
+        match (active.map(|r| r.id), self.active) {
+            (Some(id), _) => route.set_active_response(id),
+            (None, Some(name)) => {
+                return Err(BuildError::UnknownResponse {
+                    path: route.path,
+                    name,
+                })
+            }
+            (None, None) => {}
+        }
+        Ok(route)
+    }
+
+    fn fail(mut self, e: BuildError) -> Self {
+        self.error.get_or_insert(e);
+        self
+    }
+}
+
+/**
+Builds a `ServerInfo`, checking each setting as it is given. The server and its router are
+bound together by `build`, which also rejects routes declared more than once.
+**/
+#[derive(Debug)]
+pub struct ServerInfoBuilder {
+    server: Server,
+    router: Router,
+    error: Option<BuildError>,
+}
+
+impl Default for ServerInfoBuilder {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[allow(dead_code)]
+impl ServerInfoBuilder {
+    /// A server with the defaults of `ServerInfo::new`, without any routes
+    pub fn new() -> Self {
+        // Safe to unwrap; the default address is always valid
+        let ServerInfo { server, router, .. } = ServerInfo::new().unwrap();
+
+        ServerInfoBuilder {
+            server,
+            router,
+            error: None,
+        }
+    }
+
+    pub fn name(mut self, name: &str) -> Self {
+        self.server.name = name.to_string();
+        self
+    }
+
+    pub fn description(mut self, description: &str) -> Self {
+        self.server.description = description.to_string();
+        self
+    }
+
+    pub fn address(mut self, address: &str) -> Self {
+        match address.parse::<IpAddr>() {
+            Ok(address) => self.server.address = address,
+            Err(_) => return self.fail(BuildError::InvalidAddress(address.to_string())),
+        }
+        self
+    }
+
+    pub fn port(mut self, port: u16) -> Self {
+        self.server.port = port;
+        self
+    }
+
+    /// Send `key: value` with every response, unless a route or response sets the same header
+    pub fn header(mut self, key: &str, value: &str) -> Self {
+        match Header::try_new(key, value) {
+            Ok(header) => {
+                self.server.add_header(header);
+            }
+            Err(e) => return self.fail(e),
+        }
+        self
+    }
+
+    pub fn route(mut self, route: RouteBuilder) -> Self {
+        let route = match route.build() {
+            Ok(route) => route,
+            Err(e) => return self.fail(e),
+        };
+        let duplicate = self
+            .router
+            .routes
+            .iter()
+            .any(|r| r.method == route.method && r.path == route.path);
+        if duplicate {
+            let e = BuildError::DuplicateRoute {
+                method: route.method,
+                path: route.path,
+            };
+            return self.fail(e);
+        }
+
+        self.router.add_route(route);
+        self
+    }
+
+    pub fn build(self) -> Result<ServerInfo, BuildError> {
+        if let Some(e) = self.error {
+            return Err(e);
+        }
+
+        let mut router = self.router;
+        router.bind_server(&self.server);
+        Ok(ServerInfo {
+            server: self.server,
+            router,
+            scenarios: vec![],
+        })
+    }
+
+    fn fail(mut self, e: BuildError) -> Self {
+        self.error.get_or_insert(e);
+        self
+    }
+}
+
+#[allow(dead_code)]
+impl ServerInfo {
+    pub fn builder() -> ServerInfoBuilder {
+        ServerInfoBuilder::new()
+    }
+}
+
+#[allow(dead_code)]
+impl Route {
+    pub fn builder(method: Method, path: &str) -> RouteBuilder {
+        RouteBuilder::new(method, path)
+    }
+}
+
+#[allow(dead_code)]
+impl Response {
+    pub fn builder(name: &str) -> ResponseBuilder {
+        ResponseBuilder::new(name)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn builds_server_with_active_response() {
+        let serverinfo = ServerInfo::builder()
+            .name("Users")
+            .address("0.0.0.0")
+            .port(9000)
+            .header("X-Service", "users")
+            .route(
+                Route::builder(Method::GET, "/users/:id")
+                    .response(Response::builder("Found").json(&json!({ "id": 1 })))
+                    .response(Response::builder("Missing").status(404))
+                    .active("Missing"),
+            )
+            .build()
+            .unwrap();
+
+        assert_eq!(serverinfo.router.server_id, Some(serverinfo.server.id));
+        assert_eq!(serverinfo.server.router_id, serverinfo.router.id);
+        assert_eq!(serverinfo.server.port, 9000);
+        let route = &serverinfo.router.routes[0];
+        assert_eq!(route.get_active_response().unwrap().status, 404);
+        assert_eq!(route.responses[0].response_type, ResponseType::Json);
+    }
+
+    #[test]
+    fn reports_first_invalid_setting() {
+        let error = ServerInfo::builder()
+            .address("localhost")
+            .header("Bad Header", "value")
+            .build()
+            .unwrap_err();
+        assert_eq!(error, BuildError::InvalidAddress("localhost".into()));
+
+        let error = Route::builder(Method::GET, "/")
+            .response(Response::builder("Ok").status(1000))
+            .build()
+            .unwrap_err();
+        assert_eq!(error, BuildError::InvalidStatus(1000));
+
+        let error = Response::builder("Ok")
+            .header("X-Trace", "line\nbreak")
+            .build()
+            .unwrap_err();
+        assert!(matches!(error, BuildError::InvalidHeaderValue { .. }));
+
+        let error = Route::builder(Method::GET, "users").build().unwrap_err();
+        assert_eq!(error, BuildError::InvalidPath("users".into()));
+    }
+
+    #[test]
+    fn rejects_unknown_and_duplicate_names() {
+        let error = Route::builder(Method::GET, "/")
+            .response(Response::builder("Ok"))
+            .active("Missing")
+            .build()
+            .unwrap_err();
+        assert_eq!(
+            error.to_string(),
+            "Route `/` has no response named `Missing`"
+        );
+
+        let error = Route::builder(Method::GET, "/")
+            .response(Response::builder("Ok"))
+            .response(Response::builder("Ok").status(500))
+            .build()
+            .unwrap_err();
+        assert!(matches!(error, BuildError::DuplicateResponse { .. }));
+
+        let error = ServerInfo::builder()
+            .route(Route::builder(Method::GET, "/"))
+            .route(Route::builder(Method::GET, "/"))
+            .build()
+            .unwrap_err();
+        assert!(matches!(error, BuildError::DuplicateRoute { .. }));
+    }
+}
@@ -0,0 +1,71 @@
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+use crate::template::json_contains;
+
+/**
+Serve a `Route` as a GraphQL endpoint. Requests are parsed as GraphQL, either from a
+JSON body or, for `GET`, from the `query`, `operationName` and `variables` query params,
+and answered by the first `Response` whose `graphql` rule matches the operation.
+
+If `schema` is set to the path of an SDL file, operations which no response matches
+are answered with data generated from the schema, rather than the active response.
+**/
+#[derive(Debug, Clone, Default, Deserialize, Serialize, PartialEq)]
+pub struct GraphqlConfig {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub schema: Option<String>,
+}
+
+/// Selects a `Response` for GraphQL operations with this name and/or variables
+#[derive(Debug, Clone, Default, Deserialize, Serialize, PartialEq)]
+pub struct GraphqlMatch {
+    #[serde(
+        rename = "operationName",
+        default,
+        skip_serializing_if = "Option::is_none"
+    )]
+    pub operation_name: Option<String>,
+    /// Matches any variables containing at least these fields and values
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub variables: Option<Value>,
+}
+
+impl GraphqlMatch {
+    pub fn matches(&self, operation_name: Option<&str>, variables: &Value) -> bool {
+        let name_met = self
+            .operation_name
+            .as_deref()
+            .is_none_or(|name| operation_name == Some(name));
+        let variables_met = self
+            .variables
+            .as_ref()
+            .is_none_or(|expected| json_contains(variables, expected));
+
+        name_met && variables_met
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn matches_by_operation_name_and_variables() {
+        let rule: GraphqlMatch =
+            serde_json::from_str(r#"{ "operationName": "GetUser", "variables": { "id": "1" } }"#)
+                .expect("Unable to parse JSON.");
+
+        assert!(rule.matches(Some("GetUser"), &json!({ "id": "1", "full": true })));
+        assert!(!rule.matches(Some("GetUser"), &json!({ "id": "2" })));
+        assert!(!rule.matches(Some("ListUsers"), &json!({ "id": "1" })));
+        assert!(!rule.matches(None, &json!({ "id": "1" })));
+
+        let any_name = GraphqlMatch {
+            operation_name: None,
+            variables: Some(json!({ "id": "1" })),
+        };
+        assert!(any_name.matches(None, &json!({ "id": "1" })));
+    }
+}
@@ -0,0 +1,70 @@
+use serde::{Deserialize, Serialize};
+
+use super::Header;
+
+/**
+Serve gRPC from the services of a `.proto` file, rather than plain HTTP. Each `Route`
+is a fully-qualified method, e.g. `/helloworld.Greeter/SayHello`, and each `Response`
+body is the JSON encoding of the method's output message (a JSON list of them, for
+server streaming methods).
+**/
+#[derive(Debug, Clone, Deserialize, Serialize, PartialEq)]
+pub struct GrpcConfig {
+    pub proto: String,
+    /// Directories to resolve imports from. Defaults to the directory containing `proto`.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub includes: Vec<String>,
+}
+
+impl GrpcConfig {
+    #[allow(unused)]
+    pub fn new(proto: &str) -> Self {
+        GrpcConfig {
+            proto: proto.to_string(),
+            includes: vec![],
+        }
+    }
+}
+
+/// The gRPC status sent in the trailers of a `Response`. Defaults to `OK`.
+#[derive(Debug, Clone, Default, Deserialize, Serialize, PartialEq)]
+pub struct GrpcStatus {
+    #[serde(default)]
+    pub code: i32,
+    #[serde(default, skip_serializing_if = "String::is_empty")]
+    pub message: String,
+    /// Additional trailing metadata
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub trailers: Vec<Header>,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn can_deserialize_grpc_status() {
+        let json = r###"
+        {
+            "code": 5,
+            "message": "No such user",
+            "trailers": [
+                {
+                    "id": "5ea28783-d770-4754-98e9-749aab005511",
+                    "key": "x-request-cost",
+                    "value": "3",
+                    "active": true
+                }
+            ]
+        }
+        "###;
+
+        let status: GrpcStatus = serde_json::from_str(json).expect("Unable to parse JSON.");
+        assert_eq!(status.code, 5);
+        assert_eq!(status.message, "No such user");
+        assert_eq!(status.trailers[0].key, "x-request-cost");
+
+        let status: GrpcStatus = serde_json::from_str("{}").unwrap();
+        assert_eq!(status, GrpcStatus::default());
+    }
+}
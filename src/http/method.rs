@@ -1,5 +1,6 @@
 use core::fmt;
 use serde::{Deserialize, Serialize};
+use std::str::FromStr;
 
 #[allow(clippy::upper_case_acronyms)]
 #[derive(Clone, Debug, Deserialize, Serialize, PartialEq)]
@@ -31,6 +32,26 @@ impl fmt::Display for Method {
     }
 }
 
+impl FromStr for Method {
+    type Err = String;
+
+    /// Parse a method name, ignoring case
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_uppercase().as_str() {
+            "GET" => Ok(Method::GET),
+            "DELETE" => Ok(Method::DELETE),
+            "POST" => Ok(Method::POST),
+            "PUT" => Ok(Method::PUT),
+            "HEAD" => Ok(Method::HEAD),
+            "CONNECT" => Ok(Method::CONNECT),
+            "OPTIONS" => Ok(Method::OPTIONS),
+            "TRACE" => Ok(Method::TRACE),
+            "PATCH" => Ok(Method::PATCH),
+            _ => Err(format!("`{}` is not a supported HTTP method", s)),
+        }
+    }
+}
+
 impl From<actix_web::http::Method> for Method {
     fn from(method: actix_web::http::Method) -> Self {
         match method {
@@ -1,17 +1,41 @@
+mod builder;
+mod graphql;
+mod grpc;
 mod header;
 mod method;
+mod resource;
 mod response;
+mod response_stream;
 mod response_type;
 mod route;
 mod router;
+mod scenario;
 mod server;
 mod serverinfo;
+mod sse;
+mod state_action;
+mod tls;
+mod validation;
+mod websocket;
+mod workspace;
 
+pub use builder::*;
+pub use graphql::*;
+pub use grpc::*;
 pub use header::*;
 pub use method::*;
+pub use resource::*;
 pub use response::*;
+pub use response_stream::*;
 pub use response_type::*;
 pub use route::*;
 pub use router::*;
+pub use scenario::*;
 pub use server::*;
 pub use serverinfo::*;
+pub use sse::*;
+pub use state_action::*;
+pub use tls::*;
+pub use validation::*;
+pub use websocket::*;
+pub use workspace::*;
@@ -0,0 +1,74 @@
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+const DEFAULT_ID_FIELD: &str = "id";
+
+/**
+Declares a REST resource, for which list, get, create, update and delete
+endpoints are generated and backed by an in-memory collection:
+
+- `GET    {basePath}`: list items, paginated with the `page` and `per_page` query params
+- `POST   {basePath}`: create an item
+- `GET    {basePath}/:id`: fetch an item
+- `PUT    {basePath}/:id`: replace an item
+- `DELETE {basePath}/:id`: delete an item
+
+The collection starts out containing the `seed` items.
+**/
+#[derive(Debug, Clone, Deserialize, Serialize, PartialEq)]
+pub struct Resource {
+    pub name: String,
+    #[serde(rename = "basePath")]
+    pub base_path: String,
+    /// The item field used to identify items within the collection
+    #[serde(rename = "idField", default = "default_id_field")]
+    pub id_field: String,
+    #[serde(default)]
+    pub seed: Vec<Value>,
+}
+
+impl Resource {
+    #[allow(unused)]
+    pub fn new(name: &str, base_path: &str) -> Self {
+        Resource {
+            name: name.to_string(),
+            base_path: base_path.to_string(),
+            id_field: default_id_field(),
+            seed: vec![],
+        }
+    }
+
+    /// The path of a single item within this resource
+    pub fn item_path(&self) -> String {
+        format!("{}/:id", self.base_path.trim_end_matches('/'))
+    }
+}
+
+fn default_id_field() -> String {
+    DEFAULT_ID_FIELD.to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn can_deserialize_resource() {
+        let json = r###"
+        {
+            "name": "users",
+            "basePath": "/api/users/",
+            "seed": [
+                { "id": 1, "name": "Alice" },
+                { "id": 2, "name": "Bob" }
+            ]
+        }
+        "###;
+
+        let resource: Resource = serde_json::from_str(json).expect("Unable to parse JSON.");
+        assert_eq!(resource.name, "users");
+        assert_eq!(resource.id_field, "id");
+        assert_eq!(resource.seed.len(), 2);
+        assert_eq!(resource.item_path(), "/api/users/:id");
+    }
+}
@@ -1,9 +1,13 @@
+use anyhow::{Context, Result};
 use serde::{Deserialize, Serialize};
 use uuid::Uuid;
 
-use super::{Header, ResponseType};
+use super::{
+    ClientCertMatch, GraphqlMatch, GrpcStatus, Header, ResponseStream, ResponseType,
+    ScenarioTransition, SseEvent, StateAction,
+};
 
-#[derive(Debug, Deserialize, Serialize)]
+#[derive(Debug, Clone, Deserialize, Serialize)]
 pub struct Response {
     pub id: Uuid,
     pub name: String,
@@ -13,6 +17,34 @@ pub struct Response {
     pub response_type: ResponseType,
     pub active: bool,
     pub headers: Vec<Header>,
+    /// Render the response body as a template (see `crate::template`) before serving it
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub templated: bool,
+    /// Mutations applied to the server's state store whenever this response is served
+    #[serde(
+        rename = "stateActions",
+        default,
+        skip_serializing_if = "Vec::is_empty"
+    )]
+    pub state_actions: Vec<StateAction>,
+    #[serde(flatten)]
+    pub scenario: ScenarioTransition,
+    /// Only serve this response to clients presenting a matching TLS certificate
+    #[serde(
+        rename = "clientCert",
+        default,
+        skip_serializing_if = "Option::is_none"
+    )]
+    pub client_cert: Option<ClientCertMatch>,
+    /// If set, the body is streamed from this rather than taken from `response`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub stream: Option<ResponseStream>,
+    /// Status and trailers to send when serving gRPC
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub grpc: Option<GrpcStatus>,
+    /// On a GraphQL route, only serve this response to operations matching this rule
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub graphql: Option<GraphqlMatch>,
 }
 
 impl Default for Response {
@@ -25,6 +57,13 @@ impl Default for Response {
             response_type: ResponseType::default(),
             active: true,
             headers: vec![],
+            templated: false,
+            state_actions: vec![],
+            scenario: ScenarioTransition::default(),
+            client_cert: None,
+            stream: None,
+            grpc: None,
+            graphql: None,
         }
     }
 }
@@ -45,9 +84,41 @@ impl Response {
         self.response.clone()
     }
 
+    /// Parse the body of an `sse` response as its list of events
+    pub fn get_events(&self) -> Result<Vec<SseEvent>> {
+        SseEvent::parse_list(&self.response)
+            .with_context(|| format!("Response `{}` is not a valid list of SSE events", self.name))
+    }
+
     #[allow(unused)]
     pub fn add_header(&mut self, header: Header) -> &mut Self {
         self.headers.push(header);
         self
     }
+
+    #[allow(unused)]
+    /// Adds a `StateAction` to be applied each time this response is served
+    pub fn add_state_action(&mut self, action: StateAction) -> &mut Self {
+        self.state_actions.push(action);
+        self
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn unused_options_are_not_serialized() {
+        let json = serde_json::to_value(Response::default()).unwrap();
+        let object = json.as_object().unwrap();
+        assert!(!object.contains_key("templated"));
+        assert!(!object.contains_key("stateActions"));
+
+        let response = Response {
+            templated: true,
+            ..Response::default()
+        };
+        assert_eq!(serde_json::to_value(response).unwrap()["templated"], true);
+    }
 }
@@ -0,0 +1,73 @@
+use serde::{Deserialize, Serialize};
+
+/// A piece of a streamed response body, sent after waiting `delayMs`
+#[derive(Debug, Clone, Default, Deserialize, Serialize, PartialEq)]
+pub struct ResponseChunk {
+    pub data: String,
+    #[serde(rename = "delayMs", default)]
+    pub delay_ms: u64,
+}
+
+/**
+A body generated by rendering `template` once per record, for example to mock an
+NDJSON API. As well as the usual template expressions, `{{index}}` renders the
+zero-based index of the record being generated.
+**/
+#[derive(Debug, Clone, Deserialize, Serialize, PartialEq)]
+pub struct RecordStream {
+    pub count: u64,
+    pub template: String,
+    /// Appended to every record
+    #[serde(default = "default_separator")]
+    pub separator: String,
+    /// How long to wait before sending each record
+    #[serde(rename = "delayMs", default)]
+    pub delay_ms: u64,
+}
+
+fn default_separator() -> String {
+    "\n".to_string()
+}
+
+/// A response body sent incrementally with `Transfer-Encoding: chunked`, rather than all at once
+#[derive(Debug, Clone, Deserialize, Serialize, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum ResponseStream {
+    /// Send each chunk in turn
+    Chunks(Vec<ResponseChunk>),
+    /// Generate the body from a template
+    Records(RecordStream),
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn can_deserialize_response_streams() {
+        let json =
+            r###"{ "chunks": [{ "data": "first" }, { "data": "second", "delayMs": 100 }] }"###;
+        let stream: ResponseStream = serde_json::from_str(json).expect("Unable to parse JSON.");
+        assert_eq!(
+            stream,
+            ResponseStream::Chunks(vec![
+                ResponseChunk {
+                    data: "first".into(),
+                    delay_ms: 0
+                },
+                ResponseChunk {
+                    data: "second".into(),
+                    delay_ms: 100
+                },
+            ])
+        );
+
+        let json = r###"{ "records": { "count": 3, "template": "{\"id\": {{index}}}" } }"###;
+        let stream: ResponseStream = serde_json::from_str(json).expect("Unable to parse JSON.");
+        let ResponseStream::Records(records) = stream else {
+            panic!("Expected a record stream");
+        };
+        assert_eq!(records.count, 3);
+        assert_eq!(records.separator, "\n");
+    }
+}
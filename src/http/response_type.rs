@@ -1,10 +1,13 @@
 use serde::{Deserialize, Serialize};
 
-#[derive(Debug, Default, Deserialize, Serialize, PartialEq)]
+#[derive(Debug, Clone, Default, Deserialize, Serialize, PartialEq)]
 pub enum ResponseType {
     #[default]
     #[serde(rename = "text")]
     Text,
     #[serde(rename = "json")]
     Json,
+    /// Server-Sent Events. The response body is a JSON list of `SseEvent`s, streamed as `text/event-stream`.
+    #[serde(rename = "sse")]
+    Sse,
 }
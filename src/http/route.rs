@@ -1,19 +1,35 @@
 use serde::de::Deserializer;
 use serde::Deserialize;
 use serde::Serialize;
+use serde_json::Value;
 use uuid::Uuid;
 
+use super::ClientIdentity;
+use super::GraphqlConfig;
 use super::Header;
 use super::Method;
+use super::RequestValidation;
 use super::Response;
+use super::ScenarioTransition;
+use super::WebSocketScript;
 
-#[derive(Debug, Serialize)]
+#[derive(Debug, Clone, Serialize)]
 pub struct Route {
     pub id: Uuid,
     pub path: String,
     pub method: Method,
     pub headers: Vec<Header>,
     pub responses: Vec<Response>,
+    #[serde(flatten)]
+    pub scenario: ScenarioTransition,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub validation: Option<RequestValidation>,
+    /// If set, this route accepts WebSocket connections and follows the script, rather than serving `responses`
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub websocket: Option<WebSocketScript>,
+    /// If set, requests are parsed as GraphQL and responses selected by operation
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub graphql: Option<GraphqlConfig>,
 
     #[serde(rename = "activeResponse")] // rename handled manually in deserialize() below
     active_response: Option<Uuid>,
@@ -31,6 +47,10 @@ impl Route {
             method,
             headers: vec![],
             responses: vec![],
+            scenario: ScenarioTransition::default(),
+            validation: None,
+            websocket: None,
+            graphql: None,
             active_response_index: None,
         }
     }
@@ -51,6 +71,73 @@ impl Route {
             .and_then(|idx| self.responses.get(idx))
     }
 
+    /**
+    Pick the response to serve, given a predicate reporting whether a scenario
+    transition's requirement is currently met, and the identity of the client's
+    TLS certificate, if it presented one.
+
+    The first response with a `requiredScenarioState` and/or `clientCert` rule,
+    all of which are met, wins; if there are none, this falls back to the active response.
+    **/
+    pub fn select_response<F>(
+        &self,
+        matches: F,
+        client: Option<&ClientIdentity>,
+    ) -> Option<&Response>
+    where
+        F: Fn(&ScenarioTransition) -> bool,
+    {
+        self.select_ruled_response(None, matches, client)
+            .or_else(|| self.get_active_response())
+    }
+
+    /**
+    As `select_response`, for a GraphQL operation, but without falling back to the
+    active response. Responses may also have a `graphql` rule, met by operations with
+    a matching name and variables.
+    **/
+    pub fn select_graphql_response<F>(
+        &self,
+        operation_name: Option<&str>,
+        variables: &Value,
+        matches: F,
+        client: Option<&ClientIdentity>,
+    ) -> Option<&Response>
+    where
+        F: Fn(&ScenarioTransition) -> bool,
+    {
+        self.select_ruled_response(Some((operation_name, variables)), matches, client)
+    }
+
+    // The first response with rules, all of which are met. A `graphql` rule is never met
+    // outside of a GraphQL `operation`.
+    fn select_ruled_response<F>(
+        &self,
+        operation: Option<(Option<&str>, &Value)>,
+        matches: F,
+        client: Option<&ClientIdentity>,
+    ) -> Option<&Response>
+    where
+        F: Fn(&ScenarioTransition) -> bool,
+    {
+        self.responses.iter().find(|r| {
+            let has_rules = r.scenario.required_state.is_some()
+                || r.client_cert.is_some()
+                || r.graphql.is_some();
+            let scenario_met =
+                r.scenario.required_state.is_none() || matches(&r.scenario.inherit(&self.scenario));
+            let client_met = r
+                .client_cert
+                .as_ref()
+                .is_none_or(|rule| rule.matches(client));
+            let graphql_met = r.graphql.as_ref().is_none_or(|rule| {
+                operation.is_some_and(|(name, variables)| rule.matches(name, variables))
+            });
+
+            has_rules && scenario_met && client_met && graphql_met
+        })
+    }
+
     /**
     Updates the internal references to the wanted active response, such that
     subsequent calls to `get_active_response()` would return that response.
@@ -76,6 +163,28 @@ impl Route {
         self.responses.push(response);
     }
 
+    /**
+    Removes the response with the given id, returning it. If it was the active
+    response, the route falls back to serving its first remaining response.
+    **/
+    #[allow(unused)]
+    pub fn remove_response(&mut self, id: Uuid) -> Option<Response> {
+        let index = self.responses.iter().position(|r| r.id == id)?;
+        let removed = self.responses.remove(index);
+
+        match self.active_response {
+            Some(active) if active == id => {
+                self.active_response = None;
+                self.active_response_index = None;
+            }
+            // Indices after the removed response have shifted
+            Some(active) => self.set_active_response(active),
+            None => {}
+        }
+
+        Some(removed)
+    }
+
     #[allow(unused)]
     pub fn add_header(&mut self, header: Header) {
         self.headers.push(header);
@@ -96,6 +205,14 @@ impl<'de> Deserialize<'de> for Route {
             method: Method,
             headers: Vec<Header>,
             responses: Vec<Response>,
+            #[serde(flatten)]
+            scenario: ScenarioTransition,
+            #[serde(default)]
+            validation: Option<RequestValidation>,
+            #[serde(default)]
+            websocket: Option<WebSocketScript>,
+            #[serde(default)]
+            graphql: Option<GraphqlConfig>,
         }
 
         let route_helper = RouteHelper::deserialize(deserializer)?;
@@ -107,6 +224,10 @@ impl<'de> Deserialize<'de> for Route {
             method: route_helper.method,
             headers: route_helper.headers,
             responses: route_helper.responses,
+            scenario: route_helper.scenario,
+            validation: route_helper.validation,
+            websocket: route_helper.websocket,
+            graphql: route_helper.graphql,
             active_response_index: None,
         };
 
@@ -121,7 +242,7 @@ impl<'de> Deserialize<'de> for Route {
 
 #[cfg(test)]
 mod tests {
-    use crate::http::ResponseType;
+    use crate::http::{ClientCertMatch, ResponseType};
 
     use super::*;
     use uuid::uuid;
@@ -185,6 +306,26 @@ mod tests {
         );
     }
 
+    #[test]
+    fn removing_responses_keeps_active_response() {
+        let mut route = Route::new("/", Method::GET);
+        let responses = ["First", "Second", "Third"]
+            .map(|name| Response::new(name, 200, ResponseType::Text, name));
+        let ids = responses.each_ref().map(|r| r.id);
+        for response in responses {
+            route.add_response(response);
+        }
+
+        route.set_active_response(ids[2]);
+        assert_eq!(route.remove_response(ids[0]).unwrap().name, "First");
+        assert_eq!(route.get_active_response().unwrap().id, ids[2]);
+
+        // Removing the active response falls back to the first remaining one
+        route.remove_response(ids[2]);
+        assert_eq!(route.get_active_response().unwrap().id, ids[1]);
+        assert!(route.remove_response(ids[2]).is_none());
+    }
+
     #[test]
     fn can_default_active_response() {
         let response1 = Response::new("", 200, ResponseType::Text, "hello");
@@ -204,6 +345,62 @@ mod tests {
         assert_eq!(new_active_response.unwrap().id, resp2_id);
     }
 
+    #[test]
+    fn select_response_prefers_responses_whose_scenario_state_matches() {
+        let active = Response::new("", 200, ResponseType::Text, "created");
+        let active_id = active.id;
+        let mut paid = Response::new("", 200, ResponseType::Text, "paid");
+        paid.scenario.required_state = Some("paid".into());
+        let paid_id = paid.id;
+
+        let mut route = Route::new("/order", Method::GET);
+        route.scenario.scenario = Some("order".into());
+        route.add_response(paid);
+        route.add_response(active);
+        route.set_active_response(active_id);
+
+        // Responses inherit the route's scenario name
+        let selected = route.select_response(
+            |t| {
+                t.scenario.as_deref() == Some("order")
+                    && t.required_state.as_deref() == Some("paid")
+            },
+            None,
+        );
+        assert_eq!(selected.unwrap().id, paid_id);
+
+        let selected = route.select_response(|_| false, None);
+        assert_eq!(selected.unwrap().id, active_id);
+    }
+
+    #[test]
+    fn select_response_prefers_responses_whose_client_cert_matches() {
+        let active = Response::new("", 200, ResponseType::Text, "anonymous");
+        let active_id = active.id;
+        let mut tenant = Response::new("", 200, ResponseType::Text, "tenant-a");
+        tenant.client_cert = Some(ClientCertMatch {
+            subject: Some("tenant-a".into()),
+            san: None,
+        });
+        let tenant_id = tenant.id;
+
+        let mut route = Route::new("/whoami", Method::GET);
+        route.add_response(tenant);
+        route.add_response(active);
+        route.set_active_response(active_id);
+
+        let identity = ClientIdentity {
+            subject: "CN=tenant-a".into(),
+            common_name: Some("tenant-a".into()),
+            sans: vec![],
+        };
+        let selected = route.select_response(|_| true, Some(&identity));
+        assert_eq!(selected.unwrap().id, tenant_id);
+
+        let selected = route.select_response(|_| true, None);
+        assert_eq!(selected.unwrap().id, active_id);
+    }
+
     #[test]
     fn set_active_response_updates_all_internal_references() {
         let mut route = Route::new("/", Method::GET);
@@ -1,7 +1,7 @@
 use serde::{Deserialize, Serialize};
 use uuid::Uuid;
 
-use super::{route::Route, Server};
+use super::{route::Route, Resource, Server};
 
 #[allow(dead_code)]
 #[derive(Debug, Deserialize, Serialize)]
@@ -10,6 +10,8 @@ pub struct Router {
     #[serde(rename = "serverId")]
     pub server_id: Option<Uuid>,
     pub routes: Vec<Route>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub resources: Vec<Resource>,
 }
 
 impl Router {
@@ -19,6 +21,7 @@ impl Router {
             id: Uuid::new_v4(),
             server_id,
             routes: vec![],
+            resources: vec![],
         }
     }
 
@@ -35,6 +38,20 @@ impl Router {
         self.routes.push(route);
         self
     }
+
+    #[allow(dead_code)]
+    /// Removes the `mockerize_cli::http::Route` with the given id, returning it
+    pub fn remove_route(&mut self, id: Uuid) -> Option<Route> {
+        let index = self.routes.iter().position(|r| r.id == id)?;
+        Some(self.routes.remove(index))
+    }
+
+    #[allow(dead_code)]
+    /// Adds a new `mockerize_cli::http::Resource` to the `Router` instance
+    pub fn add_resource(&mut self, resource: Resource) -> &mut Self {
+        self.resources.push(resource);
+        self
+    }
 }
 
 #[cfg(test)]
@@ -0,0 +1,90 @@
+use serde::{Deserialize, Serialize};
+
+/// The state every scenario starts in, unless declared otherwise in `ServerInfo`
+pub const DEFAULT_SCENARIO_STATE: &str = "Started";
+
+/// A named state machine, used to model multi-step flows such as
+/// "order created → paid → shipped"
+#[derive(Debug, Clone, Deserialize, Serialize, PartialEq)]
+pub struct Scenario {
+    pub name: String,
+    #[serde(rename = "initialState", default = "default_scenario_state")]
+    pub initial_state: String,
+}
+
+impl Scenario {
+    #[allow(unused)]
+    pub fn new(name: &str, initial_state: &str) -> Self {
+        Scenario {
+            name: name.to_string(),
+            initial_state: initial_state.to_string(),
+        }
+    }
+}
+
+fn default_scenario_state() -> String {
+    DEFAULT_SCENARIO_STATE.to_string()
+}
+
+/**
+Ties a `Route` or `Response` to a named scenario.
+
+If `required_state` is set, the route or response is only used while the scenario is
+in that state. If `new_state` is set, the scenario transitions to it once served.
+A `Response` which does not name a scenario inherits the one named by its `Route`.
+**/
+#[derive(Debug, Clone, Default, Deserialize, Serialize, PartialEq)]
+pub struct ScenarioTransition {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub scenario: Option<String>,
+    #[serde(
+        rename = "requiredScenarioState",
+        skip_serializing_if = "Option::is_none"
+    )]
+    pub required_state: Option<String>,
+    #[serde(rename = "newScenarioState", skip_serializing_if = "Option::is_none")]
+    pub new_state: Option<String>,
+}
+
+impl ScenarioTransition {
+    /// Use `fallback` as the scenario name, unless one is already set
+    pub fn inherit(&self, fallback: &ScenarioTransition) -> ScenarioTransition {
+        ScenarioTransition {
+            scenario: self.scenario.clone().or_else(|| fallback.scenario.clone()),
+            ..self.clone()
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn can_deserialize_scenario_with_default_initial_state() {
+        let scenario: Scenario =
+            serde_json::from_str(r#"{ "name": "order" }"#).expect("Unable to parse JSON.");
+        assert_eq!(scenario, Scenario::new("order", DEFAULT_SCENARIO_STATE));
+    }
+
+    #[test]
+    fn inherit_only_fills_in_missing_scenario_name() {
+        let route = ScenarioTransition {
+            scenario: Some("order".into()),
+            ..Default::default()
+        };
+
+        let response = ScenarioTransition {
+            required_state: Some("paid".into()),
+            ..Default::default()
+        };
+        assert_eq!(response.inherit(&route).scenario, Some("order".into()));
+        assert_eq!(response.inherit(&route).required_state, Some("paid".into()));
+
+        let response = ScenarioTransition {
+            scenario: Some("payment".into()),
+            ..Default::default()
+        };
+        assert_eq!(response.inherit(&route).scenario, Some("payment".into()));
+    }
+}
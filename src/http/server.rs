@@ -5,7 +5,7 @@ use std::fmt;
 use std::net::IpAddr;
 use uuid::Uuid;
 
-use super::Header;
+use super::{GrpcConfig, Header, TlsConfig};
 
 #[derive(Debug, Deserialize, Serialize)]
 pub struct Server {
@@ -18,6 +18,41 @@ pub struct Server {
     pub name: String,
     pub description: String,
     pub headers: Vec<Header>,
+    /// If set, the server only accepts HTTPS connections
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub tls: Option<TlsConfig>,
+    /// Accept HTTP/2 as well as HTTP/1.x: negotiated via ALPN over TLS, or with prior knowledge (h2c)
+    /// over cleartext
+    #[serde(default = "default_http2", skip_serializing_if = "is_true")]
+    pub http2: bool,
+    /// If set, the server serves gRPC over cleartext HTTP/2 (h2c) instead of plain HTTP
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub grpc: Option<GrpcConfig>,
+    /// If set, the server listens on this Unix domain socket instead of `address` and `port`
+    #[serde(
+        rename = "unixSocket",
+        default,
+        skip_serializing_if = "Option::is_none"
+    )]
+    pub unix_socket: Option<String>,
+    /// Additional address and port pairs to accept connections on, alongside `address` and `port`
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub listeners: Vec<ServerListener>,
+    /// Hostnames this server answers to, by `Host` header or TLS SNI, when sharing a port with others.
+    /// A leading `*.` matches any single label, e.g. `*.example.com`.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub hosts: Vec<String>,
+}
+
+/// An additional address and port for a `Server` to accept connections on
+#[derive(Debug, Clone, Deserialize, Serialize, PartialEq)]
+pub struct ServerListener {
+    #[serde(deserialize_with = "deserialize_ipaddr")]
+    pub address: IpAddr,
+    pub port: u16,
+    /// Whether to serve HTTPS on this listener. Defaults to whether the server has `tls` configured.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub tls: Option<bool>,
 }
 
 impl Server {
@@ -33,9 +68,20 @@ impl Server {
             name: String::default(),
             description: String::default(),
             headers: vec![],
+            tls: None,
+            http2: true,
+            grpc: None,
+            unix_socket: None,
+            listeners: vec![],
+            hosts: vec![],
         })
     }
 
+    /// Whether a request for `host` should be served by this `Server`, according to its `hosts`
+    pub fn answers_to(&self, host: &str) -> bool {
+        self.hosts.iter().any(|pattern| host_matches(pattern, host))
+    }
+
     #[allow(dead_code)]
     /// Modify the "name" field of this `Server`
     pub fn set_name(&mut self, name: &str) -> &mut Self {
@@ -60,6 +106,25 @@ impl Server {
     }
 }
 
+/// Whether `host` matches a hostname `pattern`, ignoring case. `*.` at the start of a pattern matches one label.
+pub fn host_matches(pattern: &str, host: &str) -> bool {
+    let host = host.strip_suffix('.').unwrap_or(host);
+    match pattern.strip_prefix("*.") {
+        Some(suffix) => host
+            .split_once('.')
+            .is_some_and(|(label, rest)| !label.is_empty() && rest.eq_ignore_ascii_case(suffix)),
+        None => host.eq_ignore_ascii_case(pattern),
+    }
+}
+
+fn default_http2() -> bool {
+    true
+}
+
+fn is_true(value: &bool) -> bool {
+    *value
+}
+
 // Custom deserialization function for IpAddr
 fn deserialize_ipaddr<'de, D>(deserializer: D) -> Result<IpAddr, D::Error>
 where
@@ -126,6 +191,86 @@ mod tests {
             uuid!("d49427b0-8d2f-47cc-a5b4-c3754c53583b")
         );
         assert_eq!(server.description, "Just an example of a server in JSON.");
+        assert_eq!(server.tls, None);
+        assert!(server.http2);
+        assert_eq!(server.unix_socket, None);
+        assert!(server.listeners.is_empty());
+        assert!(server.hosts.is_empty());
+    }
+
+    #[test]
+    fn matches_hosts_and_wildcards() {
+        let mut server = Server::new(Uuid::new_v4(), "127.0.0.1", 443).unwrap();
+        server.hosts = vec!["api.example.com".into(), "*.users.example.com".into()];
+
+        assert!(server.answers_to("api.example.com"));
+        assert!(server.answers_to("API.Example.com."));
+        assert!(server.answers_to("eu.users.example.com"));
+        assert!(!server.answers_to("users.example.com"));
+        assert!(!server.answers_to("a.eu.users.example.com"));
+        assert!(!server.answers_to("example.com"));
+    }
+
+    #[test]
+    fn http2_is_only_serialized_when_disabled() {
+        let mut server = Server::new(Uuid::new_v4(), "127.0.0.1", 8080).unwrap();
+        let json = serde_json::to_value(&server).unwrap();
+        assert!(json.get("http2").is_none());
+
+        server.http2 = false;
+        let json = serde_json::to_value(&server).unwrap();
+        assert_eq!(json["http2"], false);
+    }
+
+    #[test]
+    fn can_deserialize_server_with_tls() {
+        let json = r###"
+        {
+            "id": "a3c6bf8d-57ab-4693-94dd-4d3a47dd1403",
+            "address": "127.0.0.1",
+            "port": 8443,
+            "name": "Unit Test",
+            "headers": [],
+            "routerId": "d49427b0-8d2f-47cc-a5b4-c3754c53583b",
+            "description": "",
+            "tls": {
+                "cert": "certs/server.pem",
+                "key": "certs/server.key"
+            }
+        }
+        "###;
+
+        let server: Server = serde_json::from_str(json).expect("Unable to parse JSON.");
+        assert_eq!(
+            server.tls,
+            Some(TlsConfig::new("certs/server.pem", "certs/server.key"))
+        );
+    }
+
+    #[test]
+    fn can_deserialize_server_with_listeners() {
+        let json = r###"
+        {
+            "id": "a3c6bf8d-57ab-4693-94dd-4d3a47dd1403",
+            "address": "127.0.0.1",
+            "port": 8443,
+            "name": "Unit Test",
+            "headers": [],
+            "routerId": "d49427b0-8d2f-47cc-a5b4-c3754c53583b",
+            "description": "",
+            "listeners": [
+                { "address": "::1", "port": 8443 },
+                { "address": "127.0.0.1", "port": 8080, "tls": false }
+            ]
+        }
+        "###;
+
+        let server: Server = serde_json::from_str(json).expect("Unable to parse JSON.");
+        assert_eq!(server.listeners.len(), 2);
+        assert_eq!(server.listeners[0].address.to_string(), "::1");
+        assert_eq!(server.listeners[0].tls, None);
+        assert_eq!(server.listeners[1].port, 8080);
+        assert_eq!(server.listeners[1].tls, Some(false));
     }
 
     #[test]
@@ -1,10 +1,11 @@
 use anyhow::{Context, Result};
 use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
 use std::fs::File;
 use std::io::{Read, Write};
 use std::path::Path;
 
-use super::{Router, Server};
+use super::{Router, Scenario, Server, DEFAULT_SCENARIO_STATE};
 
 const DEFAULT_SERVER_ADDR: &str = "127.0.0.1";
 const DEFAULT_SERVER_PORT: u16 = 8080;
@@ -13,6 +14,8 @@ const DEFAULT_SERVER_PORT: u16 = 8080;
 pub struct ServerInfo {
     pub server: Server,
     pub router: Router,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub scenarios: Vec<Scenario>,
 }
 
 impl ServerInfo {
@@ -21,7 +24,30 @@ impl ServerInfo {
         let server = Server::new(router.id, DEFAULT_SERVER_ADDR, DEFAULT_SERVER_PORT)?;
         router.bind_server(&server);
 
-        Ok(Self { server, router })
+        Ok(Self {
+            server,
+            router,
+            scenarios: vec![],
+        })
+    }
+
+    /// The initial state of every scenario, whether declared in `scenarios` or only
+    /// referenced by a `Route` or `Response` (in which case it starts in `DEFAULT_SCENARIO_STATE`)
+    pub fn initial_scenario_states(&self) -> HashMap<String, String> {
+        let mut states = HashMap::new();
+
+        let transitions = self.router.routes.iter().flat_map(|route| {
+            std::iter::once(&route.scenario).chain(route.responses.iter().map(|r| &r.scenario))
+        });
+        for name in transitions.filter_map(|t| t.scenario.as_ref()) {
+            states.insert(name.clone(), DEFAULT_SCENARIO_STATE.to_string());
+        }
+
+        for scenario in &self.scenarios {
+            states.insert(scenario.name.clone(), scenario.initial_state.clone());
+        }
+
+        states
     }
 
     /// Load and return a `ServerInfo` from a server config JSON file
@@ -84,6 +110,24 @@ mod tests {
         );
     }
 
+    #[test]
+    fn initial_scenario_states_include_declared_and_referenced_scenarios() {
+        let mut response = Response::new("", 200, ResponseType::Text, "");
+        response.scenario.scenario = Some("payment".into());
+        let mut route = Route::new("/order", Method::GET);
+        route.scenario.scenario = Some("order".into());
+        route.add_response(response);
+
+        let mut serverinfo = ServerInfo::new().unwrap();
+        serverinfo.router.add_route(route);
+        serverinfo.scenarios.push(Scenario::new("order", "created"));
+
+        let states = serverinfo.initial_scenario_states();
+        assert_eq!(states.len(), 2);
+        assert_eq!(states["order"], "created");
+        assert_eq!(states["payment"], DEFAULT_SCENARIO_STATE);
+    }
+
     #[test]
     fn can_serialize_serverinfo() {
         let response = Response::new("response name", 200, ResponseType::Text, "body");
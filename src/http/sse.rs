@@ -0,0 +1,101 @@
+use anyhow::Result;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+/// A single Server-Sent Event, as listed in the body of an `sse` `Response`
+#[derive(Debug, Clone, Default, Deserialize, Serialize, PartialEq)]
+pub struct SseEvent {
+    /// Event name. Clients treat unnamed events as `message`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub event: Option<String>,
+    /// Event data. Strings are sent as-is, any other JSON value is serialized.
+    #[serde(default)]
+    pub data: Value,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub id: Option<String>,
+    /// Reconnection time to suggest to the client, in milliseconds
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub retry: Option<u64>,
+    /// How long to wait before emitting this event
+    #[serde(rename = "delayMs", default)]
+    pub delay_ms: u64,
+    /// Emit this event this many times in total, waiting `delayMs` before each
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub repeat: Option<u32>,
+}
+
+impl SseEvent {
+    /// Parse a JSON list of events, as given in the body of an `sse` response
+    pub fn parse_list(body: &str) -> Result<Vec<SseEvent>> {
+        Ok(serde_json::from_str(body)?)
+    }
+
+    /// Format this event for the `text/event-stream` wire format
+    pub fn to_wire(&self) -> String {
+        let mut out = String::new();
+        if let Some(event) = &self.event {
+            out.push_str(&format!("event: {}\n", event));
+        }
+        if let Some(id) = &self.id {
+            out.push_str(&format!("id: {}\n", id));
+        }
+        if let Some(retry) = self.retry {
+            out.push_str(&format!("retry: {}\n", retry));
+        }
+
+        let data = match &self.data {
+            Value::String(s) => s.clone(),
+            Value::Null => String::new(),
+            value => value.to_string(),
+        };
+        for line in data.split('\n') {
+            out.push_str(&format!("data: {}\n", line));
+        }
+
+        out.push('\n');
+        out
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn can_deserialize_events() {
+        let json = r###"
+        [
+            { "event": "greeting", "data": "hello", "id": "1" },
+            { "data": { "count": 1 }, "delayMs": 500, "repeat": 3 }
+        ]
+        "###;
+
+        let events: Vec<SseEvent> = serde_json::from_str(json).expect("Unable to parse JSON.");
+        assert_eq!(events[0].event.as_deref(), Some("greeting"));
+        assert_eq!(events[1].data, json!({ "count": 1 }));
+        assert_eq!(events[1].delay_ms, 500);
+        assert_eq!(events[1].repeat, Some(3));
+    }
+
+    #[test]
+    fn events_are_formatted_for_the_wire() {
+        let event = SseEvent {
+            event: Some("update".into()),
+            data: Value::String("line one\nline two".into()),
+            id: Some("7".into()),
+            retry: Some(1000),
+            ..Default::default()
+        };
+        assert_eq!(
+            event.to_wire(),
+            "event: update\nid: 7\nretry: 1000\ndata: line one\ndata: line two\n\n"
+        );
+
+        let event = SseEvent {
+            data: json!({ "a": 1 }),
+            ..Default::default()
+        };
+        assert_eq!(event.to_wire(), "data: {\"a\":1}\n\n");
+    }
+}
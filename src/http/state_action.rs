@@ -0,0 +1,65 @@
+use serde::{Deserialize, Serialize};
+
+/// A declarative mutation of the server's in-memory state store, executed
+/// each time the owning `Response` is served. Keys and values are templates,
+/// so they may reference path params, query params, headers or the request
+/// body (for example `items/{{params.id}}`).
+#[derive(Debug, Clone, Deserialize, Serialize, PartialEq)]
+#[serde(tag = "action", rename_all = "lowercase")]
+pub enum StateAction {
+    /// Store `value` under `key`. If the rendered value is valid JSON it is stored
+    /// as JSON, otherwise as a plain string.
+    Set { key: String, value: String },
+    /// Add `by` to the integer stored under `key`, treating a missing key as 0.
+    /// The result saturates at the bounds of an `i64`.
+    Incr {
+        key: String,
+        #[serde(default = "default_incr_by")]
+        by: i64,
+    },
+    /// Remove `key` from the store, if present
+    Delete { key: String },
+}
+
+fn default_incr_by() -> i64 {
+    1
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn can_deserialize_state_actions() {
+        let json = r###"
+        [
+            { "action": "set", "key": "items/{{params.id}}", "value": "{{body}}" },
+            { "action": "incr", "key": "items:count" },
+            { "action": "incr", "key": "items:count", "by": -2 },
+            { "action": "delete", "key": "items/{{params.id}}" }
+        ]
+        "###;
+
+        let actions: Vec<StateAction> = serde_json::from_str(json).expect("Unable to parse JSON.");
+        assert_eq!(
+            actions,
+            vec![
+                StateAction::Set {
+                    key: "items/{{params.id}}".into(),
+                    value: "{{body}}".into()
+                },
+                StateAction::Incr {
+                    key: "items:count".into(),
+                    by: 1
+                },
+                StateAction::Incr {
+                    key: "items:count".into(),
+                    by: -2
+                },
+                StateAction::Delete {
+                    key: "items/{{params.id}}".into()
+                },
+            ]
+        );
+    }
+}
@@ -0,0 +1,171 @@
+use serde::{Deserialize, Serialize};
+
+use super::Header;
+
+const DEFAULT_REJECTION_STATUS: u16 = 401;
+
+/// TLS settings for a `Server`. Paths to PEM encoded files are resolved against
+/// the current working directory.
+#[derive(Debug, Clone, Deserialize, Serialize, PartialEq)]
+pub struct TlsConfig {
+    /// Certificate chain to present to clients, leaf certificate first
+    pub cert: String,
+    /// Private key matching the leaf certificate
+    pub key: String,
+    /// If set, clients are asked for a certificate issued by one of the given CAs
+    #[serde(
+        rename = "clientAuth",
+        default,
+        skip_serializing_if = "Option::is_none"
+    )]
+    pub client_auth: Option<ClientAuth>,
+}
+
+impl TlsConfig {
+    #[allow(unused)]
+    pub fn new(cert: &str, key: &str) -> Self {
+        TlsConfig {
+            cert: cert.to_string(),
+            key: key.to_string(),
+            client_auth: None,
+        }
+    }
+}
+
+/**
+Mutual TLS settings. Clients presenting a certificate not issued by `ca` fail the
+TLS handshake. Clients presenting no certificate at all are served `rejection`
+if `required`, otherwise they are served as normal.
+**/
+#[derive(Debug, Clone, Deserialize, Serialize, PartialEq)]
+pub struct ClientAuth {
+    /// PEM encoded bundle of CA certificates trusted to issue client certificates
+    pub ca: String,
+    #[serde(default = "default_required")]
+    pub required: bool,
+    #[serde(default)]
+    pub rejection: ClientCertRejection,
+}
+
+impl ClientAuth {
+    #[allow(unused)]
+    pub fn new(ca: &str) -> Self {
+        ClientAuth {
+            ca: ca.to_string(),
+            required: true,
+            rejection: ClientCertRejection::default(),
+        }
+    }
+}
+
+fn default_required() -> bool {
+    true
+}
+
+/// The response served to clients which did not present a required certificate
+#[derive(Debug, Clone, Deserialize, Serialize, PartialEq)]
+pub struct ClientCertRejection {
+    #[serde(default = "default_rejection_status")]
+    pub status: u16,
+    #[serde(default)]
+    pub response: String,
+    #[serde(default)]
+    pub headers: Vec<Header>,
+}
+
+impl Default for ClientCertRejection {
+    fn default() -> Self {
+        ClientCertRejection {
+            status: DEFAULT_REJECTION_STATUS,
+            response: String::default(),
+            headers: vec![],
+        }
+    }
+}
+
+fn default_rejection_status() -> u16 {
+    DEFAULT_REJECTION_STATUS
+}
+
+/// Identifying details of the certificate a client presented during the TLS handshake
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct ClientIdentity {
+    /// Full subject distinguished name, e.g. `CN=tenant-a, O=Example`
+    pub subject: String,
+    pub common_name: Option<String>,
+    /// DNS names, email addresses, URIs and IP addresses from the subject alternative names
+    pub sans: Vec<String>,
+}
+
+/**
+A rule matching the certificate presented by a client. Every field given must match:
+`subject` matches either the full subject DN or its common name, and `san` matches
+any one of the certificate's subject alternative names.
+**/
+#[derive(Debug, Clone, Default, Deserialize, Serialize, PartialEq)]
+pub struct ClientCertMatch {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub subject: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub san: Option<String>,
+}
+
+impl ClientCertMatch {
+    pub fn matches(&self, identity: Option<&ClientIdentity>) -> bool {
+        let Some(identity) = identity else {
+            return false;
+        };
+
+        let subject_matches = self.subject.as_ref().is_none_or(|subject| {
+            identity.subject == *subject || identity.common_name.as_ref() == Some(subject)
+        });
+        let san_matches = self
+            .san
+            .as_ref()
+            .is_none_or(|san| identity.sans.contains(san));
+
+        subject_matches && san_matches
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn make_identity() -> ClientIdentity {
+        ClientIdentity {
+            subject: "CN=tenant-a, O=Example".to_string(),
+            common_name: Some("tenant-a".to_string()),
+            sans: vec!["tenant-a.example.com".to_string()],
+        }
+    }
+
+    #[test]
+    fn client_cert_match_checks_every_given_field() {
+        let identity = make_identity();
+        let by_cn = ClientCertMatch {
+            subject: Some("tenant-a".into()),
+            san: None,
+        };
+        let by_dn = ClientCertMatch {
+            subject: Some("CN=tenant-a, O=Example".into()),
+            san: None,
+        };
+        let by_both = ClientCertMatch {
+            subject: Some("tenant-a".into()),
+            san: Some("tenant-b.example.com".into()),
+        };
+
+        assert!(by_cn.matches(Some(&identity)));
+        assert!(by_dn.matches(Some(&identity)));
+        assert!(!by_both.matches(Some(&identity)));
+        assert!(!by_cn.matches(None));
+    }
+
+    #[test]
+    fn client_auth_defaults_to_required_with_401_rejection() {
+        let client_auth: ClientAuth = serde_json::from_str(r#"{ "ca": "certs/ca.pem" }"#).unwrap();
+        assert_eq!(client_auth, ClientAuth::new("certs/ca.pem"));
+        assert_eq!(client_auth.rejection.status, 401);
+    }
+}
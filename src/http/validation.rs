@@ -0,0 +1,104 @@
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::fs;
+
+const DEFAULT_FAILURE_STATUS: u16 = 400;
+
+/// A JSON Schema, given either inline or as a path to a file containing one.
+/// Relative paths are resolved against the current working directory.
+#[derive(Debug, Clone, Deserialize, Serialize, PartialEq)]
+#[serde(untagged)]
+pub enum SchemaSource {
+    File(String),
+    Inline(Value),
+}
+
+impl SchemaSource {
+    /// Load the schema, reading it from disk if necessary
+    pub fn load(&self) -> Result<Value> {
+        match self {
+            SchemaSource::Inline(schema) => Ok(schema.clone()),
+            SchemaSource::File(path) => {
+                let data = fs::read_to_string(path)
+                    .with_context(|| format!("Failed to read schema file `{}`", path))?;
+                serde_json::from_str(&data)
+                    .with_context(|| format!("Could not parse schema file `{}` as JSON", path))
+            }
+        }
+    }
+}
+
+/**
+Describes how incoming requests to a `Route` should be validated. Requests which
+fail validation are rejected with `failureStatus`, and a JSON body listing each error.
+
+Query params are validated as a JSON object, with values that look like numbers or
+booleans converted accordingly. Headers are validated as a JSON object of strings,
+keyed by lowercase header name.
+**/
+#[derive(Debug, Clone, Deserialize, Serialize, PartialEq)]
+pub struct RequestValidation {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub body: Option<SchemaSource>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub query: Option<SchemaSource>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub headers: Option<SchemaSource>,
+    #[serde(rename = "failureStatus", default = "default_failure_status")]
+    pub failure_status: u16,
+}
+
+impl Default for RequestValidation {
+    fn default() -> Self {
+        RequestValidation {
+            body: None,
+            query: None,
+            headers: None,
+            failure_status: DEFAULT_FAILURE_STATUS,
+        }
+    }
+}
+
+fn default_failure_status() -> u16 {
+    DEFAULT_FAILURE_STATUS
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn can_deserialize_inline_and_file_schemas() {
+        let json = r###"
+        {
+            "body": { "type": "object", "required": ["name"] },
+            "query": "tests/data/query.schema.json",
+            "failureStatus": 422
+        }
+        "###;
+
+        let validation: RequestValidation =
+            serde_json::from_str(json).expect("Unable to parse JSON.");
+        assert_eq!(
+            validation.body,
+            Some(SchemaSource::Inline(
+                json!({ "type": "object", "required": ["name"] })
+            ))
+        );
+        assert_eq!(
+            validation.query,
+            Some(SchemaSource::File("tests/data/query.schema.json".into()))
+        );
+        assert_eq!(validation.headers, None);
+        assert_eq!(validation.failure_status, 422);
+    }
+
+    #[test]
+    fn load_reports_missing_schema_files() {
+        let source = SchemaSource::File("tests/data/does-not-exist.json".into());
+        let err = source.load().unwrap_err();
+        assert!(err.to_string().contains("does-not-exist.json"));
+    }
+}
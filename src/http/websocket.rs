@@ -0,0 +1,152 @@
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+use crate::template::json_contains;
+
+/// A single WebSocket message, either `{"text": "..."}` or `{"json": {...}}`
+#[derive(Debug, Clone, Deserialize, Serialize, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum WebSocketMessage {
+    Text(String),
+    Json(Value),
+}
+
+impl WebSocketMessage {
+    /// The text frame to send for this message
+    pub fn to_text(&self) -> String {
+        match self {
+            WebSocketMessage::Text(text) => text.clone(),
+            WebSocketMessage::Json(value) => value.to_string(),
+        }
+    }
+
+    /**
+    Whether an incoming text frame matches this message, when used as a matcher.
+    `text` must match exactly, while `json` matches any JSON document containing
+    at least the given fields and values.
+    **/
+    pub fn matches(&self, incoming: &str) -> bool {
+        match self {
+            WebSocketMessage::Text(text) => text == incoming,
+            WebSocketMessage::Json(expected) => serde_json::from_str::<Value>(incoming)
+                .is_ok_and(|actual| json_contains(&actual, expected)),
+        }
+    }
+}
+
+/// Instructs the server to close the connection with `code`, optionally after a delay
+#[derive(Debug, Clone, Deserialize, Serialize, PartialEq)]
+pub struct WebSocketClose {
+    pub code: u16,
+    #[serde(default, skip_serializing_if = "String::is_empty")]
+    pub reason: String,
+    #[serde(rename = "afterMs", default)]
+    pub after_ms: u64,
+}
+
+/// Messages sent in response to an incoming message matching `match`
+#[derive(Debug, Clone, Deserialize, Serialize, PartialEq)]
+pub struct WebSocketReply {
+    #[serde(rename = "match")]
+    pub matcher: WebSocketMessage,
+    #[serde(default)]
+    pub messages: Vec<WebSocketMessage>,
+    /// Close the connection once the messages have been sent
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub close: Option<WebSocketClose>,
+}
+
+/// A message sent every `intervalMs`, either indefinitely or `count` times
+#[derive(Debug, Clone, Deserialize, Serialize, PartialEq)]
+pub struct WebSocketPush {
+    #[serde(rename = "intervalMs")]
+    pub interval_ms: u64,
+    pub message: WebSocketMessage,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub count: Option<u32>,
+}
+
+/**
+The scripted conversation of a WebSocket `Route`. Once a client connects, each of
+`onConnect` is sent, `pushes` begin, and incoming text messages are answered by the
+first reply whose `match` they satisfy. If `close` is set, the connection is closed
+`close.afterMs` after connecting.
+**/
+#[derive(Debug, Clone, Default, Deserialize, Serialize, PartialEq)]
+pub struct WebSocketScript {
+    #[serde(rename = "onConnect", default)]
+    pub on_connect: Vec<WebSocketMessage>,
+    #[serde(default)]
+    pub replies: Vec<WebSocketReply>,
+    #[serde(default)]
+    pub pushes: Vec<WebSocketPush>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub close: Option<WebSocketClose>,
+}
+
+impl WebSocketScript {
+    /// Find the reply for an incoming text message, if any
+    pub fn reply_for(&self, incoming: &str) -> Option<&WebSocketReply> {
+        self.replies.iter().find(|r| r.matcher.matches(incoming))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn can_deserialize_script() {
+        let json = r###"
+        {
+            "onConnect": [{ "text": "welcome" }],
+            "replies": [
+                { "match": { "text": "ping" }, "messages": [{ "text": "pong" }] },
+                {
+                    "match": { "json": { "type": "subscribe" } },
+                    "messages": [{ "json": { "type": "subscribed" } }],
+                    "close": { "code": 4000, "reason": "done" }
+                }
+            ],
+            "pushes": [{ "intervalMs": 1000, "message": { "text": "tick" }, "count": 3 }],
+            "close": { "code": 1000, "afterMs": 5000 }
+        }
+        "###;
+
+        let script: WebSocketScript = serde_json::from_str(json).expect("Unable to parse JSON.");
+        assert_eq!(
+            script.on_connect,
+            vec![WebSocketMessage::Text("welcome".into())]
+        );
+        assert_eq!(script.replies.len(), 2);
+        assert_eq!(script.pushes[0].count, Some(3));
+        assert_eq!(script.close.unwrap().after_ms, 5000);
+    }
+
+    #[test]
+    fn replies_match_text_exactly_and_json_partially() {
+        let script = WebSocketScript {
+            replies: vec![
+                WebSocketReply {
+                    matcher: WebSocketMessage::Text("ping".into()),
+                    messages: vec![],
+                    close: None,
+                },
+                WebSocketReply {
+                    matcher: WebSocketMessage::Json(json!({"type": "subscribe"})),
+                    messages: vec![],
+                    close: None,
+                },
+            ],
+            ..Default::default()
+        };
+
+        assert!(script.reply_for("ping").is_some());
+        assert!(script.reply_for("ping ").is_none());
+        assert!(script
+            .reply_for(r#"{"type": "subscribe", "channel": "news"}"#)
+            .is_some());
+        assert!(script.reply_for(r#"{"type": "unsubscribe"}"#).is_none());
+    }
+}
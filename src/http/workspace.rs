@@ -0,0 +1,316 @@
+use anyhow::{bail, Context, Result};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::collections::HashMap;
+use std::fs;
+use std::net::IpAddr;
+use std::path::Path;
+
+use super::{Header, ServerInfo};
+
+/**
+A bundle of server configs, started together with `mockerize-cli up`. Each server is
+either a path to a server config file (relative to the workspace file) or an inline config.
+
+Within each server config and header, `${NAME}` in any string is replaced with the variable `NAME`,
+looked up in the server's `env`, then the workspace's `env`, then the process environment.
+References to undefined variables are left as-is.
+**/
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct Workspace {
+    #[serde(default, skip_serializing_if = "String::is_empty")]
+    pub name: String,
+    /// Headers sent by every server, unless a server sets the same header itself
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub headers: Vec<Header>,
+    #[serde(default, skip_serializing_if = "HashMap::is_empty")]
+    pub env: HashMap<String, String>,
+    pub servers: Vec<WorkspaceServer>,
+}
+
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct WorkspaceServer {
+    #[serde(flatten)]
+    pub source: WorkspaceServerSource,
+    /// Variables for this server only, taking precedence over the workspace's
+    #[serde(default, skip_serializing_if = "HashMap::is_empty")]
+    pub env: HashMap<String, String>,
+    #[serde(default)]
+    pub overrides: ServerOverrides,
+}
+
+/// Where a workspace server's config comes from: `{"path": "..."}` or `{"config": {...}}`
+#[derive(Debug, Clone, Deserialize, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum WorkspaceServerSource {
+    Path(String),
+    Config(Value),
+}
+
+/// Settings replacing those of a workspace server's config
+#[derive(Debug, Clone, Default, Deserialize, Serialize)]
+pub struct ServerOverrides {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub name: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub address: Option<IpAddr>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub port: Option<u16>,
+    /// Layered over the server's headers, replacing any with the same key
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub headers: Vec<Header>,
+}
+
+impl Workspace {
+    /// Load a `Workspace` from a JSON file
+    pub fn from_file<P: AsRef<Path>>(file_path: P) -> Result<Self> {
+        let file_path = file_path.as_ref();
+        let data = fs::read_to_string(file_path)
+            .with_context(|| format!("Failed to read workspace file `{}`", file_path.display()))?;
+
+        serde_json::from_str(&data).with_context(|| {
+            format!(
+                "Could not deserialize file contents `{}` into Workspace struct",
+                file_path.display()
+            )
+        })
+    }
+
+    /**
+    Load every server of the workspace, with variables substituted and shared headers
+    and overrides applied. Each is returned with a label naming where it came from.
+    Relative paths are resolved against `base_dir`.
+    **/
+    pub fn load_servers(&self, base_dir: &Path) -> Result<Vec<(String, ServerInfo)>> {
+        if self.servers.is_empty() {
+            bail!("Workspace does not contain any servers");
+        }
+
+        self.servers
+            .iter()
+            .enumerate()
+            .map(|(i, server)| {
+                let (label, config) = match &server.source {
+                    WorkspaceServerSource::Path(path) => {
+                        let path = base_dir.join(path);
+                        let data = fs::read_to_string(&path).with_context(|| {
+                            format!("Failed to open config file `{}`", path.display())
+                        })?;
+                        let config = serde_json::from_str(&data).with_context(|| {
+                            format!("Could not parse config file `{}`", path.display())
+                        })?;
+                        (path.display().to_string(), config)
+                    }
+                    WorkspaceServerSource::Config(config) => {
+                        (format!("servers[{}]", i), config.clone())
+                    }
+                };
+
+                let serverinfo = self
+                    .build_server(server, config)
+                    .with_context(|| format!("Invalid workspace server `{}`", label))?;
+                Ok((label, serverinfo))
+            })
+            .collect()
+    }
+
+    fn build_server(&self, server: &WorkspaceServer, mut config: Value) -> Result<ServerInfo> {
+        let lookup = |name: &str| {
+            server
+                .env
+                .get(name)
+                .or_else(|| self.env.get(name))
+                .cloned()
+                .or_else(|| std::env::var(name).ok())
+        };
+        substitute_vars(&mut config, &lookup);
+
+        let mut serverinfo: ServerInfo = serde_json::from_value(config)
+            .context("Could not deserialize config into ServerInfo struct")?;
+
+        let substitute_header = |header: &Header| Header {
+            value: substitute_str(&header.value, &lookup),
+            ..header.clone()
+        };
+
+        let headers = &mut serverinfo.server.headers;
+        for shared in &self.headers {
+            if !headers.iter().any(|h| h.key == shared.key) {
+                headers.push(substitute_header(shared));
+            }
+        }
+
+        let overrides = &server.overrides;
+        for header in &overrides.headers {
+            headers.retain(|h| h.key != header.key);
+            headers.push(substitute_header(header));
+        }
+        if let Some(name) = &overrides.name {
+            serverinfo.server.name = name.clone();
+        }
+        if let Some(address) = overrides.address {
+            serverinfo.server.address = address;
+        }
+        if let Some(port) = overrides.port {
+            serverinfo.server.port = port;
+        }
+
+        Ok(serverinfo)
+    }
+}
+
+// Replace `${NAME}` in every string within `value`, wherever `lookup` knows `NAME`
+fn substitute_vars<F>(value: &mut Value, lookup: &F)
+where
+    F: Fn(&str) -> Option<String>,
+{
+    match value {
+        Value::String(s) if s.contains("${") => *s = substitute_str(s, lookup),
+        Value::Array(items) => items.iter_mut().for_each(|v| substitute_vars(v, lookup)),
+        Value::Object(map) => map.values_mut().for_each(|v| substitute_vars(v, lookup)),
+        _ => {}
+    }
+}
+
+fn substitute_str<F>(input: &str, lookup: &F) -> String
+where
+    F: Fn(&str) -> Option<String>,
+{
+    let mut output = String::new();
+    let mut rest = input;
+
+    while let Some(start) = rest.find("${") {
+        output.push_str(&rest[..start]);
+        let after = &rest[start + 2..];
+        match after.find('}') {
+            Some(end) => {
+                let name = &after[..end];
+                match lookup(name) {
+                    Some(value) => output.push_str(&value),
+                    None => output.push_str(&rest[start..start + 2 + end + 1]),
+                }
+                rest = &after[end + 1..];
+            }
+            None => {
+                output.push_str(&rest[start..]);
+                rest = "";
+            }
+        }
+    }
+
+    output.push_str(rest);
+    output
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn make_config(name: &str, port: u16) -> Value {
+        let mut serverinfo = ServerInfo::new().unwrap();
+        serverinfo.server.name = name.to_string();
+        serverinfo.server.port = port;
+        serverinfo.server.headers = vec![Header::new("X-Service", "${SERVICE}")];
+        serde_json::to_value(serverinfo).unwrap()
+    }
+
+    #[test]
+    fn substitutes_known_variables_only() {
+        let lookup = |name: &str| (name == "HOST").then(|| "example.com".to_string());
+        assert_eq!(
+            substitute_str("https://${HOST}/api/${MISSING}/${", &lookup),
+            "https://example.com/api/${MISSING}/${"
+        );
+    }
+
+    #[test]
+    fn applies_env_shared_headers_and_overrides() {
+        let workspace = Workspace {
+            name: "shop".into(),
+            headers: vec![
+                Header::new("X-Service", "shared"),
+                Header::new("X-Env", "${STAGE}"),
+            ],
+            env: HashMap::from([
+                ("SERVICE".to_string(), "shared".to_string()),
+                ("STAGE".to_string(), "test".to_string()),
+            ]),
+            servers: vec![
+                WorkspaceServer {
+                    source: WorkspaceServerSource::Config(make_config("users", 8080)),
+                    env: HashMap::from([("SERVICE".to_string(), "users".to_string())]),
+                    overrides: ServerOverrides::default(),
+                },
+                WorkspaceServer {
+                    source: WorkspaceServerSource::Config(make_config("orders", 8080)),
+                    env: HashMap::new(),
+                    overrides: ServerOverrides {
+                        port: Some(8081),
+                        headers: vec![Header::new("X-Env", "override")],
+                        ..Default::default()
+                    },
+                },
+            ],
+        };
+
+        let servers = workspace.load_servers(Path::new(".")).unwrap();
+        let header = |i: usize, key: &str| {
+            let headers = &servers[i].1.server.headers;
+            headers
+                .iter()
+                .find(|h| h.key == key)
+                .map(|h| h.value.clone())
+        };
+
+        assert_eq!(servers[0].0, "servers[0]");
+        assert_eq!(header(0, "X-Service").as_deref(), Some("users"));
+        assert_eq!(header(0, "X-Env").as_deref(), Some("test"));
+        assert_eq!(header(1, "X-Service").as_deref(), Some("shared"));
+        assert_eq!(header(1, "X-Env").as_deref(), Some("override"));
+        assert_eq!(servers[1].1.server.port, 8081);
+    }
+
+    #[test]
+    fn loads_server_files_relative_to_workspace() {
+        let path = Path::new("tests/data/example.workspace.json");
+        let workspace = Workspace::from_file(path).unwrap();
+        let servers = workspace.load_servers(path.parent().unwrap()).unwrap();
+
+        assert_eq!(servers.len(), 2);
+        assert_eq!(servers[0].0, "tests/data/example.server.json");
+        assert_eq!(servers[0].1.server.name, "lists");
+        assert_eq!(servers[1].1.server.port, 8082);
+        let stage = servers[1]
+            .1
+            .server
+            .headers
+            .iter()
+            .find(|h| h.key == "X-Stage");
+        assert_eq!(stage.unwrap().value, "staging");
+    }
+
+    #[test]
+    fn can_deserialize_workspace() {
+        let json = json!({
+            "name": "shop",
+            "env": { "STAGE": "test" },
+            "servers": [
+                { "path": "users.server.json", "overrides": { "port": 9001 } },
+                { "config": make_config("orders", 8080), "env": { "SERVICE": "orders" } }
+            ]
+        });
+
+        let workspace: Workspace = serde_json::from_value(json).expect("Unable to parse JSON.");
+        assert_eq!(workspace.servers.len(), 2);
+        assert!(matches!(
+            &workspace.servers[0].source,
+            WorkspaceServerSource::Path(path) if path == "users.server.json"
+        ));
+        assert_eq!(workspace.servers[0].overrides.port, Some(9001));
+        assert!(matches!(
+            workspace.servers[1].source,
+            WorkspaceServerSource::Config(_)
+        ));
+    }
+}
@@ -0,0 +1,122 @@
+/*!
+Records the requests a server receives, so that tests embedding a server (see `crate::mock`) can verify them.
+Requests are only recorded when a `RequestJournal` is passed to `startup::run` through `RunOptions`.
+*/
+
+use actix_http::BoxedPayloadStream;
+use actix_web::body::MessageBody;
+use actix_web::dev::{Payload, ServiceRequest, ServiceResponse};
+use actix_web::middleware::Next;
+use actix_web::web::{self, Bytes, BytesMut, Query};
+use actix_web::HttpMessage;
+use futures_util::{stream, StreamExt};
+use serde_json::Value;
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex, MutexGuard, PoisonError};
+
+use crate::admin::ADMIN_PATH;
+
+/// A request received by a server
+#[derive(Debug, Clone, PartialEq)]
+pub struct RecordedRequest {
+    pub method: String,
+    /// The HTTP version the request was made with, e.g. `HTTP/1.1` or `HTTP/2.0`
+    pub version: String,
+    pub path: String,
+    pub query: HashMap<String, String>,
+    /// Keyed by lowercase header name
+    pub headers: HashMap<String, String>,
+    pub body: String,
+}
+
+#[allow(dead_code)]
+impl RecordedRequest {
+    /// Parse the request body as JSON, if possible
+    pub fn json(&self) -> Option<Value> {
+        serde_json::from_str(&self.body).ok()
+    }
+}
+
+/// Every request received by a server, in the order they arrived. Requests to the admin endpoints are not recorded.
+/// Cloning a `RequestJournal` yields another handle to the same underlying data.
+#[derive(Debug, Clone, Default)]
+pub struct RequestJournal {
+    inner: Arc<Mutex<Vec<RecordedRequest>>>,
+}
+
+#[allow(dead_code)]
+impl RequestJournal {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// A copy of every request recorded so far
+    pub fn requests(&self) -> Vec<RecordedRequest> {
+        self.lock().clone()
+    }
+
+    pub fn record(&self, request: RecordedRequest) {
+        self.lock().push(request);
+    }
+
+    /// Forget every request recorded so far
+    pub fn reset(&self) {
+        self.lock().clear();
+    }
+
+    fn lock(&self) -> MutexGuard<'_, Vec<RecordedRequest>> {
+        self.inner.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+}
+
+/// Middleware recording each request into the app's `RequestJournal`, if it has one
+pub(crate) async fn record(
+    mut req: ServiceRequest,
+    next: Next<impl MessageBody>,
+) -> Result<ServiceResponse<impl MessageBody>, actix_web::Error> {
+    let journal = req.app_data::<web::Data<RequestJournal>>().cloned();
+    if let Some(journal) = journal.filter(|_| !req.path().starts_with(ADMIN_PATH)) {
+        let (body, payload) = read_payload(req.take_payload()).await;
+        journal.record(RecordedRequest {
+            method: req.method().to_string(),
+            version: format!("{:?}", req.version()),
+            path: req.path().to_string(),
+            query: Query::<HashMap<String, String>>::from_query(req.query_string())
+                .map(|q| q.into_inner())
+                .unwrap_or_default(),
+            headers: req
+                .headers()
+                .iter()
+                .filter_map(|(k, v)| {
+                    Some((k.as_str().to_lowercase(), v.to_str().ok()?.to_string()))
+                })
+                .collect(),
+            body: String::from_utf8_lossy(&body).into_owned(),
+        });
+        req.set_payload(payload);
+    }
+
+    next.call(req).await
+}
+
+/**
+Read `payload` in full, however large, returning its contents and a payload yielding them again for
+the route handler. If reading fails, the body is recorded as far as it was read, and the handler is
+left to see the same error, as it would have without a journal.
+**/
+async fn read_payload(mut payload: Payload) -> (Bytes, Payload) {
+    let mut body = BytesMut::new();
+    while let Some(chunk) = payload.next().await {
+        match chunk {
+            Ok(chunk) => body.extend_from_slice(&chunk),
+            Err(e) => {
+                let body = body.freeze();
+                let replay = stream::iter([Ok(body.clone()), Err(e)]);
+                return (body, Payload::from(Box::pin(replay) as BoxedPayloadStream));
+            }
+        }
+    }
+
+    let body = body.freeze();
+    (body.clone(), Payload::from(body))
+}
@@ -6,6 +6,20 @@ Specifically, `mockerize-cli` focuses on provisioning quick server runtimes gear
 experience that the Mockerize application provides.
 */
 
+pub mod admin;
 pub mod cli;
+pub mod graphql;
+pub mod grpc;
 pub mod http;
+pub mod journal;
+pub mod mock;
+pub mod openapi;
+pub mod resource;
+pub mod sse;
 pub mod startup;
+pub mod state;
+pub mod streaming;
+pub mod template;
+pub mod tls;
+pub mod validator;
+pub mod websocket;
@@ -8,11 +8,24 @@ use tracing_bunyan_formatter::{BunyanFormattingLayer, JsonStorageLayer};
 use tracing_subscriber::layer::SubscriberExt;
 use tracing_subscriber::{EnvFilter, Registry};
 
-use cli::{Args, Commands};
+use cli::{Args, Commands, ServerStatus};
 
+mod admin;
 mod cli;
+mod graphql;
+mod grpc;
 mod http;
+mod journal;
+mod openapi;
+mod resource;
+mod sse;
 mod startup;
+mod state;
+mod streaming;
+mod template;
+mod tls;
+mod validator;
+mod websocket;
 
 #[tokio::main]
 async fn main() -> Result<()> {
@@ -35,6 +48,19 @@ async fn main() -> Result<()> {
                 process::exit(1);
             }
         },
+        Some(Commands::Up(cmd)) => cmd.handle().await?,
+        Some(Commands::Stop(cmd)) => cmd.handle()?,
+        // Exit codes follow the LSB convention for init scripts' status action
+        Some(Commands::Status(cmd)) => match cmd.handle()? {
+            ServerStatus::Running(_) => {}
+            ServerStatus::Stale(_) => process::exit(1),
+            ServerStatus::NotRunning => process::exit(3),
+        },
+        Some(Commands::Restart(cmd)) => cmd.handle().await?,
+        Some(Commands::Wait(cmd)) => cmd.handle().await?,
+        Some(Commands::Route(cmd)) => cmd.handle()?,
+        Some(Commands::Response(cmd)) => cmd.handle()?,
+        Some(Commands::Header(cmd)) => cmd.handle()?,
         None => {
             Args::command().print_help()?;
             println!();
@@ -0,0 +1,249 @@
+/*!
+Mock servers embedded in Rust tests. A `MockServer` listens on a port chosen by the OS, records every
+request it receives for verification, and shuts down when dropped.
+
+```no_run
+use mockerize_cli::http::Method;
+use mockerize_cli::mock::{MockResponse, MockServer};
+
+# async fn example() -> anyhow::Result<()> {
+let server = MockServer::builder()
+    .route(Method::GET, "/users/:id", MockResponse::new(200).json(&serde_json::json!({ "id": 1 })))
+    .start()
+    .await?;
+
+// Point the code under test at `server.uri()`, then check what it sent
+server.verify(Method::GET, "/users/:id", 1)?;
+# Ok(())
+# }
+```
+*/
+
+use actix_web::dev::{ResourceDef, ServerHandle};
+use anyhow::{bail, Context, Result};
+use serde_json::Value;
+use std::net::{SocketAddr, TcpListener};
+
+use crate::http::{Header, Method, Response, ResponseType, Route, ServerInfo};
+use crate::journal::{RecordedRequest, RequestJournal};
+use crate::startup::{run, transform_route_path, RunOptions};
+
+/// A running mock server, which is stopped when dropped
+pub struct MockServer {
+    scheme: &'static str,
+    address: SocketAddr,
+    handle: ServerHandle,
+    journal: RequestJournal,
+}
+
+impl MockServer {
+    pub fn builder() -> MockServerBuilder {
+        MockServerBuilder::default()
+    }
+
+    /// Start a server without any routes, which answers every request with a 404
+    pub async fn start() -> Result<MockServer> {
+        Self::builder().start().await
+    }
+
+    /// The base URI of the server, such as `http://127.0.0.1:41234`, or `https://` if the config has `tls`
+    pub fn uri(&self) -> String {
+        format!("{}://{}", self.scheme, self.address)
+    }
+
+    /// The URI of `path` on this server
+    pub fn url(&self, path: &str) -> String {
+        format!("{}{}", self.uri(), path)
+    }
+
+    pub fn address(&self) -> SocketAddr {
+        self.address
+    }
+
+    /// Every request received so far, in the order they arrived
+    pub fn received_requests(&self) -> Vec<RecordedRequest> {
+        self.journal.requests()
+    }
+
+    /// The requests received so far for `method` and `path`, which may contain `:param` segments as in a route
+    pub fn requests_to(&self, method: Method, path: &str) -> Vec<RecordedRequest> {
+        let pattern = ResourceDef::new(transform_route_path(path));
+        let method = method.to_string();
+
+        self.received_requests()
+            .into_iter()
+            .filter(|request| request.method == method && pattern.is_match(&request.path))
+            .collect()
+    }
+
+    /// Check that exactly `times` requests were received for `method` and `path`
+    pub fn verify(&self, method: Method, path: &str, times: usize) -> Result<()> {
+        let received = self.requests_to(method.clone(), path).len();
+        if received != times {
+            let mut requests = self
+                .received_requests()
+                .iter()
+                .map(|request| format!("\n  {} {}", request.method, request.path))
+                .collect::<String>();
+            if requests.is_empty() {
+                requests = " none".to_string();
+            }
+            bail!(
+                "Expected {} {} {} request(s), but received {}. Requests received:{}",
+                times,
+                method,
+                path,
+                received,
+                requests
+            );
+        }
+        Ok(())
+    }
+
+    /// Forget every request received so far
+    pub fn reset(&self) {
+        self.journal.reset();
+    }
+}
+
+impl Drop for MockServer {
+    fn drop(&mut self) {
+        // The stop command is sent straight away, there is no need to wait for the server to finish
+        drop(self.handle.stop(false));
+    }
+}
+
+/// Builds the config of a `MockServer`, route by route
+#[derive(Default)]
+pub struct MockServerBuilder {
+    config: Option<ServerInfo>,
+    headers: Vec<Header>,
+    routes: Vec<Route>,
+}
+
+impl MockServerBuilder {
+    /**
+    Start from an existing config, such as one loaded with `ServerInfo::from_file`. Its `tls` is honoured,
+    but its address, port, `listeners` and `unix_socket` are ignored, and gRPC configs are rejected by `start`.
+    **/
+    pub fn config(mut self, serverinfo: ServerInfo) -> Self {
+        self.config = Some(serverinfo);
+        self
+    }
+
+    /// Send `key: value` with every response, unless a route or response sets the same header
+    pub fn header(mut self, key: &str, value: &str) -> Self {
+        self.headers.push(Header::new(key, value));
+        self
+    }
+
+    /// Answer `method` requests to `path` with `response`. Paths may contain `:param` segments.
+    pub fn route(self, method: Method, path: &str, response: impl Into<Response>) -> Self {
+        let mut route = Route::new(path, method);
+        route.add_response(response.into());
+        self.mount(route)
+    }
+
+    /// Add a fully configured route, for anything `route` does not cover
+    pub fn mount(mut self, route: Route) -> Self {
+        self.routes.push(route);
+        self
+    }
+
+    /// Start the server on `127.0.0.1`, on a port chosen by the OS. Must be called within a Tokio runtime.
+    pub async fn start(self) -> Result<MockServer> {
+        let mut serverinfo = match self.config {
+            Some(serverinfo) => serverinfo,
+            None => ServerInfo::new()?,
+        };
+        if serverinfo.server.grpc.is_some() {
+            bail!("Mock servers only serve HTTP, not gRPC");
+        }
+        // The server only ever listens on the single port bound below
+        serverinfo.server.listeners.clear();
+        serverinfo.server.unix_socket = None;
+        let scheme = if serverinfo.server.tls.is_some() {
+            "https"
+        } else {
+            "http"
+        };
+
+        for header in self.headers {
+            serverinfo.server.add_header(header);
+        }
+        for route in self.routes {
+            serverinfo.router.add_route(route);
+        }
+
+        let listener =
+            TcpListener::bind("127.0.0.1:0").context("Could not bind mock server to a port")?;
+        let address = listener.local_addr()?;
+        serverinfo.server.address = address.ip();
+        serverinfo.server.port = address.port();
+
+        let journal = RequestJournal::new();
+        let options = RunOptions {
+            workers: Some(1),
+            disable_signals: true,
+            journal: Some(journal.clone()),
+            ..Default::default()
+        };
+        let server = run(serverinfo, listener, options).context("Could not start mock server")?;
+        let handle = server.handle();
+        tokio::spawn(server);
+
+        Ok(MockServer {
+            scheme,
+            address,
+            handle,
+            journal,
+        })
+    }
+}
+
+/// A response for `MockServerBuilder::route`, with a text body unless given JSON
+pub struct MockResponse {
+    status: u16,
+    response_type: ResponseType,
+    body: String,
+    headers: Vec<Header>,
+}
+
+impl MockResponse {
+    pub fn new(status: u16) -> Self {
+        MockResponse {
+            status,
+            response_type: ResponseType::Text,
+            body: String::new(),
+            headers: vec![],
+        }
+    }
+
+    pub fn body(mut self, body: &str) -> Self {
+        self.body = body.to_string();
+        self
+    }
+
+    /// Respond with `value` as the body, sent with a `Content-Type` of `application/json`
+    pub fn json(mut self, value: &Value) -> Self {
+        self.response_type = ResponseType::Json;
+        self.body = value.to_string();
+        self.header("Content-Type", "application/json")
+    }
+
+    pub fn header(mut self, key: &str, value: &str) -> Self {
+        self.headers.retain(|h| !h.key.eq_ignore_ascii_case(key));
+        self.headers.push(Header::new(key, value));
+        self
+    }
+}
+
+impl From<MockResponse> for Response {
+    fn from(mock: MockResponse) -> Self {
+        let mut response = Response::new("", mock.status, mock.response_type, &mock.body);
+        for header in mock.headers {
+            response.add_header(header);
+        }
+        response
+    }
+}
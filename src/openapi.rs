@@ -0,0 +1,519 @@
+use anyhow::{anyhow, bail, Context, Result};
+use jsonschema::Validator;
+use serde_json::Value;
+use std::collections::HashMap;
+use std::fs;
+use std::path::Path;
+use std::sync::{Arc, Mutex, PoisonError};
+use yaml_rust2::{Yaml, YamlLoader};
+
+use crate::http::{Header, Method, ResponseType, ServerInfo};
+use crate::startup::merge_headers;
+
+/**
+An OpenAPI document which served responses are expected to conform to.
+
+Routes are matched to the document's paths by segment, with any path template
+parameter (`{id}`) matching either a route param (`:id`) or a literal segment.
+For a matched operation, a response's status must be documented (exactly, by
+range such as `2XX`, or by `default`), any required response headers must be present,
+and JSON bodies must match the documented schema for their content type.
+**/
+pub struct OpenApiContract {
+    document: Value,
+    validators: Mutex<HashMap<String, Arc<Validator>>>,
+}
+
+/// The parts of a served response that are checked against the contract
+pub struct ServedResponse<'a> {
+    pub status: u16,
+    pub headers: &'a [Header],
+    pub response_type: &'a ResponseType,
+    pub body: &'a str,
+}
+
+impl OpenApiContract {
+    pub fn new(document: Value) -> Result<Self> {
+        if !document.get("paths").is_some_and(Value::is_object) {
+            bail!("OpenAPI document does not contain a `paths` object");
+        }
+
+        Ok(OpenApiContract {
+            document,
+            validators: Mutex::new(HashMap::new()),
+        })
+    }
+
+    /// Load an OpenAPI document from a JSON or YAML file
+    pub fn from_file<P: AsRef<Path>>(file_path: P) -> Result<Self> {
+        let file_path = file_path.as_ref();
+        let data = fs::read_to_string(file_path).with_context(|| {
+            format!("Failed to read OpenAPI document `{}`", file_path.display())
+        })?;
+
+        let is_yaml = file_path
+            .extension()
+            .is_some_and(|ext| ext == "yaml" || ext == "yml");
+        let document = if is_yaml {
+            parse_yaml(&data)
+        } else {
+            serde_json::from_str(&data).map_err(anyhow::Error::from)
+        }
+        .with_context(|| format!("Could not parse OpenAPI document `{}`", file_path.display()))?;
+
+        Self::new(document)
+    }
+
+    /// Check every response of every route in `serverinfo`, returning a diagnostic for each violation.
+    /// The bodies of templated responses are not checked, as they are only known once served.
+    pub fn check_serverinfo(&self, serverinfo: &ServerInfo) -> Vec<String> {
+        let mut diagnostics = vec![];
+
+        for route in &serverinfo.router.routes {
+            for response in &route.responses {
+                let headers = merge_headers(
+                    &serverinfo.server.headers,
+                    &route.headers,
+                    &response.headers,
+                );
+                let body = response.get_response_body();
+                let served = ServedResponse {
+                    status: response.status,
+                    headers: &headers,
+                    response_type: &response.response_type,
+                    body: if response.templated { "" } else { &body },
+                };
+
+                for violation in self.check(&route.method, &route.path, &served) {
+                    diagnostics.push(format!(
+                        "{} {} response `{}` ({}): {}",
+                        route.method, route.path, response.name, response.id, violation
+                    ));
+                }
+            }
+        }
+
+        diagnostics
+    }
+
+    /// Check a single response served for `method` and `path` (in Mockerize route format)
+    pub fn check(&self, method: &Method, path: &str, served: &ServedResponse) -> Vec<String> {
+        let Some(path_key) = self.find_path(path) else {
+            return vec![format!("path `{}` is not documented", path)];
+        };
+
+        let operation_pointer = format!(
+            "/paths/{}/{}",
+            escape_pointer_token(&path_key),
+            method.to_string().to_lowercase()
+        );
+        let Some(operation) = self.document.pointer(&operation_pointer) else {
+            return vec![format!(
+                "operation {} `{}` is not documented",
+                method, path_key
+            )];
+        };
+
+        let Some(status_key) = find_status(operation, served.status) else {
+            return vec![format!(
+                "status {} is not documented for {} `{}`",
+                served.status, method, path_key
+            )];
+        };
+
+        let response_pointer = format!("{}/responses/{}", operation_pointer, status_key);
+        let Some((response_pointer, spec)) = self.resolve(&response_pointer) else {
+            return vec![format!("could not resolve `{}`", response_pointer)];
+        };
+
+        let mut violations = vec![];
+        violations.extend(check_headers(spec, served.headers));
+        violations.extend(self.check_body(&response_pointer, spec, served));
+        violations
+    }
+
+    // Find the documented path best matching `path`, preferring literal segment matches over templated ones
+    fn find_path(&self, path: &str) -> Option<String> {
+        let route_segments: Vec<_> = path.trim_matches('/').split('/').collect();
+        let paths = self.document.get("paths")?.as_object()?;
+
+        paths
+            .keys()
+            .filter_map(|key| {
+                let spec_segments: Vec<_> = key.trim_matches('/').split('/').collect();
+                if spec_segments.len() != route_segments.len() {
+                    return None;
+                }
+
+                let mut literal_matches = 0;
+                for (spec, route) in spec_segments.iter().zip(&route_segments) {
+                    if spec.starts_with('{') && spec.ends_with('}') {
+                        continue;
+                    } else if spec == route {
+                        literal_matches += 1;
+                    } else {
+                        return None;
+                    }
+                }
+                Some((literal_matches, key))
+            })
+            .max_by_key(|(literal_matches, _)| *literal_matches)
+            .map(|(_, key)| key.clone())
+    }
+
+    // Follow `$ref`s (within this document) until reaching a concrete object
+    fn resolve(&self, pointer: &str) -> Option<(String, &Value)> {
+        let mut pointer = pointer.to_string();
+        for _ in 0..16 {
+            let value = self.document.pointer(&pointer)?;
+            match value.get("$ref").and_then(Value::as_str) {
+                Some(reference) => pointer = reference.strip_prefix('#')?.to_string(),
+                None => return Some((pointer, value)),
+            }
+        }
+        None
+    }
+
+    fn check_body(
+        &self,
+        response_pointer: &str,
+        spec: &Value,
+        served: &ServedResponse,
+    ) -> Vec<String> {
+        let content_type = content_type(served.headers, served.response_type);
+        let Some(content) = spec.get("content").and_then(Value::as_object) else {
+            if !served.body.is_empty() {
+                return vec!["response has a body, but none is documented".to_string()];
+            }
+            return vec![];
+        };
+
+        let (major, _) = content_type.split_once('/').unwrap_or((&content_type, ""));
+        let media_key = [
+            content_type.clone(),
+            format!("{}/*", major),
+            "*/*".to_string(),
+        ]
+        .into_iter()
+        .find(|key| content.contains_key(key));
+        let Some(media_key) = media_key else {
+            return vec![format!("content type `{}` is not documented", content_type)];
+        };
+
+        let is_json = content_type.ends_with("/json") || content_type.ends_with("+json");
+        if !is_json || served.body.is_empty() || content[&media_key].get("schema").is_none() {
+            return vec![];
+        }
+
+        let body: Value = match serde_json::from_str(served.body) {
+            Ok(body) => body,
+            Err(e) => return vec![format!("body is not valid JSON: {}", e)],
+        };
+
+        let schema_pointer = format!(
+            "{}/content/{}/schema",
+            response_pointer,
+            escape_pointer_token(&media_key)
+        );
+        let validator = match self.validator(&schema_pointer) {
+            Ok(validator) => validator,
+            Err(e) => return vec![format!("could not compile schema: {}", e)],
+        };
+
+        validator
+            .iter_errors(&body)
+            .map(|error| match error.instance_path.to_string() {
+                path if path.is_empty() => format!("body: {}", error),
+                path => format!("body at `{}`: {}", path, error),
+            })
+            .collect()
+    }
+
+    // Compile (or fetch from cache) a validator for the schema at `pointer`. The whole document is
+    // used as the root schema, so that `$ref`s to `#/components/schemas/...` resolve.
+    fn validator(&self, pointer: &str) -> Result<Arc<Validator>> {
+        let mut validators = self
+            .validators
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner);
+        if let Some(validator) = validators.get(pointer) {
+            return Ok(Arc::clone(validator));
+        }
+
+        let mut root = self.document.clone();
+        root["$ref"] = Value::String(format!("#{}", percent_encode_fragment(pointer)));
+        let validator = jsonschema::validator_for(&root).map_err(|e| anyhow!("{}", e))?;
+        let validator = Arc::new(validator);
+        validators.insert(pointer.to_string(), Arc::clone(&validator));
+        Ok(validator)
+    }
+}
+
+fn find_status(operation: &Value, status: u16) -> Option<String> {
+    let responses = operation.get("responses")?.as_object()?;
+    let status = status.to_string();
+    let range = format!("{}XX", &status[..1]);
+
+    responses
+        .keys()
+        .find(|key| **key == status)
+        .or_else(|| {
+            responses
+                .keys()
+                .find(|key| key.eq_ignore_ascii_case(&range))
+        })
+        .or_else(|| responses.keys().find(|key| *key == "default"))
+        .cloned()
+}
+
+fn check_headers(spec: &Value, headers: &[Header]) -> Vec<String> {
+    let Some(documented) = spec.get("headers").and_then(Value::as_object) else {
+        return vec![];
+    };
+
+    documented
+        .iter()
+        .filter(|(_, header)| header.get("required").and_then(Value::as_bool) == Some(true))
+        .filter(|(name, _)| !headers.iter().any(|h| h.key.eq_ignore_ascii_case(name)))
+        .map(|(name, _)| format!("required header `{}` is missing", name))
+        .collect()
+}
+
+// The content type of a response is given by its `Content-Type` header, or otherwise implied by its type
+fn content_type(headers: &[Header], response_type: &ResponseType) -> String {
+    let header = headers
+        .iter()
+        .find(|h| h.key.eq_ignore_ascii_case("content-type"))
+        .map(|h| {
+            h.value
+                .split(';')
+                .next()
+                .unwrap_or_default()
+                .trim()
+                .to_lowercase()
+        });
+
+    header.unwrap_or_else(|| match response_type {
+        ResponseType::Json => "application/json".to_string(),
+        ResponseType::Sse => "text/event-stream".to_string(),
+        _ => "text/plain".to_string(),
+    })
+}
+
+fn escape_pointer_token(token: &str) -> String {
+    token.replace('~', "~0").replace('/', "~1")
+}
+
+fn percent_encode_fragment(fragment: &str) -> String {
+    fragment
+        .bytes()
+        .map(|b| match b {
+            b'a'..=b'z' | b'A'..=b'Z' | b'0'..=b'9' | b'-' | b'.' | b'_' | b'~' | b'/' => {
+                (b as char).to_string()
+            }
+            _ => format!("%{:02X}", b),
+        })
+        .collect()
+}
+
+// Parse the first document of a YAML file as JSON
+fn parse_yaml(data: &str) -> Result<Value> {
+    let document = YamlLoader::load_from_str(data)?
+        .into_iter()
+        .next()
+        .unwrap_or(Yaml::Null);
+    yaml_to_json(document)
+}
+
+fn yaml_to_json(yaml: Yaml) -> Result<Value> {
+    Ok(match yaml {
+        Yaml::Null => Value::Null,
+        Yaml::Boolean(b) => Value::Bool(b),
+        Yaml::Integer(i) => Value::from(i),
+        Yaml::Real(ref real) => yaml
+            .as_f64()
+            .and_then(serde_json::Number::from_f64)
+            .map(Value::Number)
+            .ok_or_else(|| anyhow!("Unsupported number `{}`", real))?,
+        Yaml::String(s) => Value::String(s),
+        Yaml::Array(items) => items
+            .into_iter()
+            .map(yaml_to_json)
+            .collect::<Result<_>>()
+            .map(Value::Array)?,
+        Yaml::Hash(entries) => entries
+            .into_iter()
+            .map(|(key, value)| Ok((yaml_key(key)?, yaml_to_json(value)?)))
+            .collect::<Result<_>>()
+            .map(Value::Object)?,
+        Yaml::Alias(_) | Yaml::BadValue => bail!("Undefined YAML alias"),
+    })
+}
+
+// Map keys such as status codes may be any scalar, but JSON only has string keys
+fn yaml_key(key: Yaml) -> Result<String> {
+    match key {
+        Yaml::String(s) | Yaml::Real(s) => Ok(s),
+        Yaml::Integer(i) => Ok(i.to_string()),
+        Yaml::Boolean(b) => Ok(b.to_string()),
+        Yaml::Null => Ok("null".to_string()),
+        _ => bail!("Unsupported YAML map key"),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn make_contract() -> OpenApiContract {
+        OpenApiContract::new(json!({
+            "openapi": "3.0.3",
+            "paths": {
+                "/users/{userId}": {
+                    "get": {
+                        "responses": {
+                            "200": {
+                                "description": "A user",
+                                "headers": { "X-Request-Id": { "required": true } },
+                                "content": {
+                                    "application/json": {
+                                        "schema": { "$ref": "#/components/schemas/User" }
+                                    }
+                                }
+                            },
+                            "4XX": { "$ref": "#/components/responses/Error" }
+                        }
+                    }
+                },
+                "/users/me": {
+                    "get": { "responses": { "204": { "description": "Me" } } }
+                }
+            },
+            "components": {
+                "schemas": {
+                    "User": {
+                        "type": "object",
+                        "required": ["id", "name"],
+                        "properties": { "id": { "type": "integer" }, "name": { "type": "string" } }
+                    }
+                },
+                "responses": {
+                    "Error": {
+                        "description": "Error",
+                        "content": { "text/plain": {} }
+                    }
+                }
+            }
+        }))
+        .unwrap()
+    }
+
+    fn served<'a>(status: u16, headers: &'a [Header], body: &'a str) -> ServedResponse<'a> {
+        ServedResponse {
+            status,
+            headers,
+            response_type: &ResponseType::Json,
+            body,
+        }
+    }
+
+    #[test]
+    fn conforming_responses_have_no_violations() {
+        let contract = make_contract();
+        let headers = [Header::new("X-Request-Id", "1")];
+
+        let violations = contract.check(
+            &Method::GET,
+            "/users/:id",
+            &served(200, &headers, r#"{"id": 1, "name": "Alice"}"#),
+        );
+        assert_eq!(violations, Vec::<String>::new());
+
+        let text = [Header::new("Content-Type", "text/plain")];
+        let violations = contract.check(&Method::GET, "/users/:id", &served(404, &text, "nope"));
+        assert_eq!(violations, Vec::<String>::new());
+
+        // Literal segments are preferred over templated ones
+        let violations = contract.check(&Method::GET, "/users/me", &served(204, &[], ""));
+        assert_eq!(violations, Vec::<String>::new());
+    }
+
+    #[test]
+    fn violations_are_described() {
+        let contract = make_contract();
+
+        let violations = contract.check(
+            &Method::GET,
+            "/users/:id",
+            &served(200, &[], r#"{"id": "1"}"#),
+        );
+        assert_eq!(violations.len(), 3);
+        assert!(violations[0].contains("X-Request-Id"));
+        // Schema errors follow the order of the schema's keywords
+        assert!(violations[1].contains("name"));
+        assert!(violations[2].contains("/id"));
+
+        let violations = contract.check(&Method::GET, "/users/:id", &served(500, &[], ""));
+        assert_eq!(
+            violations,
+            vec!["status 500 is not documented for GET `/users/{userId}`"]
+        );
+
+        let violations = contract.check(&Method::POST, "/users/:id", &served(200, &[], ""));
+        assert_eq!(
+            violations,
+            vec!["operation POST `/users/{userId}` is not documented"]
+        );
+
+        let violations = contract.check(&Method::GET, "/orders", &served(200, &[], ""));
+        assert_eq!(violations, vec!["path `/orders` is not documented"]);
+
+        let violations = contract.check(&Method::GET, "/users/:id", &served(404, &[], "{}"));
+        assert_eq!(
+            violations,
+            vec!["content type `application/json` is not documented"]
+        );
+    }
+
+    #[test]
+    fn parses_yaml_as_json() {
+        let yaml = r#"
+openapi: 3.0.3
+paths:
+  /users:
+    get:
+      responses:
+        200: &ok
+          description: OK
+        default: *ok
+      x-rate: 1.5
+      deprecated: false
+      security: [~]
+"#;
+        let document = parse_yaml(yaml).unwrap();
+        assert_eq!(
+            document,
+            json!({
+                "openapi": "3.0.3",
+                "paths": { "/users": { "get": {
+                    "responses": {
+                        "200": { "description": "OK" },
+                        "default": { "description": "OK" }
+                    },
+                    "x-rate": 1.5,
+                    "deprecated": false,
+                    "security": [null]
+                } } }
+            })
+        );
+
+        assert!(parse_yaml("paths: *undefined").is_err());
+        assert!(parse_yaml("paths: [").is_err());
+    }
+
+    #[test]
+    fn documents_without_paths_are_rejected() {
+        assert!(OpenApiContract::new(json!({ "openapi": "3.0.3" })).is_err());
+    }
+}
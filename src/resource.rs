@@ -0,0 +1,371 @@
+use actix_web::http::StatusCode;
+use actix_web::web::{self, Query};
+use actix_web::{HttpResponse, HttpResponseBuilder};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::collections::HashMap;
+use std::future::ready;
+use std::sync::{Arc, PoisonError, RwLock};
+use tracing::debug;
+use uuid::Uuid;
+
+use crate::http::{Header, Resource};
+use crate::startup::transform_route_path;
+use crate::template::value_to_string;
+
+#[derive(Debug)]
+struct Collection {
+    resource: Resource,
+    items: RwLock<Vec<Value>>,
+}
+
+impl Collection {
+    fn position(items: &[Value], id_field: &str, id: &str) -> Option<usize> {
+        items.iter().position(|item| {
+            item.get(id_field)
+                .is_some_and(|value| value_to_string(value) == id)
+        })
+    }
+}
+
+/// A single page of items, as returned by a resource's list endpoint
+#[derive(Debug, Serialize, PartialEq)]
+pub struct Page {
+    pub data: Vec<Value>,
+    pub page: usize,
+    pub per_page: usize,
+    pub total_pages: usize,
+    pub total: usize,
+}
+
+#[derive(Debug, PartialEq)]
+pub enum ResourceError {
+    NotFound,
+    Conflict,
+    NotAnObject,
+}
+
+/// The in-memory collections backing every `Resource` of a single server.
+/// Cloning a `ResourceStore` yields another handle to the same underlying data.
+#[derive(Debug, Clone, Default)]
+pub struct ResourceStore {
+    collections: Arc<HashMap<String, Collection>>,
+}
+
+impl ResourceStore {
+    pub fn new(resources: &[Resource]) -> Self {
+        let collections = resources
+            .iter()
+            .map(|resource| {
+                let collection = Collection {
+                    resource: resource.clone(),
+                    items: RwLock::new(resource.seed.clone()),
+                };
+                (resource.name.clone(), collection)
+            })
+            .collect();
+
+        ResourceStore {
+            collections: Arc::new(collections),
+        }
+    }
+
+    fn collection(&self, name: &str) -> Result<&Collection, ResourceError> {
+        self.collections.get(name).ok_or(ResourceError::NotFound)
+    }
+
+    /// List items of resource `name`. Pages start at 1; if `per_page` is not given, all items are returned.
+    pub fn list(
+        &self,
+        name: &str,
+        page: Option<usize>,
+        per_page: Option<usize>,
+    ) -> Result<Page, ResourceError> {
+        let collection = self.collection(name)?;
+        let items = collection
+            .items
+            .read()
+            .unwrap_or_else(PoisonError::into_inner);
+
+        let total = items.len();
+        let per_page = per_page.unwrap_or(total).max(1);
+        let page = page.unwrap_or(1).max(1);
+        let data = items
+            .iter()
+            .skip((page - 1).saturating_mul(per_page))
+            .take(per_page)
+            .cloned()
+            .collect();
+
+        Ok(Page {
+            data,
+            page,
+            per_page,
+            total_pages: total.div_ceil(per_page),
+            total,
+        })
+    }
+
+    pub fn get(&self, name: &str, id: &str) -> Result<Value, ResourceError> {
+        let collection = self.collection(name)?;
+        let items = collection
+            .items
+            .read()
+            .unwrap_or_else(PoisonError::into_inner);
+
+        Collection::position(&items, &collection.resource.id_field, id)
+            .map(|idx| items[idx].clone())
+            .ok_or(ResourceError::NotFound)
+    }
+
+    /// Add `item` to resource `name`, generating an ID for it if it does not have one
+    pub fn create(&self, name: &str, mut item: Value) -> Result<Value, ResourceError> {
+        let collection = self.collection(name)?;
+        let id_field = &collection.resource.id_field;
+        let object = item.as_object_mut().ok_or(ResourceError::NotAnObject)?;
+
+        if !object.contains_key(id_field) {
+            // Generated IDs come first, as if the client had sent them
+            let id = Value::String(Uuid::new_v4().to_string());
+            object.shift_insert(0, id_field.clone(), id);
+        }
+        let id = value_to_string(&object[id_field]);
+
+        let mut items = collection
+            .items
+            .write()
+            .unwrap_or_else(PoisonError::into_inner);
+        if Collection::position(&items, id_field, &id).is_some() {
+            return Err(ResourceError::Conflict);
+        }
+
+        items.push(item.clone());
+        Ok(item)
+    }
+
+    /// Replace the item identified by `id`. The item keeps its original ID.
+    pub fn update(&self, name: &str, id: &str, mut item: Value) -> Result<Value, ResourceError> {
+        let collection = self.collection(name)?;
+        let id_field = &collection.resource.id_field;
+        let object = item.as_object_mut().ok_or(ResourceError::NotAnObject)?;
+
+        let mut items = collection
+            .items
+            .write()
+            .unwrap_or_else(PoisonError::into_inner);
+        let idx = Collection::position(&items, id_field, id).ok_or(ResourceError::NotFound)?;
+
+        if let Some(existing_id) = items[idx].get(id_field) {
+            object.shift_insert(0, id_field.clone(), existing_id.clone());
+        }
+        items[idx] = item.clone();
+        Ok(item)
+    }
+
+    pub fn delete(&self, name: &str, id: &str) -> Result<(), ResourceError> {
+        let collection = self.collection(name)?;
+        let mut items = collection
+            .items
+            .write()
+            .unwrap_or_else(PoisonError::into_inner);
+        let idx = Collection::position(&items, &collection.resource.id_field, id)
+            .ok_or(ResourceError::NotFound)?;
+
+        items.remove(idx);
+        Ok(())
+    }
+
+    /// Return resource `name` to its seed data
+    pub fn reset_resource(&self, name: &str) -> Result<(), ResourceError> {
+        let collection = self.collection(name)?;
+        let mut items = collection
+            .items
+            .write()
+            .unwrap_or_else(PoisonError::into_inner);
+        items.clone_from(&collection.resource.seed);
+        Ok(())
+    }
+
+    /// Return every resource to its seed data
+    pub fn reset(&self) {
+        for name in self.collections.keys() {
+            let _ = self.reset_resource(name);
+        }
+    }
+}
+
+#[derive(Debug, Deserialize)]
+struct Pagination {
+    page: Option<usize>,
+    per_page: Option<usize>,
+}
+
+/// Register the generated endpoints of each resource onto an Actix app.
+/// Server-level headers are included in every response.
+pub fn configure(cfg: &mut web::ServiceConfig, resources: &[Resource], server_headers: &[Header]) {
+    for resource in resources {
+        let base_path = transform_route_path(&resource.base_path);
+        let item_path = transform_route_path(&resource.item_path());
+        let name: Arc<str> = Arc::from(resource.name.as_str());
+        let headers: Arc<[Header]> = Arc::from(server_headers);
+
+        let (n, h) = (name.clone(), headers.clone());
+        cfg.route(
+            &base_path,
+            web::get().to(
+                move |store: web::Data<ResourceStore>, query: Query<Pagination>| {
+                    let result = store.list(&n, query.page, query.per_page);
+                    ready(respond(&h, result, StatusCode::OK))
+                },
+            ),
+        );
+
+        let (n, h) = (name.clone(), headers.clone());
+        cfg.route(
+            &base_path,
+            web::post().to(
+                move |store: web::Data<ResourceStore>, item: web::Json<Value>| {
+                    let result = store.create(&n, item.into_inner());
+                    ready(respond(&h, result, StatusCode::CREATED))
+                },
+            ),
+        );
+
+        let (n, h) = (name.clone(), headers.clone());
+        cfg.route(
+            &item_path,
+            web::get().to(
+                move |store: web::Data<ResourceStore>, id: web::Path<String>| {
+                    let result = store.get(&n, &id);
+                    ready(respond(&h, result, StatusCode::OK))
+                },
+            ),
+        );
+
+        let (n, h) = (name.clone(), headers.clone());
+        cfg.route(
+            &item_path,
+            web::put().to(
+                move |store: web::Data<ResourceStore>,
+                      id: web::Path<String>,
+                      item: web::Json<Value>| {
+                    let result = store.update(&n, &id, item.into_inner());
+                    ready(respond(&h, result, StatusCode::OK))
+                },
+            ),
+        );
+
+        let (n, h) = (name, headers);
+        cfg.route(
+            &item_path,
+            web::delete().to(
+                move |store: web::Data<ResourceStore>, id: web::Path<String>| {
+                    let result = store.delete(&n, &id).map(|_| Value::Null);
+                    ready(respond(&h, result, StatusCode::NO_CONTENT))
+                },
+            ),
+        );
+    }
+}
+
+fn respond<T: Serialize>(
+    headers: &[Header],
+    result: Result<T, ResourceError>,
+    success: StatusCode,
+) -> HttpResponse {
+    let status = match &result {
+        Ok(_) => success,
+        Err(ResourceError::NotFound) => StatusCode::NOT_FOUND,
+        Err(ResourceError::Conflict) => StatusCode::CONFLICT,
+        Err(ResourceError::NotAnObject) => StatusCode::BAD_REQUEST,
+    };
+    debug!("Responding to resource request with status code {status}");
+
+    let mut resp = HttpResponseBuilder::new(status);
+    for header in headers {
+        resp.append_header((header.key.clone(), header.value.clone()));
+    }
+
+    match result {
+        Ok(_) if status == StatusCode::NO_CONTENT => resp.finish(),
+        Ok(body) => resp.json(body),
+        Err(_) => resp.finish(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn make_store() -> ResourceStore {
+        let mut resource = Resource::new("users", "/api/users");
+        resource.seed = vec![
+            json!({"id": 1, "name": "Alice"}),
+            json!({"id": 2, "name": "Bob"}),
+            json!({"id": 3, "name": "Carol"}),
+        ];
+        ResourceStore::new(&[resource])
+    }
+
+    #[test]
+    fn list_paginates_items() {
+        let store = make_store();
+
+        let page = store.list("users", None, None).unwrap();
+        assert_eq!(page.data.len(), 3);
+        assert_eq!(page.total_pages, 1);
+
+        let page = store.list("users", Some(2), Some(2)).unwrap();
+        assert_eq!(page.data, vec![json!({"id": 3, "name": "Carol"})]);
+        assert_eq!(page.total_pages, 2);
+        assert_eq!(page.total, 3);
+
+        assert_eq!(
+            store.list("unknown", None, None),
+            Err(ResourceError::NotFound)
+        );
+    }
+
+    #[test]
+    fn create_update_and_delete_items() {
+        let store = make_store();
+
+        let created = store.create("users", json!({"name": "Dave"})).unwrap();
+        let id = created["id"].as_str().unwrap().to_string();
+        assert_eq!(store.get("users", &id).unwrap(), created);
+
+        assert_eq!(
+            store.create("users", json!({"id": 1})),
+            Err(ResourceError::Conflict)
+        );
+        assert_eq!(
+            store.create("users", json!([])),
+            Err(ResourceError::NotAnObject)
+        );
+
+        let updated = store
+            .update("users", "1", json!({"name": "Alicia"}))
+            .unwrap();
+        assert_eq!(updated, json!({"id": 1, "name": "Alicia"}));
+        assert_eq!(
+            store.update("users", "99", json!({})),
+            Err(ResourceError::NotFound)
+        );
+
+        assert_eq!(store.delete("users", "2"), Ok(()));
+        assert_eq!(store.get("users", "2"), Err(ResourceError::NotFound));
+    }
+
+    #[test]
+    fn reset_restores_seed_data() {
+        let store = make_store();
+        store.delete("users", "1").unwrap();
+        store.create("users", json!({"name": "Dave"})).unwrap();
+
+        store.reset();
+        let page = store.list("users", None, None).unwrap();
+        assert_eq!(page.total, 3);
+        assert_eq!(page.data[0], json!({"id": 1, "name": "Alice"}));
+    }
+}
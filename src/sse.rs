@@ -0,0 +1,153 @@
+use actix_web::rt::time;
+use actix_web::web::Bytes;
+use anyhow::{Context, Result};
+use futures_util::stream::{self, Stream};
+use std::convert::Infallible;
+use std::time::Duration;
+
+use crate::http::{ResponseType, ServerInfo, SseEvent};
+
+/// Check that the body of every `sse` response in `serverinfo` is a valid list of events.
+/// Templated bodies are only known once rendered, so are checked as they are served.
+pub fn check_event_streams(serverinfo: &ServerInfo) -> Result<()> {
+    for route in &serverinfo.router.routes {
+        for response in &route.responses {
+            if response.response_type == ResponseType::Sse && !response.templated {
+                response.get_events().with_context(|| {
+                    format!(
+                        "Invalid SSE response for route {} {}",
+                        route.method, route.path
+                    )
+                })?;
+            }
+        }
+    }
+
+    Ok(())
+}
+
+/**
+The sequence `events` are emitted in, honouring each event's `repeat`. Repeats are produced
+lazily, so that a large `repeat` is never held in memory.
+If the client is reconnecting with a `Last-Event-ID`, the sequence resumes after the
+first event with that ID; an unknown ID restarts the sequence from the beginning.
+**/
+pub fn schedule(
+    events: Vec<SseEvent>,
+    last_event_id: Option<&str>,
+) -> impl Iterator<Item = SseEvent> {
+    let mut events: Vec<(SseEvent, u32)> = events
+        .into_iter()
+        .map(|event| {
+            let count = event.repeat.unwrap_or(1);
+            (event, count)
+        })
+        .collect();
+
+    // Events repeated zero times are never emitted, so can't be resumed from
+    let resume_from = last_event_id.and_then(|id| {
+        events
+            .iter()
+            .position(|(e, count)| *count > 0 && e.id.as_deref() == Some(id))
+    });
+    if let Some(idx) = resume_from {
+        events.drain(..idx);
+        events[0].1 -= 1;
+    }
+
+    events
+        .into_iter()
+        .flat_map(|(event, count)| std::iter::repeat_n(event, count as usize))
+}
+
+/// Stream `events` in the `text/event-stream` format, waiting each event's `delayMs` before emitting it
+pub fn event_stream(
+    events: impl Iterator<Item = SseEvent>,
+) -> impl Stream<Item = Result<Bytes, Infallible>> {
+    stream::unfold(events, |mut events| async move {
+        let event = events.next()?;
+        if event.delay_ms > 0 {
+            time::sleep(Duration::from_millis(event.delay_ms)).await;
+        }
+        Some((Ok(Bytes::from(event.to_wire())), events))
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::http::{Method, Response, Route, Router, Server};
+
+    fn make_event(id: &str) -> SseEvent {
+        SseEvent {
+            id: Some(id.to_string()),
+            ..Default::default()
+        }
+    }
+
+    fn ids(events: impl Iterator<Item = SseEvent>) -> Vec<String> {
+        events.filter_map(|e| e.id).collect()
+    }
+
+    #[test]
+    fn check_event_streams_skips_templated_responses() {
+        let mut router = Router::new(None);
+        let server = Server::new(router.id, "127.0.0.1", 0).unwrap();
+        router.bind_server(&server);
+        let mut route = Route::new("/events", Method::GET);
+        // Only a list of events once rendered
+        let mut response = Response::new("", 200, ResponseType::Sse, "{{body}}");
+        route.add_response(response.clone());
+        router.add_route(route);
+        let mut serverinfo = ServerInfo {
+            server,
+            router,
+            scenarios: vec![],
+        };
+        assert!(check_event_streams(&serverinfo).is_err());
+
+        response.templated = true;
+        serverinfo.router.routes[0].responses = vec![response];
+        assert!(check_event_streams(&serverinfo).is_ok());
+    }
+
+    #[test]
+    fn schedule_expands_repeats() {
+        let mut repeated = make_event("2");
+        repeated.repeat = Some(3);
+        let mut skipped = make_event("4");
+        skipped.repeat = Some(0);
+
+        let events = vec![make_event("1"), repeated, make_event("3"), skipped];
+        assert_eq!(ids(schedule(events, None)), ["1", "2", "2", "2", "3"]);
+    }
+
+    #[test]
+    fn schedule_repeats_lazily() {
+        let mut repeated = make_event("1");
+        repeated.repeat = Some(u32::MAX);
+
+        let mut events = schedule(vec![repeated], None);
+        assert_eq!(
+            events.nth(1_000_000).and_then(|e| e.id).as_deref(),
+            Some("1")
+        );
+    }
+
+    #[test]
+    fn schedule_resumes_after_last_event_id() {
+        let mut repeated = make_event("2");
+        repeated.repeat = Some(3);
+        let events = vec![make_event("1"), repeated, make_event("3")];
+
+        assert_eq!(ids(schedule(events.clone(), Some("2"))), ["2", "2", "3"]);
+        assert_eq!(
+            ids(schedule(events.clone(), Some("3"))),
+            Vec::<String>::new()
+        );
+        assert_eq!(
+            ids(schedule(events, Some("unknown"))),
+            ["1", "2", "2", "2", "3"]
+        );
+    }
+}
@@ -1,56 +1,513 @@
-use actix_web::dev::Server;
-use actix_web::http::StatusCode;
-use actix_web::middleware::Logger;
-use actix_web::{web, App, HttpRequest, HttpResponse, HttpServer};
+use actix_http::body::BoxBody;
+use actix_http::{HttpServiceBuilder, Protocol, Request};
+use actix_server::GracefulShutdownSignal;
+use actix_service::{
+    fn_service, map_config, IntoServiceFactory, ServiceFactory, ServiceFactoryExt,
+};
+use actix_tls::accept::rustls_0_23::TlsStream;
+use actix_web::dev::{AppConfig, Extensions, HttpServiceFactory, RequestHead, Server, Service};
+use actix_web::http::{header, StatusCode, Uri};
+use actix_web::middleware::{from_fn, Logger};
+use actix_web::rt::net::TcpStream;
+#[cfg(unix)]
+use actix_web::rt::net::UnixStream;
+use actix_web::web::Bytes;
+use actix_web::{guard, web, App, HttpRequest, HttpResponse};
+use anyhow::bail;
+use serde_json::json;
+use std::any::Any;
 use std::collections::HashMap;
-use std::net::TcpListener;
+use std::fmt;
+use std::future::ready;
+use std::net::{SocketAddr, TcpListener};
+#[cfg(unix)]
+use std::os::unix::net::UnixListener;
 use std::sync::Arc;
-use tracing::{debug, info, info_span, Instrument};
+use std::time::Duration;
+use tracing::{debug, info, info_span, warn};
 use uuid::Uuid;
 
-use crate::http::{Header, Route, ServerInfo};
+use crate::admin;
+use crate::graphql::{compile_graphql, resolve, GraphqlSchema, Resolved};
+use crate::http::{
+    ClientCertRejection, ClientIdentity, Header, Response, ResponseType, Route, ServerInfo,
+    SseEvent,
+};
+use crate::journal::{self, RequestJournal};
+use crate::openapi::{OpenApiContract, ServedResponse};
+use crate::resource::{self, ResourceStore};
+use crate::sse::{check_event_streams, event_stream, schedule};
+use crate::state::{apply_actions, ScenarioStore, StateStore};
+use crate::streaming::body_stream;
+use crate::template::{render, RequestData, TemplateContext};
+use crate::tls::{client_identity, load_server_config, load_virtual_host_config};
+use crate::validator::{compile_validators, RequestValidator, ValidationFailure};
+use crate::websocket::make_websocket_handler;
+
+/// Runtime options which are not part of a server config
+#[derive(Default)]
+pub struct RunOptions {
+    /// Number of Actix workers to start. Defaults to one per CPU core.
+    pub workers: Option<usize>,
+    /// If given, each served response is checked against this contract and any violations logged
+    pub contract: Option<Arc<OpenApiContract>>,
+    /// Serve HTTPS using this config, rather than any TLS settings of the `Server`
+    pub tls: Option<rustls::ServerConfig>,
+    /// How long in-flight requests are given to finish when stopping gracefully, rounded up to
+    /// whole seconds. Defaults to 30 seconds.
+    pub shutdown_timeout: Option<Duration>,
+    /// Leave handling of SIGINT, SIGTERM and CTRL+C to the caller, rather than stopping the server
+    pub disable_signals: bool,
+    /// If given, every request received is recorded here
+    pub journal: Option<RequestJournal>,
+    /// Labels to log each server with if it has no name, in the order the servers are given
+    pub labels: Vec<String>,
+}
+
+/// A socket for a server to accept connections on
+pub enum Listener {
+    /// Serves HTTPS if TLS is configured, otherwise cleartext HTTP
+    Tcp(TcpListener),
+    /// Always serves cleartext HTTP, even if TLS is configured
+    Cleartext(TcpListener),
+    /// Serves HTTP/1.x only, and does not support TLS
+    #[cfg(unix)]
+    Unix(UnixListener),
+}
+
+impl From<TcpListener> for Listener {
+    fn from(listener: TcpListener) -> Self {
+        Listener::Tcp(listener)
+    }
+}
+
+impl Listener {
+    /// Duplicate the socket, so that it stays open for another server once one using it has stopped
+    pub fn try_clone(&self) -> std::io::Result<Listener> {
+        Ok(match self {
+            Listener::Tcp(listener) => Listener::Tcp(listener.try_clone()?),
+            Listener::Cleartext(listener) => Listener::Cleartext(listener.try_clone()?),
+            #[cfg(unix)]
+            Listener::Unix(listener) => Listener::Unix(listener.try_clone()?),
+        })
+    }
+}
+
+#[cfg(unix)]
+impl From<UnixListener> for Listener {
+    fn from(listener: UnixListener) -> Self {
+        Listener::Unix(listener)
+    }
+}
 
 /// Starts a new Actix server to listen and begin accepting HTTP traffic on the
-/// given `TcpListener`. Responses to those HTTP requests will be dicated by
-/// the given `ServerInfo`. By default, Actix will start one worker per CPU
-/// core, however, this may be overridden by specifying a count for `workers`.
+/// given `Listener`, either a `TcpListener` or a `UnixListener`. Responses to
+/// those HTTP requests will be dicated by the given `ServerInfo`. By default,
+/// Actix will start one worker per CPU core, however, this may be overridden by
+/// specifying a count for `options.workers`.
+#[allow(dead_code)]
 pub fn run(
     serverinfo: ServerInfo,
-    listener: TcpListener,
-    workers: Option<usize>,
+    listener: impl Into<Listener>,
+    options: RunOptions,
+) -> Result<Server, std::io::Error> {
+    run_listeners(serverinfo, vec![listener.into()], options)
+}
+
+/// As `run`, but accepting traffic on every one of `listeners`
+pub fn run_listeners(
+    serverinfo: ServerInfo,
+    listeners: Vec<Listener>,
+    options: RunOptions,
+) -> Result<Server, std::io::Error> {
+    run_virtual_hosts(vec![serverinfo], listeners, options)
+}
+
+/**
+As `run_listeners`, but serving several servers on the same listeners. Each request is dispatched
+to the server whose `hosts` match its `Host` header and, over TLS, each server's certificate is chosen
+by SNI. Requests for any other host go to the server without `hosts`, or failing that, the first server.
+Settings of the listeners themselves, such as `http2`, are taken from that default server.
+**/
+pub fn run_virtual_hosts(
+    mut servers: Vec<ServerInfo>,
+    listeners: Vec<Listener>,
+    options: RunOptions,
 ) -> Result<Server, std::io::Error> {
-    for route in &serverinfo.router.routes {
-        info!("Registering route {} {}", &route.method, &route.path);
+    let mut labels: Vec<String> = servers
+        .iter()
+        .enumerate()
+        .map(|(i, serverinfo)| match options.labels.get(i) {
+            Some(label) if serverinfo.server.name.is_empty() => label.clone(),
+            _ => serverinfo.server.name.clone(),
+        })
+        .collect();
+
+    // The default server is registered last, so that it only sees requests no other server answers to
+    let default = default_host(&servers).map_err(invalid_config)?;
+    let default_label = labels.remove(default);
+    let default = servers.remove(default);
+    let http2 = default.server.http2;
+    servers.push(default);
+    labels.push(default_label);
+
+    let tls = match options.tls {
+        Some(tls) => Some(tls),
+        None => load_tls(&servers).map_err(invalid_config)?,
+    };
+
+    let hosts = servers
+        .into_iter()
+        .zip(labels)
+        .map(|(serverinfo, label)| VirtualHost::compile(serverinfo, label))
+        .collect::<anyhow::Result<Vec<_>>>()
+        .map_err(invalid_config)?;
+    let hosts = Arc::new(hosts);
+    let contract = options.contract;
+    let journal = options.journal.map(web::Data::new);
+
+    let make_app = move || {
+        // Mocks answer requests with bodies of any size, rather than actix's default limit of 256 KiB
+        let mut app = App::new()
+            .app_data(web::PayloadConfig::new(usize::MAX))
+            .wrap(from_fn(journal::record))
+            .wrap(Logger::default());
+        if let Some(journal) = &journal {
+            app = app.app_data(journal.clone());
+        }
+        for (i, host) in hosts.iter().enumerate() {
+            let guarded = i + 1 < hosts.len();
+            app = app.service(host.service(guarded, &contract));
+        }
+        app
+    };
+    // The app for one listener, with the config actix-web's `HttpServer` would give it. That config
+    // can only be made through an API exempt from semver, so actix-web is pinned to an exact version.
+    let app_factory = move |secure: bool, addr: SocketAddr| {
+        let app = make_app()
+            .into_factory()
+            .map_err(|e: actix_web::Error| e.error_response());
+        map_config(app, move |_| {
+            AppConfig::__priv_test_new(secure, addr.to_string(), addr)
+        })
+    };
+
+    let mut server = Server::build();
+    if let Some(workers) = options.workers {
+        server = server.workers(workers);
+    }
+    if let Some(timeout) = options.shutdown_timeout {
+        server = server.shutdown_timeout(timeout.as_secs_f64().ceil() as u64);
+    }
+    if options.disable_signals {
+        server = server.disable_signals();
     }
+    let signal = server.graceful_shutdown_signal();
+
+    for listener in listeners {
+        let signal = signal.clone();
+        let app_factory = app_factory.clone();
+        server = match (listener, &tls) {
+            (Listener::Tcp(listener), Some(tls)) if http2 => {
+                let addr = listener.local_addr()?;
+                let tls = tls.clone();
+                server.listen(service_name(addr), listener, move || {
+                    service_builder(&signal, Some(addr))
+                        .finish(app_factory(true, addr))
+                        .rustls_0_23(tls.clone())
+                })?
+            }
+            // Actix always offers `h2` over TLS, so serve HTTP/1.x alone and only offer `http/1.1`
+            (Listener::Tcp(listener), Some(tls)) => {
+                let addr = listener.local_addr()?;
+                let mut tls = tls.clone();
+                tls.alpn_protocols = vec![b"http/1.1".to_vec()];
+                server.listen(service_name(addr), listener, move || {
+                    service_builder(&signal, Some(addr))
+                        .h1(app_factory(true, addr))
+                        .rustls_0_23(tls.clone())
+                })?
+            }
+            (Listener::Tcp(listener) | Listener::Cleartext(listener), _) if http2 => {
+                let addr = listener.local_addr()?;
+                server.listen(service_name(addr), listener, move || {
+                    service_builder(&signal, Some(addr))
+                        .finish(app_factory(false, addr))
+                        .tcp_auto_h2c()
+                })?
+            }
+            (Listener::Tcp(listener) | Listener::Cleartext(listener), _) => {
+                let addr = listener.local_addr()?;
+                server.listen(service_name(addr), listener, move || {
+                    service_builder(&signal, Some(addr))
+                        .finish(app_factory(false, addr))
+                        .tcp()
+                })?
+            }
+            #[cfg(unix)]
+            (Listener::Unix(listener), None) => {
+                let name = format!("actix-web-service-{:?}", listener.local_addr()?);
+                // As with `HttpServer`, requests over a Unix socket appear to come to this address
+                let addr = SocketAddr::from(([127, 0, 0, 1], 8080));
+                server.listen_uds(name, listener, move || {
+                    fn_service(|io: UnixStream| async { Ok((io, Protocol::Http1, None)) })
+                        .and_then(service_builder(&signal, None).finish(app_factory(false, addr)))
+                })?
+            }
+            #[cfg(unix)]
+            (Listener::Unix(_), Some(_)) => {
+                return Err(invalid_config(anyhow::anyhow!(
+                    "TLS is not supported over Unix domain sockets"
+                )))
+            }
+        };
+    }
+
+    Ok(server.run())
+}
+
+// An HTTP service builder for one listener, configured as actix-web's `HttpServer` would
+fn service_builder<T, S>(
+    signal: &GracefulShutdownSignal,
+    addr: Option<SocketAddr>,
+) -> HttpServiceBuilder<T, S>
+where
+    T: Any,
+    S: ServiceFactory<Request, Config = ()>,
+    S::Error: Into<actix_http::Response<BoxBody>> + 'static,
+    S::InitError: fmt::Debug,
+    <S::Service as Service<Request>>::Future: 'static,
+{
+    let signal = signal.clone();
+    let builder = HttpServiceBuilder::default()
+        .graceful_shutdown_signal(move || {
+            let signal = signal.clone();
+            async move { signal.notified().await }
+        })
+        .client_disconnect_timeout(Duration::from_secs(1))
+        .on_connect_ext(|io: &T, data: &mut Extensions| store_client_identity(io, data));
+    match addr {
+        Some(addr) => builder.local_addr(addr),
+        None => builder,
+    }
+}
 
-    // We wrap serverinfo in an Arc, otherwise we end up with issues cloning its components down the line
-    // as each Actix worker will need access
-    let serverinfo = Arc::new(serverinfo);
+fn service_name(addr: SocketAddr) -> String {
+    format!("actix-web-service-{}", addr)
+}
 
-    let server = HttpServer::new(move || {
-        let mut app = App::new().wrap(Logger::default());
+// The server to serve requests for unknown hosts: the one without `hosts`, otherwise the first
+fn default_host(servers: &[ServerInfo]) -> anyhow::Result<usize> {
+    let mut defaults = servers
+        .iter()
+        .enumerate()
+        .filter(|(_, serverinfo)| serverinfo.server.hosts.is_empty())
+        .map(|(i, _)| i);
+
+    match (defaults.next(), defaults.next()) {
+        (_, Some(_)) => bail!("Only one server sharing a port may leave `hosts` empty"),
+        (Some(i), None) => Ok(i),
+        (None, None) if servers.is_empty() => bail!("No servers to run"),
+        (None, None) => Ok(0),
+    }
+}
+
+// Load the TLS settings of servers sharing a port, which must either all serve HTTPS or none do
+fn load_tls(servers: &[ServerInfo]) -> anyhow::Result<Option<rustls::ServerConfig>> {
+    let configs = servers
+        .iter()
+        .filter_map(|serverinfo| {
+            let server = &serverinfo.server;
+            server
+                .tls
+                .as_ref()
+                .map(|tls| (server.hosts.as_slice(), tls))
+        })
+        .collect::<Vec<_>>();
+
+    match configs.len() {
+        0 => Ok(None),
+        n if n != servers.len() => bail!("Servers sharing a port must either all use TLS, or none"),
+        1 => load_server_config(configs[0].1).map(Some),
+        _ => load_virtual_host_config(&configs).map(Some),
+    }
+}
+
+// A server, with everything compiled from its config which is shared by every worker
+struct VirtualHost {
+    serverinfo: Arc<ServerInfo>,
+    label: Arc<str>,
+    validators: HashMap<Uuid, Arc<RequestValidator>>,
+    graphql_schemas: HashMap<Uuid, Arc<GraphqlSchema>>,
+    state: web::Data<StateStore>,
+    scenarios: web::Data<ScenarioStore>,
+    resources: web::Data<ResourceStore>,
+    required_client_cert: Option<Arc<ClientCertRejection>>,
+}
+
+impl VirtualHost {
+    fn compile(serverinfo: ServerInfo, label: String) -> anyhow::Result<Self> {
+        let server_span = info_span!("Starting server", server = %label);
+        let _server_span_guard = server_span.enter();
+
+        for route in &serverinfo.router.routes {
+            info!("Registering route {} {}", &route.method, &route.path);
+        }
+        for resource in &serverinfo.router.resources {
+            info!(
+                "Registering resource {} at {}",
+                &resource.name, &resource.base_path
+            );
+        }
+        if !serverinfo.server.hosts.is_empty() {
+            info!("Answering to hosts {}", serverinfo.server.hosts.join(", "));
+        }
+
+        let validators = compile_validators(&serverinfo)?;
+        check_event_streams(&serverinfo)?;
+        let graphql_schemas = compile_graphql(&serverinfo)?;
+
+        let required_client_cert = serverinfo
+            .server
+            .tls
+            .as_ref()
+            .and_then(|tls| tls.client_auth.as_ref())
+            .filter(|client_auth| client_auth.required)
+            .map(|client_auth| Arc::new(client_auth.rejection.clone()));
+
+        // Stores must be created outside of the app factory so that all workers share them
+        Ok(VirtualHost {
+            state: web::Data::new(StateStore::new()),
+            scenarios: web::Data::new(ScenarioStore::new(serverinfo.initial_scenario_states())),
+            resources: web::Data::new(ResourceStore::new(&serverinfo.router.resources)),
+            // We wrap serverinfo in an Arc, otherwise we end up with issues cloning its components down the line
+            // as each Actix worker will need access
+            serverinfo: Arc::new(serverinfo),
+            label: Arc::from(label),
+            validators,
+            graphql_schemas,
+            required_client_cert,
+        })
+    }
+
+    // Build the Actix service for this server, only matching requests for its `hosts` if `guarded`
+    fn service(
+        &self,
+        guarded: bool,
+        contract: &Option<Arc<OpenApiContract>>,
+    ) -> impl HttpServiceFactory {
+        let serverinfo = &self.serverinfo;
+        let rejection = self.required_client_cert.clone();
+        let server_headers = serverinfo.server.headers.clone();
+
+        let mut scope = web::scope("");
+        if guarded {
+            let serverinfo = serverinfo.clone();
+            scope = scope.guard(guard::fn_guard(move |ctx| {
+                request_host(ctx.head()).is_some_and(|host| serverinfo.server.answers_to(&host))
+            }));
+        }
+
+        let mut scope = scope
+            .app_data(self.state.clone())
+            .app_data(self.scenarios.clone())
+            .app_data(self.resources.clone())
+            .configure(admin::configure);
 
         for route in &serverinfo.router.routes {
             let path = transform_route_path(&route.path);
+            if let Some(script) = &route.websocket {
+                scope = scope.route(&path, make_websocket_handler(route, script));
+                continue;
+            }
+
             let server_headers = Arc::new(serverinfo.server.headers.clone());
-            let route_handler = make_route_handler(server_headers, route);
+            let validator = self.validators.get(&route.id).cloned();
+            let graphql_schema = self.graphql_schemas.get(&route.id).cloned();
+            let route_handler = make_route_handler(
+                self.label.clone(),
+                server_headers,
+                route,
+                validator,
+                graphql_schema,
+                contract.clone(),
+            );
 
             if let Some(handler) = route_handler {
-                app = app.route(&path, handler);
+                scope = scope.route(&path, handler);
             }
         }
 
-        app
-    });
+        // Explicitly declared routes take precedence over those generated for resources
+        scope
+            .configure(|cfg| {
+                resource::configure(
+                    cfg,
+                    &serverinfo.router.resources,
+                    &serverinfo.server.headers,
+                )
+            })
+            .wrap_fn(move |req, srv| {
+                let rejected = match &rejection {
+                    // Cleartext listeners, such as those with `"tls": false`, never require a certificate
+                    Some(rejection)
+                        if req.conn_data::<TlsConnection>().is_some()
+                            && req.conn_data::<ClientIdentity>().is_none() =>
+                    {
+                        Err(req.into_response(reject_client(&server_headers, rejection)))
+                    }
+                    _ => Ok(srv.call(req)),
+                };
 
-    let server = if let Some(workers) = workers {
-        server.workers(workers)
-    } else {
-        server
+                async move {
+                    match rejected {
+                        Ok(fut) => fut.await.map(|res| res.map_into_left_body()),
+                        Err(res) => Ok(res.map_into_right_body()),
+                    }
+                }
+            })
+    }
+}
+
+// The host a request is for, from its `Host` header, or its URI for HTTP/2
+fn request_host(head: &RequestHead) -> Option<String> {
+    let from_header = head
+        .headers
+        .get(header::HOST)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.parse::<Uri>().ok())
+        .and_then(|uri| uri.host().map(String::from));
+
+    from_header.or_else(|| head.uri.host().map(String::from))
+}
+
+// Marks connections made over TLS, as only those can present a client certificate
+struct TlsConnection;
+
+// Record the identity of the certificate presented by a TLS client, for response selection
+fn store_client_identity(conn: &dyn Any, data: &mut Extensions) {
+    let Some(stream) = conn.downcast_ref::<TlsStream<TcpStream>>() else {
+        return;
     };
+    data.insert(TlsConnection);
+
+    let cert = stream
+        .get_ref()
+        .1
+        .peer_certificates()
+        .and_then(|certs| certs.first());
+    if let Some(cert) = cert {
+        match client_identity(cert) {
+            Ok(identity) => {
+                data.insert(identity);
+            }
+            Err(e) => warn!("Could not read client certificate: {:#}", e),
+        }
+    }
+}
 
-    let server = server.listen(listener)?.run();
-    Ok(server)
+fn invalid_config(e: anyhow::Error) -> std::io::Error {
+    std::io::Error::new(std::io::ErrorKind::InvalidData, format!("{:#}", e))
 }
 
 /*
@@ -83,59 +540,270 @@ pub fn merge_headers(
 }
 
 // Convert our Route into an Actix-web route handler, which can be bound to an Actix Web App
-fn make_route_handler(server_headers: Arc<Vec<Header>>, route: &Route) -> Option<actix_web::Route> {
-    let response = route.get_active_response()?;
-    let body = Arc::new(response.get_response_body());
-    let status = response.status;
-    let resp_id = response.id;
-    let method = route.method.clone();
-    let path = route.path.clone();
-
-    let route_headers = Arc::new(route.headers.clone());
-    let response_headers = Arc::new(response.headers.clone());
-
-    let handler = move |_req: HttpRequest| {
-        // I'm sure this double-clone isn't the most efficient thing in the world, but...
-        // I don't know a better alternative to prevent borrowed data escaping function.
-        // An Arc on Route won't work, because it would still need to outlive a static lifetime.
-        // TODO: Figure this out.
-        let body = body.clone();
-        let server_headers = Arc::clone(&server_headers);
-        let route_headers = Arc::clone(&route_headers);
-        let response_headers = Arc::clone(&response_headers);
-        let headers = merge_headers(&server_headers, &route_headers, &response_headers.clone());
+fn make_route_handler(
+    server_name: Arc<str>,
+    server_headers: Arc<Vec<Header>>,
+    route: &Route,
+    validator: Option<Arc<RequestValidator>>,
+    graphql_schema: Option<Arc<GraphqlSchema>>,
+    contract: Option<Arc<OpenApiContract>>,
+) -> Option<actix_web::Route> {
+    // Routes without any responses are not served, unless they can mock them from a schema
+    if graphql_schema.is_none() {
+        route.get_active_response()?;
+    }
+
+    let route = Arc::new(route.clone());
+    let method: actix_web::http::Method = route.method.clone().into();
+
+    let route_transition = route.scenario.clone();
+    let route_guard = guard::fn_guard(move |ctx| {
+        ctx.app_data::<web::Data<ScenarioStore>>()
+            .is_none_or(|scenarios| scenarios.matches(&route_transition))
+    });
+
+    let handler = move |req: HttpRequest,
+                        payload: Bytes,
+                        state: web::Data<StateStore>,
+                        scenarios: web::Data<ScenarioStore>| {
+        let request = RequestData::new(&req, &payload);
+        let client = req.conn_data::<ClientIdentity>();
+
+        // Scenarios stay locked from checking the route's requirement until the chosen response's
+        // transitions are applied, so concurrent requests can't both act on the same state. A request
+        // which lost that race to another no longer matches this route, so is not found.
+        let mut scenarios = scenarios.lock();
+        if !scenarios.matches(&route.scenario) {
+            return ready(HttpResponse::NotFound().finish());
+        }
+        let matches = |transition: &_| scenarios.matches(transition);
+        let resolved = match &route.graphql {
+            Some(_) => resolve(&route, graphql_schema.as_deref(), &request, matches, client),
+            // Safe to unwrap; we've already checked that this route has responses
+            None => Ok(Resolved::Response(
+                route.select_response(matches, client).unwrap(),
+            )),
+        };
 
         let request_id = Uuid::new_v4();
         let request_span = info_span!(
             "Client requested mock endpoint",
             %request_id,
-            method = %method,
-            path = %path,
-            response_id = %resp_id
+            server = %server_name,
+            version = ?req.version(),
+            method = %route.method,
+            path = %route.path,
+            response_id = tracing::field::Empty
         );
+        if let Ok(Resolved::Response(response)) = &resolved {
+            request_span.record("response_id", tracing::field::display(response.id));
+        }
 
         let _request_span_guard = request_span.enter();
         let handler_span = info_span!("Handling response");
-
-        async move {
-            let mut resp = HttpResponse::build(StatusCode::from_u16(status).unwrap());
-            let body_len = body.len();
-            debug!("Responding with status code {status}, body {body_len} bytes");
-
-            for header in headers {
-                resp.append_header((header.key.clone(), header.value.clone()));
+        let _handler_span_guard = handler_span.enter();
+
+        let failures = validator
+            .as_ref()
+            .map(|validator| validator.validate(&request))
+            .unwrap_or_default();
+
+        if failures.is_empty() {
+            match &resolved {
+                Ok(Resolved::Response(response)) => {
+                    scenarios.advance(&route.scenario);
+                    scenarios.advance(&response.scenario.inherit(&route.scenario));
+                }
+                Ok(Resolved::Mock(_)) => scenarios.advance(&route.scenario),
+                Err(_) => {}
             }
-            resp.body(body.to_string())
         }
-        .instrument(handler_span)
+        drop(scenarios);
+
+        let http_response = match &validator {
+            Some(validator) if !failures.is_empty() => {
+                reject_request(validator.failure_status, failures)
+            }
+            _ => match resolved {
+                Ok(Resolved::Response(response)) => serve_response(
+                    &route,
+                    response,
+                    &server_headers,
+                    &request,
+                    &state,
+                    contract.as_deref(),
+                ),
+                Ok(Resolved::Mock(data)) => serve_mock(&route, data, &server_headers),
+                Err(e) => reject_graphql(e),
+            },
+        };
+
+        ready(http_response)
     };
 
-    let method: actix_web::http::Method = route.method.clone().into();
-    let route_handler = web::route().method(method).to(handler);
+    let route_handler = web::route().method(method).guard(route_guard).to(handler);
 
     Some(route_handler)
 }
 
+/// Apply the state actions of serving `response`, returning its body, rendered if the
+/// response is templated
+pub(crate) fn prepare_body(
+    response: &Response,
+    request: &RequestData,
+    state: &StateStore,
+) -> String {
+    // Actions and rendering happen under the same lock, so that the response body
+    // reflects exactly the state this request produced
+    if response.templated || !response.state_actions.is_empty() {
+        let mut state = state.write();
+        apply_actions(&mut state, &response.state_actions, request);
+
+        if response.templated {
+            render(
+                &response.get_response_body(),
+                &TemplateContext {
+                    request,
+                    state: &state,
+                    index: None,
+                },
+            )
+        } else {
+            response.get_response_body()
+        }
+    } else {
+        response.get_response_body()
+    }
+}
+
+// Serve `response`, applying any state actions along the way
+fn serve_response(
+    route: &Route,
+    response: &Response,
+    server_headers: &[Header],
+    request: &RequestData,
+    state: &StateStore,
+    contract: Option<&OpenApiContract>,
+) -> HttpResponse {
+    let body = prepare_body(response, request, state);
+
+    let status = response.status;
+    let headers = merge_headers(server_headers, &route.headers, &response.headers);
+
+    if let Some(contract) = contract {
+        let served = ServedResponse {
+            status,
+            headers: &headers,
+            response_type: &response.response_type,
+            body: &body,
+        };
+        for violation in contract.check(&route.method, &route.path, &served) {
+            warn!("Served response violates OpenAPI contract: {}", violation);
+        }
+    }
+
+    let mut resp = HttpResponse::build(StatusCode::from_u16(status).unwrap());
+
+    if response.response_type == ResponseType::Sse {
+        let events = match SseEvent::parse_list(&body) {
+            Ok(events) => events,
+            Err(e) => {
+                warn!("Could not parse SSE events: {:#}", e);
+                return HttpResponse::InternalServerError().finish();
+            }
+        };
+        let last_event_id = request.headers.get("last-event-id").map(String::as_str);
+        let events = schedule(events, last_event_id);
+        debug!("Responding with status code {status}, streaming events");
+
+        let has_header = |key: &str| headers.iter().any(|h| h.key.eq_ignore_ascii_case(key));
+        if !has_header("content-type") {
+            resp.insert_header(("Content-Type", "text/event-stream"));
+        }
+        if !has_header("cache-control") {
+            resp.insert_header(("Cache-Control", "no-cache"));
+        }
+        for header in headers {
+            resp.append_header((header.key.clone(), header.value.clone()));
+        }
+        return resp.streaming(event_stream(events));
+    }
+
+    if let Some(stream) = &response.stream {
+        debug!("Responding with status code {status}, streaming body");
+        for header in headers {
+            resp.append_header((header.key.clone(), header.value.clone()));
+        }
+        let state = state.read().clone();
+        return resp.streaming(body_stream(
+            stream,
+            response.templated,
+            request.clone(),
+            state,
+        ));
+    }
+
+    let body_len = body.len();
+    debug!("Responding with status code {status}, body {body_len} bytes");
+
+    for header in headers {
+        resp.append_header((header.key.clone(), header.value.clone()));
+    }
+    resp.body(body)
+}
+
+// Serve data generated from a GraphQL schema
+fn serve_mock(route: &Route, data: serde_json::Value, server_headers: &[Header]) -> HttpResponse {
+    debug!("Responding with status code 200, mocked from GraphQL schema");
+
+    let headers = merge_headers(server_headers, &route.headers, &[]);
+    let mut resp = HttpResponse::Ok();
+    if !headers
+        .iter()
+        .any(|h| h.key.eq_ignore_ascii_case("content-type"))
+    {
+        resp.insert_header(("Content-Type", "application/json"));
+    }
+    for header in headers {
+        resp.append_header((header.key.clone(), header.value.clone()));
+    }
+    resp.body(data.to_string())
+}
+
+// Respond to a request which is not a valid GraphQL operation
+fn reject_graphql(e: anyhow::Error) -> HttpResponse {
+    warn!("Could not answer GraphQL request: {:#}", e);
+    HttpResponse::BadRequest().json(json!({ "errors": [{ "message": format!("{:#}", e) }] }))
+}
+
+// Respond to a request which failed validation, listing each failure
+fn reject_request(status: u16, failures: Vec<ValidationFailure>) -> HttpResponse {
+    for failure in &failures {
+        warn!(
+            location = failure.location,
+            path = %failure.path,
+            "Request failed validation: {}",
+            failure.message
+        );
+    }
+
+    let status = StatusCode::from_u16(status).unwrap_or(StatusCode::BAD_REQUEST);
+    debug!("Responding with status code {status}");
+    HttpResponse::build(status).json(json!({ "errors": failures }))
+}
+
+// Respond to a client which did not present a required TLS certificate
+fn reject_client(server_headers: &[Header], rejection: &ClientCertRejection) -> HttpResponse {
+    warn!("Rejecting request without a client certificate");
+
+    let status = StatusCode::from_u16(rejection.status).unwrap_or(StatusCode::UNAUTHORIZED);
+    let mut resp = HttpResponse::build(status);
+    for header in merge_headers(server_headers, &rejection.headers, &[]) {
+        resp.append_header((header.key.clone(), header.value.clone()));
+    }
+    resp.body(rejection.response.clone())
+}
+
 /*
 Takes a route path, transforms substitution bindings from Mockerize format to Actix-Web, returns the result.
 Any non-alphanumeric characters within a substitution will be converted to underscore (_).
@@ -144,7 +812,7 @@ If the substutition begins with a non-alphanumeric, the first instance will be i
 
 For example, `/api/v1/users/:user-id` would be transformed to `/api/v1/users/{user_id}`
 */
-fn transform_route_path(input: &str) -> String {
+pub(crate) fn transform_route_path(input: &str) -> String {
     input
         .split('/')
         .map(|s| {
@@ -198,14 +866,14 @@ mod tests {
     #[test]
     fn test_make_route_handler() {
         let response = Response::new("name", 200, ResponseType::Text, "body");
-        let id = response.id.clone();
+        let id = response.id;
 
         let mut route = Route::new("/index", Method::GET);
         route.add_response(response);
         route.set_active_response(id);
 
         let server_headers = Arc::new(vec![]);
-        let handler = make_route_handler(server_headers, &route);
+        let handler = make_route_handler(Arc::from(""), server_headers, &route, None, None, None);
         assert!(handler.is_some());
     }
 }
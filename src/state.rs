@@ -0,0 +1,305 @@
+use serde_json::Value;
+use std::collections::{BTreeMap, HashMap};
+use std::sync::{Arc, PoisonError, RwLock, RwLockReadGuard, RwLockWriteGuard};
+
+use crate::http::{ScenarioTransition, StateAction, DEFAULT_SCENARIO_STATE};
+use crate::template::{render, RequestData, TemplateContext};
+
+pub type StateMap = BTreeMap<String, Value>;
+
+/// An in-memory key/value store, shared between all workers of a single server.
+/// Cloning a `StateStore` yields another handle to the same underlying data.
+#[derive(Debug, Clone, Default)]
+pub struct StateStore {
+    inner: Arc<RwLock<StateMap>>,
+}
+
+impl StateStore {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn read(&self) -> RwLockReadGuard<'_, StateMap> {
+        self.inner.read().unwrap_or_else(PoisonError::into_inner)
+    }
+
+    pub fn write(&self) -> RwLockWriteGuard<'_, StateMap> {
+        self.inner.write().unwrap_or_else(PoisonError::into_inner)
+    }
+
+    /// Remove all stored keys
+    pub fn reset(&self) {
+        self.write().clear();
+    }
+}
+
+/// Tracks the current state of every scenario for a single server.
+/// Cloning a `ScenarioStore` yields another handle to the same underlying data.
+#[derive(Debug, Clone, Default)]
+pub struct ScenarioStore {
+    initial: Arc<HashMap<String, String>>,
+    current: Arc<RwLock<HashMap<String, String>>>,
+}
+
+impl ScenarioStore {
+    pub fn new(initial: HashMap<String, String>) -> Self {
+        ScenarioStore {
+            current: Arc::new(RwLock::new(initial.clone())),
+            initial: Arc::new(initial),
+        }
+    }
+
+    fn initial_state(&self, name: &str) -> String {
+        self.initial
+            .get(name)
+            .cloned()
+            .unwrap_or_else(|| DEFAULT_SCENARIO_STATE.to_string())
+    }
+
+    /// The current state of scenario `name`
+    pub fn get(&self, name: &str) -> String {
+        let current = self.current.read().unwrap_or_else(PoisonError::into_inner);
+        state_of(&current, &self.initial, name)
+    }
+
+    pub fn set(&self, name: &str, state: &str) {
+        let mut current = self.current.write().unwrap_or_else(PoisonError::into_inner);
+        current.insert(name.to_string(), state.to_string());
+    }
+
+    /// The current state of every known scenario
+    pub fn snapshot(&self) -> BTreeMap<String, String> {
+        let current = self.current.read().unwrap_or_else(PoisonError::into_inner);
+        current
+            .iter()
+            .map(|(name, state)| (name.clone(), state.clone()))
+            .collect()
+    }
+
+    /// Return every scenario to its initial state
+    pub fn reset(&self) {
+        let mut current = self.current.write().unwrap_or_else(PoisonError::into_inner);
+        current.clone_from(&self.initial);
+    }
+
+    /// Return a single scenario to its initial state
+    pub fn reset_scenario(&self, name: &str) {
+        self.set(name, &self.initial_state(name));
+    }
+
+    /// Whether the scenario named by `transition` is in its required state.
+    /// Transitions without a scenario or required state always match.
+    pub fn matches(&self, transition: &ScenarioTransition) -> bool {
+        match (&transition.scenario, &transition.required_state) {
+            (Some(name), Some(required)) => self.get(name) == *required,
+            _ => true,
+        }
+    }
+
+    /// Lock every scenario, so that checking their states and then advancing them
+    /// can't be interleaved with other requests doing the same
+    pub fn lock(&self) -> LockedScenarios<'_> {
+        LockedScenarios {
+            initial: &self.initial,
+            current: self.current.write().unwrap_or_else(PoisonError::into_inner),
+        }
+    }
+}
+
+/// Exclusive access to the scenarios of a `ScenarioStore`, held until dropped
+pub struct LockedScenarios<'a> {
+    initial: &'a HashMap<String, String>,
+    current: RwLockWriteGuard<'a, HashMap<String, String>>,
+}
+
+impl LockedScenarios<'_> {
+    /// Whether the scenario named by `transition` is in its required state.
+    /// Transitions without a scenario or required state always match.
+    pub fn matches(&self, transition: &ScenarioTransition) -> bool {
+        match (&transition.scenario, &transition.required_state) {
+            (Some(name), Some(required)) => {
+                state_of(&self.current, self.initial, name) == *required
+            }
+            _ => true,
+        }
+    }
+
+    /// Move the scenario named by `transition` into its new state, if any
+    pub fn advance(&mut self, transition: &ScenarioTransition) {
+        if let (Some(name), Some(new_state)) = (&transition.scenario, &transition.new_state) {
+            self.current.insert(name.clone(), new_state.clone());
+        }
+    }
+}
+
+// The current state of scenario `name`, falling back to its initial state
+fn state_of(
+    current: &HashMap<String, String>,
+    initial: &HashMap<String, String>,
+    name: &str,
+) -> String {
+    current
+        .get(name)
+        .or_else(|| initial.get(name))
+        .cloned()
+        .unwrap_or_else(|| DEFAULT_SCENARIO_STATE.to_string())
+}
+
+/// Apply each of `actions` to `state` in order. Keys and values are rendered as templates
+/// against the current request and state, so later actions can see the results of earlier ones.
+pub fn apply_actions(state: &mut StateMap, actions: &[StateAction], request: &RequestData) {
+    let render_template = |template: &str, state: &StateMap| {
+        render(
+            template,
+            &TemplateContext {
+                request,
+                state,
+                index: None,
+            },
+        )
+    };
+
+    for action in actions {
+        match action {
+            StateAction::Set { key, value } => {
+                let key = render_template(key, state);
+                let value = render_template(value, state);
+                let value = serde_json::from_str(&value).unwrap_or(Value::String(value));
+                state.insert(key, value);
+            }
+            StateAction::Incr { key, by } => {
+                let key = render_template(key, state);
+                let current = match state.get(&key) {
+                    Some(Value::Number(n)) => n.as_i64().unwrap_or_default(),
+                    Some(Value::String(s)) => s.parse().unwrap_or_default(),
+                    _ => 0,
+                };
+                state.insert(key, Value::from(current.saturating_add(*by)));
+            }
+            StateAction::Delete { key } => {
+                let key = render_template(key, state);
+                state.remove(&key);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+    use std::collections::HashMap;
+
+    #[test]
+    fn apply_actions_sets_increments_and_deletes_keys() {
+        let request = RequestData {
+            params: HashMap::from([("id".to_string(), "7".to_string())]),
+            body: r#"{"name": "Widget"}"#.to_string(),
+            ..Default::default()
+        };
+        let store = StateStore::new();
+
+        let actions = vec![
+            StateAction::Incr {
+                key: "items:count".into(),
+                by: 1,
+            },
+            StateAction::Set {
+                key: "items/{{params.id}}".into(),
+                value: "{{body}}".into(),
+            },
+            StateAction::Set {
+                key: "last".into(),
+                value: "{{body.name}}".into(),
+            },
+        ];
+        apply_actions(&mut store.write(), &actions, &request);
+
+        {
+            let state = store.read();
+            assert_eq!(state.get("items:count"), Some(&json!(1)));
+            assert_eq!(state.get("items/7"), Some(&json!({"name": "Widget"})));
+            assert_eq!(state.get("last"), Some(&json!("Widget")));
+        }
+
+        let actions = vec![
+            StateAction::Incr {
+                key: "items:count".into(),
+                by: 4,
+            },
+            StateAction::Delete {
+                key: "items/{{params.id}}".into(),
+            },
+        ];
+        apply_actions(&mut store.write(), &actions, &request);
+
+        let state = store.read();
+        assert_eq!(state.get("items:count"), Some(&json!(5)));
+        assert_eq!(state.get("items/7"), None);
+    }
+
+    #[test]
+    fn incr_saturates_instead_of_overflowing() {
+        let mut state = StateMap::from([
+            ("max".to_string(), json!(i64::MAX)),
+            ("min".to_string(), json!(i64::MIN)),
+        ]);
+        let actions = [
+            StateAction::Incr {
+                key: "max".into(),
+                by: 1,
+            },
+            StateAction::Incr {
+                key: "min".into(),
+                by: -1,
+            },
+        ];
+        apply_actions(&mut state, &actions, &RequestData::default());
+
+        assert_eq!(state.get("max"), Some(&json!(i64::MAX)));
+        assert_eq!(state.get("min"), Some(&json!(i64::MIN)));
+    }
+
+    #[test]
+    fn clones_share_state_and_reset_clears_it() {
+        let store = StateStore::new();
+        let other = store.clone();
+
+        store.write().insert("key".into(), json!("value"));
+        assert_eq!(other.read().get("key"), Some(&json!("value")));
+
+        other.reset();
+        assert!(store.read().is_empty());
+    }
+
+    #[test]
+    fn scenario_store_matches_advances_and_resets() {
+        let store = ScenarioStore::new(HashMap::from([("order".into(), "created".into())]));
+        let pay = ScenarioTransition {
+            scenario: Some("order".into()),
+            required_state: Some("created".into()),
+            new_state: Some("paid".into()),
+        };
+
+        assert!(store.matches(&pay));
+        let mut locked = store.lock();
+        assert!(locked.matches(&pay));
+        locked.advance(&pay);
+        assert!(!locked.matches(&pay));
+        drop(locked);
+        assert_eq!(store.get("order"), "paid");
+        assert!(!store.matches(&pay));
+
+        // Unknown scenarios start in the default state
+        assert_eq!(store.get("unknown"), DEFAULT_SCENARIO_STATE);
+        assert!(store.matches(&ScenarioTransition::default()));
+
+        store.set("other", "done");
+        store.reset_scenario("other");
+        assert_eq!(store.get("other"), DEFAULT_SCENARIO_STATE);
+
+        store.reset();
+        assert_eq!(store.get("order"), "created");
+        assert_eq!(store.snapshot().len(), 1);
+    }
+}
@@ -0,0 +1,144 @@
+use actix_web::rt::time;
+use actix_web::web::Bytes;
+use futures_util::stream::{self, Stream, StreamExt};
+use std::convert::Infallible;
+use std::time::Duration;
+
+use crate::http::{RecordStream, ResponseChunk, ResponseStream};
+use crate::state::StateMap;
+use crate::template::{render, RequestData, TemplateContext};
+
+type BodyStream = stream::BoxStream<'static, Result<Bytes, Infallible>>;
+
+/**
+Stream the body described by `stream`. Records are always rendered as templates,
+while chunks are only rendered if `templated`. Templates see the request and a
+snapshot of the state store taken when the response started.
+**/
+pub fn body_stream(
+    stream: &ResponseStream,
+    templated: bool,
+    request: RequestData,
+    state: StateMap,
+) -> BodyStream {
+    match stream {
+        ResponseStream::Chunks(chunks) => {
+            let chunks = chunks
+                .iter()
+                .map(|chunk| ResponseChunk {
+                    data: if templated {
+                        render(&chunk.data, &context(&request, &state, None))
+                    } else {
+                        chunk.data.clone()
+                    },
+                    delay_ms: chunk.delay_ms,
+                })
+                .collect::<Vec<_>>();
+            chunk_stream(chunks).boxed()
+        }
+        ResponseStream::Records(records) => record_stream(records.clone(), request, state).boxed(),
+    }
+}
+
+fn context<'a>(
+    request: &'a RequestData,
+    state: &'a StateMap,
+    index: Option<u64>,
+) -> TemplateContext<'a> {
+    TemplateContext {
+        request,
+        state,
+        index,
+    }
+}
+
+fn chunk_stream(chunks: Vec<ResponseChunk>) -> impl Stream<Item = Result<Bytes, Infallible>> {
+    stream::unfold(chunks.into_iter(), |mut chunks| async move {
+        let chunk = chunks.next()?;
+        delay(chunk.delay_ms).await;
+        Some((Ok(Bytes::from(chunk.data)), chunks))
+    })
+}
+
+// Records are rendered lazily, so that large streams are never held in memory
+fn record_stream(
+    records: RecordStream,
+    request: RequestData,
+    state: StateMap,
+) -> impl Stream<Item = Result<Bytes, Infallible>> {
+    stream::unfold(0, move |index| {
+        let record = (index < records.count).then(|| {
+            let mut record = render(&records.template, &context(&request, &state, Some(index)));
+            record.push_str(&records.separator);
+            record
+        });
+        let delay_ms = records.delay_ms;
+
+        async move {
+            let record = record?;
+            delay(delay_ms).await;
+            Some((Ok(Bytes::from(record)), index + 1))
+        }
+    })
+}
+
+async fn delay(ms: u64) {
+    if ms > 0 {
+        time::sleep(Duration::from_millis(ms)).await;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    async fn collect(stream: BodyStream) -> String {
+        let chunks: Vec<_> = stream.map(|chunk| chunk.unwrap()).collect().await;
+        chunks
+            .iter()
+            .map(|chunk| String::from_utf8_lossy(chunk))
+            .collect()
+    }
+
+    #[actix_web::test]
+    async fn record_streams_render_each_record() {
+        let stream = ResponseStream::Records(RecordStream {
+            count: 3,
+            template: r#"{"id": {{index}}, "owner": "{{state.owner}}"}"#.into(),
+            separator: "\n".into(),
+            delay_ms: 0,
+        });
+        let state = StateMap::from([("owner".to_string(), json!("alice"))]);
+
+        let body = collect(body_stream(&stream, false, RequestData::default(), state)).await;
+        assert_eq!(
+            body,
+            "{\"id\": 0, \"owner\": \"alice\"}\n{\"id\": 1, \"owner\": \"alice\"}\n{\"id\": 2, \"owner\": \"alice\"}\n"
+        );
+    }
+
+    #[actix_web::test]
+    async fn chunks_are_only_rendered_when_templated() {
+        let stream = ResponseStream::Chunks(vec![
+            ResponseChunk {
+                data: "{{query.name}},".into(),
+                delay_ms: 0,
+            },
+            ResponseChunk {
+                data: "done".into(),
+                delay_ms: 1,
+            },
+        ]);
+        let request = RequestData {
+            query: [("name".to_string(), "bob".to_string())].into(),
+            ..Default::default()
+        };
+
+        let body = collect(body_stream(&stream, true, request.clone(), StateMap::new())).await;
+        assert_eq!(body, "bob,done");
+
+        let body = collect(body_stream(&stream, false, request, StateMap::new())).await;
+        assert_eq!(body, "{{query.name}},done");
+    }
+}
@@ -0,0 +1,246 @@
+/*!
+A minimal templating language for response bodies and state actions.
+
+Expressions are wrapped in double braces and may be nested, in which case the
+innermost expressions are rendered first. For example, `{{state.items/{{params.id}}}}`
+first resolves the `id` path param, then looks up the resulting state key.
+
+Supported expressions:
+- `params.<name>`: a path param. Names follow the route path transformation, so `:user-id` becomes `user_id`
+- `query.<name>`: a query string param
+- `headers.<name>`: a request header (case-insensitive)
+- `body`: the raw request body
+- `body.<path>`: a field within a JSON request body, using `.` to separate keys and array indices
+- `state.<key>`: a value from the server's state store
+- `uuid`: a freshly generated v4 UUID
+- `index`: the zero-based index of the record being generated, within a record stream
+
+Unknown expressions and missing values render as an empty string.
+*/
+
+use actix_web::web::{Bytes, Query};
+use actix_web::HttpRequest;
+use serde_json::Value;
+use std::collections::HashMap;
+use uuid::Uuid;
+
+use crate::state::StateMap;
+
+/// The parts of an incoming HTTP request which templates may refer to
+#[derive(Debug, Clone, Default)]
+pub struct RequestData {
+    pub params: HashMap<String, String>,
+    pub query: HashMap<String, String>,
+    pub headers: HashMap<String, String>,
+    pub body: String,
+}
+
+impl RequestData {
+    pub fn new(req: &HttpRequest, body: &Bytes) -> Self {
+        let params = req
+            .match_info()
+            .iter()
+            .map(|(k, v)| (k.to_string(), v.to_string()))
+            .collect();
+
+        let query = Query::<HashMap<String, String>>::from_query(req.query_string())
+            .map(|q| q.into_inner())
+            .unwrap_or_default();
+
+        let headers = req
+            .headers()
+            .iter()
+            .filter_map(|(k, v)| Some((k.as_str().to_lowercase(), v.to_str().ok()?.to_string())))
+            .collect();
+
+        RequestData {
+            params,
+            query,
+            headers,
+            body: String::from_utf8_lossy(body).into_owned(),
+        }
+    }
+
+    /// Parse the request body as JSON, if possible
+    pub fn json(&self) -> Option<Value> {
+        serde_json::from_str(&self.body).ok()
+    }
+}
+
+/// Everything a template may read from while being rendered
+pub struct TemplateContext<'a> {
+    pub request: &'a RequestData,
+    pub state: &'a StateMap,
+    pub index: Option<u64>,
+}
+
+impl TemplateContext<'_> {
+    fn resolve(&self, expr: &str) -> String {
+        let (source, path) = match expr.split_once('.') {
+            Some((source, path)) => (source, Some(path)),
+            None => (expr, None),
+        };
+
+        match (source, path) {
+            ("uuid", None) => Uuid::new_v4().to_string(),
+            ("index", None) => self.index.map(|i| i.to_string()).unwrap_or_default(),
+            ("body", None) => self.request.body.clone(),
+            ("body", Some(path)) => self
+                .request
+                .json()
+                .and_then(|json| lookup_json(&json, path).map(value_to_string))
+                .unwrap_or_default(),
+            ("params", Some(name)) => self.request.params.get(name).cloned().unwrap_or_default(),
+            ("query", Some(name)) => self.request.query.get(name).cloned().unwrap_or_default(),
+            ("headers", Some(name)) => self
+                .request
+                .headers
+                .get(&name.to_lowercase())
+                .cloned()
+                .unwrap_or_default(),
+            ("state", Some(key)) => self.state.get(key).map(value_to_string).unwrap_or_default(),
+            _ => String::new(),
+        }
+    }
+}
+
+/// Render `template`, substituting each `{{expression}}` with its value from `ctx`
+pub fn render(template: &str, ctx: &TemplateContext) -> String {
+    render_segment(template, ctx, false).0
+}
+
+/*
+Renders `input` up until its end or, when `nested`, the first unmatched `}}`.
+Returns the rendered output, the number of bytes consumed (including any closing braces),
+and whether a closing `}}` was found. Unterminated expressions are emitted verbatim.
+*/
+fn render_segment(input: &str, ctx: &TemplateContext, nested: bool) -> (String, usize, bool) {
+    let mut output = String::new();
+    let mut i = 0;
+
+    while i < input.len() {
+        let rest = &input[i..];
+
+        if let Some(inner) = rest.strip_prefix("{{") {
+            let (expr, consumed, closed) = render_segment(inner, ctx, true);
+            i += 2 + consumed;
+
+            if closed {
+                output.push_str(&ctx.resolve(expr.trim()));
+            } else {
+                output.push_str("{{");
+                output.push_str(&expr);
+            }
+            continue;
+        }
+
+        if nested && rest.starts_with("}}") {
+            return (output, i + 2, true);
+        }
+
+        // Safe to unwrap; `rest` is non-empty
+        let c = rest.chars().next().unwrap();
+        output.push(c);
+        i += c.len_utf8();
+    }
+
+    (output, i, false)
+}
+
+/// Find a value within `json` by a `.` separated path of object keys and array indices
+pub fn lookup_json<'a>(json: &'a Value, path: &str) -> Option<&'a Value> {
+    path.split('.')
+        .try_fold(json, |value, segment| match value {
+            Value::Object(map) => map.get(segment),
+            Value::Array(items) => segment.parse::<usize>().ok().and_then(|i| items.get(i)),
+            _ => None,
+        })
+}
+
+/// Whether `actual` contains everything in `expected`. Arrays must match exactly.
+pub fn json_contains(actual: &Value, expected: &Value) -> bool {
+    match (actual, expected) {
+        (Value::Object(actual), Value::Object(expected)) => expected
+            .iter()
+            .all(|(k, v)| actual.get(k).is_some_and(|a| json_contains(a, v))),
+        _ => actual == expected,
+    }
+}
+
+/// Strings are rendered as-is (without quotes), anything else as its JSON representation
+pub fn value_to_string(value: &Value) -> String {
+    match value {
+        Value::String(s) => s.clone(),
+        other => other.to_string(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn make_request() -> RequestData {
+        RequestData {
+            params: HashMap::from([("id".to_string(), "42".to_string())]),
+            query: HashMap::from([("page".to_string(), "2".to_string())]),
+            headers: HashMap::from([("x-tenant".to_string(), "acme".to_string())]),
+            body: r#"{"name": "Widget", "tags": ["a", "b"], "price": 9.5}"#.to_string(),
+        }
+    }
+
+    #[test]
+    fn render_substitutes_request_values() {
+        let request = make_request();
+        let state = StateMap::new();
+        let ctx = TemplateContext {
+            request: &request,
+            state: &state,
+            index: None,
+        };
+
+        assert_eq!(render("id={{params.id}}", &ctx), "id=42");
+        assert_eq!(render("{{ query.page }}", &ctx), "2");
+        assert_eq!(render("{{headers.X-Tenant}}", &ctx), "acme");
+        assert_eq!(render("{{body.name}}", &ctx), "Widget");
+        assert_eq!(render("{{body.tags.1}}", &ctx), "b");
+        assert_eq!(render("{{body.price}}", &ctx), "9.5");
+        assert_eq!(render("{{body}}", &ctx), request.body);
+        assert_eq!(render("[{{params.missing}}]", &ctx), "[]");
+    }
+
+    #[test]
+    fn render_resolves_nested_expressions() {
+        let request = make_request();
+        let mut state = StateMap::new();
+        state.insert("items/42".into(), json!({"name": "Widget"}));
+        let ctx = TemplateContext {
+            request: &request,
+            state: &state,
+            index: None,
+        };
+
+        assert_eq!(
+            render("{{state.items/{{params.id}}}}", &ctx),
+            r#"{"name":"Widget"}"#
+        );
+    }
+
+    #[test]
+    fn render_leaves_non_template_text_untouched() {
+        let request = make_request();
+        let state = StateMap::new();
+        let ctx = TemplateContext {
+            request: &request,
+            state: &state,
+            index: None,
+        };
+
+        assert_eq!(render(r#"{"a": {"b": 1}}"#, &ctx), r#"{"a": {"b": 1}}"#);
+        assert_eq!(
+            render("unterminated {{params.id", &ctx),
+            "unterminated {{params.id"
+        );
+        assert_eq!(render("ünïcödé {{params.id}}", &ctx), "ünïcödé 42");
+    }
+}
@@ -0,0 +1,354 @@
+use anyhow::{anyhow, Context, Result};
+use rcgen::{
+    BasicConstraints, Certificate, CertificateParams, DnType, ExtendedKeyUsagePurpose, IsCa,
+    KeyPair, KeyUsagePurpose,
+};
+use rustls::client::danger::{HandshakeSignatureValid, ServerCertVerified, ServerCertVerifier};
+use rustls::crypto::{ring, verify_tls12_signature, verify_tls13_signature, CryptoProvider};
+use rustls::pki_types::pem::PemObject;
+use rustls::pki_types::{CertificateDer, PrivateKeyDer, PrivatePkcs8KeyDer, ServerName, UnixTime};
+use rustls::server::{ClientHello, ResolvesServerCert, WantsServerCert, WebPkiClientVerifier};
+use rustls::sign::CertifiedKey;
+use rustls::{
+    ClientConfig, ConfigBuilder, DigitallySignedStruct, RootCertStore, ServerConfig,
+    SignatureScheme,
+};
+use std::net::IpAddr;
+use std::sync::Arc;
+use x509_parser::extensions::GeneralName;
+use x509_parser::prelude::{FromDer, X509Certificate};
+
+use crate::http::{host_matches, ClientAuth, ClientIdentity, TlsConfig};
+
+/// Build a rustls `ServerConfig` from the PEM encoded certificate chain and key named by `tls`
+pub fn load_server_config(tls: &TlsConfig) -> Result<ServerConfig> {
+    let chain = load_certs(&tls.cert)
+        .with_context(|| format!("Failed to load TLS certificate chain `{}`", &tls.cert))?;
+
+    let key = PrivateKeyDer::from_pem_file(&tls.key)
+        .map_err(|e| anyhow!("{:?}", e))
+        .with_context(|| format!("Failed to load TLS private key `{}`", &tls.key))?;
+
+    build_server_config(chain, key, tls.client_auth.as_ref())
+}
+
+/**
+Build a rustls `ServerConfig` for virtual hosts sharing a port, choosing each host's certificate
+by SNI. Hosts are given with the hostnames they answer to; the last is the default, whose certificate
+is served when SNI matches no other. Client certificates are requested if any host asks for them.
+**/
+pub fn load_virtual_host_config(hosts: &[(&[String], &TlsConfig)]) -> Result<ServerConfig> {
+    let Some(((_, default_tls), others)) = hosts.split_last() else {
+        return Err(anyhow!("No TLS configs given for virtual hosts"));
+    };
+
+    let client_auth = shared_client_auth(hosts.iter().map(|(_, tls)| tls.client_auth.as_ref()))?;
+    let builder = config_builder(client_auth)?;
+    let load = |tls: &TlsConfig| -> Result<Arc<CertifiedKey>> {
+        let chain = load_certs(&tls.cert)
+            .with_context(|| format!("Failed to load TLS certificate chain `{}`", &tls.cert))?;
+        let key = PrivateKeyDer::from_pem_file(&tls.key)
+            .map_err(|e| anyhow!("{:?}", e))
+            .with_context(|| format!("Failed to load TLS private key `{}`", &tls.key))?;
+        let key = ring::sign::any_supported_type(&key)
+            .with_context(|| format!("Invalid TLS private key `{}`", &tls.key))?;
+        Ok(Arc::new(CertifiedKey::new(chain, key)))
+    };
+
+    let resolver = VirtualHostCerts {
+        hosts: others
+            .iter()
+            .map(|(names, tls)| Ok((names.to_vec(), load(tls)?)))
+            .collect::<Result<_>>()?,
+        default: load(default_tls)?,
+    };
+
+    Ok(builder.with_cert_resolver(Arc::new(resolver)))
+}
+
+/**
+The client certificate settings to use for virtual hosts sharing a port. Only one set of
+trusted CAs can be used per port, so every host which asks for client certificates must agree on it.
+**/
+pub fn shared_client_auth<'a>(
+    client_auths: impl IntoIterator<Item = Option<&'a ClientAuth>>,
+) -> Result<Option<&'a ClientAuth>> {
+    let mut shared: Option<&ClientAuth> = None;
+    for client_auth in client_auths.into_iter().flatten() {
+        match shared {
+            Some(shared) if shared.ca != client_auth.ca => {
+                return Err(anyhow!(
+                "Servers sharing a port must trust the same client CA, but `{}` and `{}` differ",
+                &shared.ca,
+                &client_auth.ca
+            ))
+            }
+            Some(_) => {}
+            None => shared = Some(client_auth),
+        }
+    }
+
+    Ok(shared)
+}
+
+// Picks the certificate of the virtual host named by SNI, falling back to the default host's
+#[derive(Debug)]
+struct VirtualHostCerts {
+    hosts: Vec<(Vec<String>, Arc<CertifiedKey>)>,
+    default: Arc<CertifiedKey>,
+}
+
+impl ResolvesServerCert for VirtualHostCerts {
+    fn resolve(&self, client_hello: ClientHello) -> Option<Arc<CertifiedKey>> {
+        let matched = client_hello.server_name().and_then(|name| {
+            self.hosts
+                .iter()
+                .find(|(patterns, _)| patterns.iter().any(|pattern| host_matches(pattern, name)))
+        });
+
+        Some(matched.map_or(&self.default, |(_, cert)| cert).clone())
+    }
+}
+
+/// A certificate authority which lives only as long as the process, for issuing server certificates
+pub struct EphemeralCa {
+    cert: Certificate,
+    key: KeyPair,
+}
+
+impl EphemeralCa {
+    pub fn generate() -> Result<Self> {
+        let key = KeyPair::generate()?;
+        let mut params = CertificateParams::new(Vec::<String>::new())?;
+        params.is_ca = IsCa::Ca(BasicConstraints::Unconstrained);
+        params
+            .distinguished_name
+            .push(DnType::CommonName, "Mockerize ephemeral CA");
+        params.key_usages = vec![
+            KeyUsagePurpose::KeyCertSign,
+            KeyUsagePurpose::CrlSign,
+            KeyUsagePurpose::DigitalSignature,
+        ];
+        let cert = params.self_signed(&key)?;
+
+        Ok(EphemeralCa { cert, key })
+    }
+
+    /// PEM encoded CA certificate, which clients should trust
+    pub fn cert_pem(&self) -> String {
+        self.cert.pem()
+    }
+
+    /// Issue a server certificate valid for each of `hosts`, and build a `ServerConfig` serving it
+    pub fn server_config(
+        &self,
+        hosts: &[String],
+        client_auth: Option<&ClientAuth>,
+    ) -> Result<ServerConfig> {
+        let server_key = KeyPair::generate()?;
+        let mut server_params = CertificateParams::new(hosts.to_vec())?;
+        server_params
+            .distinguished_name
+            .push(DnType::CommonName, "Mockerize server");
+        server_params.extended_key_usages = vec![ExtendedKeyUsagePurpose::ServerAuth];
+        let server_cert = server_params.signed_by(&server_key, &self.cert, &self.key)?;
+
+        let chain = vec![server_cert.der().clone(), self.cert.der().clone()];
+        let key = PrivatePkcs8KeyDer::from(server_key.serialize_der()).into();
+
+        build_server_config(chain, key, client_auth)
+    }
+}
+
+/**
+A `ClientConfig` accepting any server certificate and only offering HTTP/1.1, for checking whether
+a local HTTPS server is answering, whatever certificate it presents. Never use it to send anything secret.
+**/
+pub fn insecure_client_config() -> Result<ClientConfig> {
+    let provider = Arc::new(ring::default_provider());
+    let mut config = ClientConfig::builder_with_provider(provider.clone())
+        .with_safe_default_protocol_versions()?
+        .dangerous()
+        .with_custom_certificate_verifier(Arc::new(AcceptAnyServerCert(provider)))
+        .with_no_client_auth();
+    config.alpn_protocols = vec![b"http/1.1".to_vec()];
+    Ok(config)
+}
+
+// Skips verifying the server certificate, but still checks the handshake is signed by its key
+#[derive(Debug)]
+struct AcceptAnyServerCert(Arc<CryptoProvider>);
+
+impl ServerCertVerifier for AcceptAnyServerCert {
+    fn verify_server_cert(
+        &self,
+        _end_entity: &CertificateDer,
+        _intermediates: &[CertificateDer],
+        _server_name: &ServerName,
+        _ocsp_response: &[u8],
+        _now: UnixTime,
+    ) -> Result<ServerCertVerified, rustls::Error> {
+        Ok(ServerCertVerified::assertion())
+    }
+
+    fn verify_tls12_signature(
+        &self,
+        message: &[u8],
+        cert: &CertificateDer,
+        dss: &DigitallySignedStruct,
+    ) -> Result<HandshakeSignatureValid, rustls::Error> {
+        verify_tls12_signature(
+            message,
+            cert,
+            dss,
+            &self.0.signature_verification_algorithms,
+        )
+    }
+
+    fn verify_tls13_signature(
+        &self,
+        message: &[u8],
+        cert: &CertificateDer,
+        dss: &DigitallySignedStruct,
+    ) -> Result<HandshakeSignatureValid, rustls::Error> {
+        verify_tls13_signature(
+            message,
+            cert,
+            dss,
+            &self.0.signature_verification_algorithms,
+        )
+    }
+
+    fn supported_verify_schemes(&self) -> Vec<SignatureScheme> {
+        self.0.signature_verification_algorithms.supported_schemes()
+    }
+}
+
+/// Extract the identifying details of a DER encoded client certificate
+pub fn client_identity(cert: &CertificateDer) -> Result<ClientIdentity> {
+    let (_, cert) = X509Certificate::from_der(cert)
+        .map_err(|e| anyhow!("Invalid client certificate: {}", e))?;
+
+    let common_name = cert
+        .subject()
+        .iter_common_name()
+        .next()
+        .and_then(|cn| cn.as_str().ok())
+        .map(String::from);
+
+    let mut sans = vec![];
+    if let Ok(Some(extension)) = cert.subject_alternative_name() {
+        for name in &extension.value.general_names {
+            match name {
+                GeneralName::DNSName(s) | GeneralName::RFC822Name(s) | GeneralName::URI(s) => {
+                    sans.push(s.to_string())
+                }
+                GeneralName::IPAddress(bytes) => {
+                    let ip = match bytes.len() {
+                        4 => <[u8; 4]>::try_from(*bytes).ok().map(IpAddr::from),
+                        16 => <[u8; 16]>::try_from(*bytes).ok().map(IpAddr::from),
+                        _ => None,
+                    };
+                    sans.extend(ip.map(|ip| ip.to_string()));
+                }
+                _ => {}
+            }
+        }
+    }
+
+    Ok(ClientIdentity {
+        subject: cert.subject().to_string(),
+        common_name,
+        sans,
+    })
+}
+
+fn load_certs(path: &str) -> Result<Vec<CertificateDer<'static>>> {
+    CertificateDer::pem_file_iter(path)
+        .and_then(|certs| certs.collect::<Result<Vec<_>, _>>())
+        .map_err(|e| anyhow!("{:?}", e))
+}
+
+fn build_server_config(
+    chain: Vec<CertificateDer<'static>>,
+    key: PrivateKeyDer<'static>,
+    client_auth: Option<&ClientAuth>,
+) -> Result<ServerConfig> {
+    let config = config_builder(client_auth)?
+        .with_single_cert(chain, key)
+        .context("Invalid TLS certificate or private key")?;
+
+    Ok(config)
+}
+
+// Start building a `ServerConfig`, verifying client certificates against the CA of `client_auth`, if given
+fn config_builder(
+    client_auth: Option<&ClientAuth>,
+) -> Result<ConfigBuilder<ServerConfig, WantsServerCert>> {
+    let provider = Arc::new(ring::default_provider());
+    let builder = ServerConfig::builder_with_provider(provider.clone())
+        .with_safe_default_protocol_versions()?;
+
+    Ok(match client_auth {
+        Some(client_auth) => {
+            let mut roots = RootCertStore::empty();
+            let certs = load_certs(&client_auth.ca).with_context(|| {
+                format!("Failed to load client CA bundle `{}`", &client_auth.ca)
+            })?;
+            for cert in certs {
+                roots.add(cert).context("Invalid client CA certificate")?;
+            }
+
+            // Missing certificates are rejected per request, so that the rejection response can be configured
+            let verifier = WebPkiClientVerifier::builder_with_provider(Arc::new(roots), provider)
+                .allow_unauthenticated()
+                .build()
+                .context("Invalid client CA bundle")?;
+            builder.with_client_cert_verifier(verifier)
+        }
+        None => builder.with_no_client_auth(),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn ephemeral_ca_issues_server_configs() {
+        let ca = EphemeralCa::generate().unwrap();
+        assert!(ca.cert_pem().starts_with("-----BEGIN CERTIFICATE-----"));
+
+        let hosts = ["localhost".to_string(), "127.0.0.1".to_string()];
+        assert!(ca.server_config(&hosts, None).is_ok());
+    }
+
+    #[test]
+    fn load_server_config_reports_missing_files() {
+        let tls = TlsConfig::new("tests/data/missing.pem", "tests/data/missing.key");
+        let err = load_server_config(&tls).err().unwrap();
+        assert!(format!("{:#}", err).contains("tests/data/missing.pem"));
+    }
+
+    #[test]
+    fn client_identity_includes_subject_and_sans() {
+        let mut params = CertificateParams::new(vec![
+            "tenant-a.example.com".to_string(),
+            "10.0.0.1".to_string(),
+        ])
+        .unwrap();
+        params
+            .distinguished_name
+            .push(DnType::CommonName, "tenant-a");
+        params
+            .distinguished_name
+            .push(DnType::OrganizationName, "Example");
+        let key = KeyPair::generate().unwrap();
+        let cert = params.self_signed(&key).unwrap();
+
+        let identity = client_identity(cert.der()).unwrap();
+        assert_eq!(identity.common_name.as_deref(), Some("tenant-a"));
+        assert!(identity.subject.contains("CN=tenant-a"));
+        assert_eq!(identity.sans, vec!["tenant-a.example.com", "10.0.0.1"]);
+    }
+}
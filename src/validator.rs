@@ -0,0 +1,206 @@
+use anyhow::{anyhow, Context, Result};
+use jsonschema::Validator;
+use serde::Serialize;
+use serde_json::{Map, Value};
+use std::collections::HashMap;
+use std::sync::Arc;
+use uuid::Uuid;
+
+use crate::http::{RequestValidation, SchemaSource, ServerInfo};
+use crate::template::RequestData;
+
+/// A single reason a request failed validation
+#[derive(Debug, Serialize, PartialEq)]
+pub struct ValidationFailure {
+    /// Which part of the request failed validation: `body`, `query` or `headers`
+    pub location: &'static str,
+    /// JSON pointer to the offending value
+    pub path: String,
+    pub message: String,
+}
+
+/// Compiled form of a `RequestValidation`, ready to check incoming requests against
+pub struct RequestValidator {
+    body: Option<Validator>,
+    query: Option<Validator>,
+    headers: Option<Validator>,
+    pub failure_status: u16,
+}
+
+impl RequestValidator {
+    pub fn new(validation: &RequestValidation) -> Result<Self> {
+        Ok(RequestValidator {
+            body: compile(validation.body.as_ref()).context("Invalid body schema")?,
+            query: compile(validation.query.as_ref()).context("Invalid query schema")?,
+            headers: compile(validation.headers.as_ref()).context("Invalid headers schema")?,
+            failure_status: validation.failure_status,
+        })
+    }
+
+    /// Check `request` against each configured schema, returning every failure found
+    pub fn validate(&self, request: &RequestData) -> Vec<ValidationFailure> {
+        let mut failures = vec![];
+
+        if let Some(validator) = &self.body {
+            match request.json() {
+                Some(body) => collect_failures(validator, &body, "body", &mut failures),
+                None => failures.push(ValidationFailure {
+                    location: "body",
+                    path: String::new(),
+                    message: "Request body is not valid JSON".to_string(),
+                }),
+            }
+        }
+
+        if let Some(validator) = &self.query {
+            let query = request
+                .query
+                .iter()
+                .map(|(k, v)| (k.clone(), coerce_query_value(v)))
+                .collect::<Map<_, _>>();
+            collect_failures(validator, &Value::Object(query), "query", &mut failures);
+        }
+
+        if let Some(validator) = &self.headers {
+            let headers = request
+                .headers
+                .iter()
+                .map(|(k, v)| (k.clone(), Value::String(v.clone())))
+                .collect::<Map<_, _>>();
+            collect_failures(validator, &Value::Object(headers), "headers", &mut failures);
+        }
+
+        failures
+    }
+}
+
+/// Compile the validation rules of every route in `serverinfo`, keyed by route ID
+pub fn compile_validators(serverinfo: &ServerInfo) -> Result<HashMap<Uuid, Arc<RequestValidator>>> {
+    let mut validators = HashMap::new();
+
+    for route in &serverinfo.router.routes {
+        if let Some(validation) = &route.validation {
+            let validator = RequestValidator::new(validation).with_context(|| {
+                format!(
+                    "Invalid validation rules for route {} {}",
+                    route.method, route.path
+                )
+            })?;
+            validators.insert(route.id, Arc::new(validator));
+        }
+    }
+
+    Ok(validators)
+}
+
+fn compile(source: Option<&SchemaSource>) -> Result<Option<Validator>> {
+    let Some(source) = source else {
+        return Ok(None);
+    };
+
+    let schema = source.load()?;
+    let validator = jsonschema::validator_for(&schema).map_err(|e| anyhow!("{}", e))?;
+    Ok(Some(validator))
+}
+
+fn collect_failures(
+    validator: &Validator,
+    instance: &Value,
+    location: &'static str,
+    failures: &mut Vec<ValidationFailure>,
+) {
+    for error in validator.iter_errors(instance) {
+        failures.push(ValidationFailure {
+            location,
+            path: error.instance_path.to_string(),
+            message: error.to_string(),
+        });
+    }
+}
+
+// Query params are always strings, so allow schemas to describe numbers and booleans
+fn coerce_query_value(value: &str) -> Value {
+    match serde_json::from_str::<Value>(value) {
+        Ok(v @ (Value::Number(_) | Value::Bool(_))) => v,
+        _ => Value::String(value.to_string()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn make_validator() -> RequestValidator {
+        let validation = RequestValidation {
+            body: Some(SchemaSource::Inline(json!({
+                "type": "object",
+                "required": ["name"],
+                "properties": { "name": { "type": "string" } }
+            }))),
+            query: Some(SchemaSource::Inline(json!({
+                "type": "object",
+                "properties": { "page": { "type": "integer" } }
+            }))),
+            headers: Some(SchemaSource::Inline(json!({
+                "type": "object",
+                "required": ["x-api-key"]
+            }))),
+            failure_status: 422,
+        };
+        RequestValidator::new(&validation).unwrap()
+    }
+
+    #[test]
+    fn valid_requests_have_no_failures() {
+        let request = RequestData {
+            query: HashMap::from([("page".to_string(), "2".to_string())]),
+            headers: HashMap::from([("x-api-key".to_string(), "secret".to_string())]),
+            body: r#"{"name": "Widget"}"#.to_string(),
+            ..Default::default()
+        };
+
+        assert_eq!(make_validator().validate(&request), vec![]);
+    }
+
+    #[test]
+    fn invalid_requests_report_each_failure() {
+        let request = RequestData {
+            query: HashMap::from([("page".to_string(), "two".to_string())]),
+            body: r#"{"name": 5}"#.to_string(),
+            ..Default::default()
+        };
+
+        let failures = make_validator().validate(&request);
+        let locations: Vec<_> = failures
+            .iter()
+            .map(|f| (f.location, f.path.as_str()))
+            .collect();
+        assert_eq!(
+            locations,
+            vec![("body", "/name"), ("query", "/page"), ("headers", "")]
+        );
+    }
+
+    #[test]
+    fn non_json_bodies_fail_validation() {
+        let request = RequestData {
+            body: "not json".to_string(),
+            headers: HashMap::from([("x-api-key".to_string(), "secret".to_string())]),
+            ..Default::default()
+        };
+
+        let failures = make_validator().validate(&request);
+        assert_eq!(failures.len(), 1);
+        assert_eq!(failures[0].location, "body");
+    }
+
+    #[test]
+    fn invalid_schemas_are_rejected() {
+        let validation = RequestValidation {
+            body: Some(SchemaSource::Inline(json!({ "type": "not-a-type" }))),
+            ..Default::default()
+        };
+        assert!(RequestValidator::new(&validation).is_err());
+    }
+}
@@ -0,0 +1,118 @@
+use actix_web::rt::{self, time};
+use actix_web::{web, HttpRequest};
+use actix_ws::{CloseCode, CloseReason, Message, MessageStream, Session};
+use std::sync::Arc;
+use std::time::Duration;
+use tracing::{debug, info, info_span, warn, Instrument};
+use uuid::Uuid;
+
+use crate::http::{Route, WebSocketClose, WebSocketMessage, WebSocketPush, WebSocketScript};
+
+/// Convert a WebSocket `Route` into an Actix-web route handler, which upgrades
+/// each request and then follows `script` for the lifetime of the connection
+pub fn make_websocket_handler(route: &Route, script: &WebSocketScript) -> actix_web::Route {
+    let method: actix_web::http::Method = route.method.clone().into();
+    let path = route.path.clone();
+    let script = Arc::new(script.clone());
+
+    web::route()
+        .method(method)
+        .to(move |req: HttpRequest, body: web::Payload| {
+            let (path, script) = (path.clone(), script.clone());
+            async move {
+                let (response, session, stream) = actix_ws::handle(&req, body)?;
+
+                let connection_id = Uuid::new_v4();
+                let span = info_span!("WebSocket connection", %connection_id, %path);
+                rt::spawn(converse(script, session, stream).instrument(span));
+
+                Ok::<_, actix_web::Error>(response)
+            }
+        })
+}
+
+// Follow the script until either side closes the connection
+async fn converse(script: Arc<WebSocketScript>, mut session: Session, mut stream: MessageStream) {
+    info!("Client connected");
+
+    for message in &script.on_connect {
+        if send(&mut session, message).await.is_err() {
+            return;
+        }
+    }
+
+    let mut tasks = vec![];
+    for push in &script.pushes {
+        tasks.push(rt::spawn(push_periodically(session.clone(), push.clone())));
+    }
+    if let Some(close) = &script.close {
+        let (session, close) = (session.clone(), close.clone());
+        tasks.push(rt::spawn(async move {
+            time::sleep(Duration::from_millis(close.after_ms)).await;
+            close_with(session, &close).await;
+        }));
+    }
+
+    while let Some(Ok(message)) = stream.recv().await {
+        match message {
+            Message::Text(text) => {
+                let Some(reply) = script.reply_for(&text) else {
+                    debug!("No reply matches incoming message");
+                    continue;
+                };
+
+                for message in &reply.messages {
+                    if send(&mut session, message).await.is_err() {
+                        break;
+                    }
+                }
+                if let Some(close) = &reply.close {
+                    close_with(session.clone(), close).await;
+                    break;
+                }
+            }
+            Message::Ping(bytes) if session.pong(&bytes).await.is_err() => break,
+            Message::Close(reason) => {
+                let _ = session.clone().close(reason).await;
+                break;
+            }
+            _ => {}
+        }
+    }
+
+    for task in tasks {
+        task.abort();
+    }
+    info!("Client disconnected");
+}
+
+async fn push_periodically(mut session: Session, push: WebSocketPush) {
+    let mut interval = time::interval(Duration::from_millis(push.interval_ms.max(1)));
+    // The first tick completes immediately; pushes start one interval after connecting
+    interval.tick().await;
+
+    let mut sent = 0;
+    while push.count.is_none_or(|count| sent < count) {
+        interval.tick().await;
+        if send(&mut session, &push.message).await.is_err() {
+            return;
+        }
+        sent += 1;
+    }
+}
+
+async fn send(session: &mut Session, message: &WebSocketMessage) -> Result<(), actix_ws::Closed> {
+    session.text(message.to_text()).await
+}
+
+async fn close_with(session: Session, close: &WebSocketClose) {
+    debug!("Closing connection with code {}", close.code);
+
+    let reason = CloseReason {
+        code: CloseCode::from(close.code),
+        description: Some(close.reason.clone()).filter(|r| !r.is_empty()),
+    };
+    if session.close(Some(reason)).await.is_err() {
+        warn!("Connection was already closed");
+    }
+}
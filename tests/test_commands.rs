@@ -40,4 +40,563 @@ mod tests {
 
         assert_eq!(stdout, "OK\n");
     }
+
+    #[test]
+    fn test_cmd_passes_config_conforming_to_openapi_document() {
+        let output = Command::new(env!("CARGO_BIN_EXE_mockerize-cli"))
+            .arg("test")
+            .arg("tests/data/example.server.json")
+            .arg("--openapi")
+            .arg("tests/data/example.openapi.yaml")
+            .output()
+            .expect("Failed to execute process");
+
+        let stdout = String::from_utf8_lossy(&output.stdout);
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        assert!(output.status.success(), "{}", stderr);
+        assert_eq!(stdout, "OK\n");
+    }
+
+    #[test]
+    fn test_cmd_reports_openapi_violations() {
+        let output = Command::new(env!("CARGO_BIN_EXE_mockerize-cli"))
+            .arg("test")
+            .arg("tests/data/example.server.json")
+            .arg("--openapi")
+            .arg("tests/data/mismatched.openapi.json")
+            .output()
+            .expect("Failed to execute process");
+
+        assert_eq!(output.status.code().unwrap(), 1);
+
+        let stdout = String::from_utf8_lossy(&output.stdout);
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        assert_eq!(stdout, "ERROR\n");
+
+        // Each violating response is listed, naming its route and response
+        assert!(stderr.contains("GET /api/v1/lists response `Empty list`"));
+        assert!(stderr.contains("content type `application/text` is not documented"));
+        assert!(stderr.contains(
+            "body: {\"data\":[],\"page\":1,\"total_pages\":1,\"total\":0} is not of type \"array\""
+        ));
+        assert!(stderr.contains("operation POST `/api/v1/lists` is not documented"));
+        assert!(stderr.contains("path `/api/v1/lists/:item-id` is not documented"));
+    }
+
+    #[test]
+    fn test_cmd_checks_grpc_routes_against_proto() {
+        let output = Command::new(env!("CARGO_BIN_EXE_mockerize-cli"))
+            .arg("test")
+            .arg("tests/data/greeter.server.json")
+            .output()
+            .expect("Failed to execute process");
+
+        let stdout = String::from_utf8_lossy(&output.stdout);
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        assert!(output.status.success(), "{}", stderr);
+        assert_eq!(stdout, "OK\n");
+    }
+
+    #[test]
+    fn run_cmd_rejects_servers_listening_on_the_same_port() {
+        let pid_file = std::env::temp_dir().join(format!("mockerize-{}.pid", std::process::id()));
+        let output = Command::new(env!("CARGO_BIN_EXE_mockerize-cli"))
+            .arg("run")
+            .arg("tests/data/example.server.json")
+            .arg("tests/data/example.server.json")
+            .arg("--pid-file")
+            .arg(&pid_file)
+            .output()
+            .expect("Failed to execute process");
+        let _ = std::fs::remove_file(&pid_file);
+
+        assert!(!output.status.success());
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        assert!(stderr.contains("both listen on"), "{}", stderr);
+    }
+
+    #[test]
+    fn detached_server_can_be_checked_restarted_and_stopped() {
+        let dir = std::env::temp_dir().join(format!("mockerize-daemon-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let path = |name: &str| dir.join(name).to_string_lossy().to_string();
+
+        // Listen on any free port, so as not to collide with other tests
+        let config = std::fs::read_to_string("tests/data/example.server.json").unwrap();
+        std::fs::write(
+            path("server.json"),
+            config.replace("\"port\": 8080", "\"port\": 0"),
+        )
+        .unwrap();
+
+        let (config, pid_file, log_file) =
+            (path("server.json"), path("server.pid"), path("server.log"));
+        let cli = |command: &str, detach: bool| {
+            let mut cmd = Command::new(env!("CARGO_BIN_EXE_mockerize-cli"));
+            cmd.arg(command);
+            if command != "stop" && command != "status" {
+                cmd.arg(&config).arg("--log-file").arg(&log_file);
+            }
+            if detach {
+                cmd.arg("--detach");
+            }
+            cmd.arg("--pid-file")
+                .arg(&pid_file)
+                .output()
+                .expect("Failed to execute process")
+        };
+        let read_pid = || {
+            std::fs::read_to_string(&pid_file)
+                .unwrap()
+                .trim()
+                .to_string()
+        };
+
+        let output = cli("run", true);
+        assert!(output.status.success(), "{:?}", output);
+        let first_pid = read_pid();
+        assert!(String::from_utf8_lossy(&output.stdout).contains(&first_pid));
+
+        let output = cli("status", false);
+        assert_eq!(output.status.code(), Some(0));
+        assert_eq!(
+            String::from_utf8_lossy(&output.stdout),
+            format!("Running with PID {}.\n", first_pid)
+        );
+
+        let output = cli("restart", true);
+        assert!(output.status.success(), "{:?}", output);
+        assert_ne!(read_pid(), first_pid);
+
+        let output = cli("stop", false);
+        assert!(output.status.success(), "{:?}", output);
+        assert!(String::from_utf8_lossy(&output.stdout).starts_with("Stopped server with PID"));
+
+        let output = cli("status", false);
+        assert_eq!(output.status.code(), Some(3));
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn wait_cmd_blocks_until_server_answers() {
+        let dir = std::env::temp_dir().join(format!("mockerize-wait-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let path = |name: &str| dir.join(name).to_string_lossy().to_string();
+
+        // Find a free port for the server to use
+        let port = std::net::TcpListener::bind("127.0.0.1:0")
+            .unwrap()
+            .local_addr()
+            .unwrap()
+            .port();
+        let config = std::fs::read_to_string("tests/data/example.server.json").unwrap();
+        std::fs::write(
+            path("server.json"),
+            config.replace("\"port\": 8080", &format!("\"port\": {}", port)),
+        )
+        .unwrap();
+
+        let wait = |timeout: &str| {
+            Command::new(env!("CARGO_BIN_EXE_mockerize-cli"))
+                .args([
+                    "wait",
+                    "--config",
+                    &path("server.json"),
+                    "--timeout",
+                    timeout,
+                ])
+                .output()
+                .expect("Failed to execute process")
+        };
+
+        // Nothing is listening yet
+        let output = wait("300ms");
+        assert!(!output.status.success());
+        assert!(String::from_utf8_lossy(&output.stderr).contains("was not ready within 300ms"));
+
+        let mut server = Command::new(env!("CARGO_BIN_EXE_mockerize-cli"))
+            .arg("run")
+            .arg(path("server.json"))
+            .args(["--pid-file", &path("server.pid")])
+            .args(["--ready-file", &path("server.ready")])
+            .stdout(std::process::Stdio::null())
+            .stderr(std::process::Stdio::null())
+            .spawn()
+            .expect("Failed to execute process");
+
+        let output = wait("10s");
+        server.kill().unwrap();
+        server.wait().unwrap();
+
+        assert!(output.status.success(), "{:?}", output);
+        assert!(String::from_utf8_lossy(&output.stdout).contains("is ready"));
+        assert!(std::path::Path::new(&path("server.ready")).exists());
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn wait_cmd_checks_https_and_grpc_servers() {
+        let dir = std::env::temp_dir().join(format!("mockerize-wait-tls-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let path = |name: &str| dir.join(name).to_string_lossy().to_string();
+        let free_port = || {
+            std::net::TcpListener::bind("127.0.0.1:0")
+                .unwrap()
+                .local_addr()
+                .unwrap()
+                .port()
+        };
+
+        let cert = rcgen::generate_simple_self_signed(vec!["localhost".to_string()]).unwrap();
+        std::fs::write(path("cert.pem"), cert.cert.pem()).unwrap();
+        std::fs::write(path("key.pem"), cert.key_pair.serialize_pem()).unwrap();
+
+        let config = std::fs::read_to_string("tests/data/example.server.json").unwrap();
+        let mut https: serde_json::Value = serde_json::from_str(&config).unwrap();
+        https["server"]["port"] = free_port().into();
+        https["server"]["tls"] =
+            serde_json::json!({ "cert": path("cert.pem"), "key": path("key.pem") });
+        std::fs::write(path("https.json"), https.to_string()).unwrap();
+
+        let config = std::fs::read_to_string("tests/data/greeter.server.json").unwrap();
+        let mut grpc: serde_json::Value = serde_json::from_str(&config).unwrap();
+        grpc["server"]["port"] = free_port().into();
+        std::fs::write(path("grpc.json"), grpc.to_string()).unwrap();
+
+        let mut server = Command::new(env!("CARGO_BIN_EXE_mockerize-cli"))
+            .args(["run", &path("https.json"), &path("grpc.json")])
+            .args(["--pid-file", &path("server.pid")])
+            .stdout(std::process::Stdio::null())
+            .stderr(std::process::Stdio::null())
+            .spawn()
+            .expect("Failed to execute process");
+
+        let output = Command::new(env!("CARGO_BIN_EXE_mockerize-cli"))
+            .args(["wait", "--config", &path("https.json")])
+            .args(["--config", &path("grpc.json"), "--timeout", "10s"])
+            .output()
+            .expect("Failed to execute process");
+        server.kill().unwrap();
+        server.wait().unwrap();
+
+        assert!(output.status.success(), "{:?}", output);
+        let stdout = String::from_utf8_lossy(&output.stdout);
+        assert!(stdout.contains("https.json` is ready"));
+        assert!(stdout.contains("grpc.json` is ready"));
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn run_cmd_reports_ports_chosen_by_the_os() {
+        use std::io::{Read, Write};
+
+        let dir = std::env::temp_dir().join(format!("mockerize-ports-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let path = |name: &str| dir.join(name).to_string_lossy().to_string();
+
+        let config = std::fs::read_to_string("tests/data/example.server.json").unwrap();
+        std::fs::write(
+            path("server.json"),
+            config.replace("\"port\": 8080", "\"port\": 0"),
+        )
+        .unwrap();
+
+        let mut server = Command::new(env!("CARGO_BIN_EXE_mockerize-cli"))
+            .arg("run")
+            .arg(path("server.json"))
+            .args(["--pid-file", &path("server.pid")])
+            .args(["--port-file", &path("server.port")])
+            .args(["--ready-file", &path("server.ready")])
+            .stdout(std::process::Stdio::piped())
+            .stderr(std::process::Stdio::null())
+            .spawn()
+            .expect("Failed to execute process");
+
+        let deadline = std::time::Instant::now() + std::time::Duration::from_secs(10);
+        while !std::path::Path::new(&path("server.ready")).exists() {
+            assert!(
+                std::time::Instant::now() < deadline,
+                "Server never became ready"
+            );
+            std::thread::sleep(std::time::Duration::from_millis(50));
+        }
+
+        let port: u16 = std::fs::read_to_string(path("server.port"))
+            .unwrap()
+            .trim()
+            .parse()
+            .unwrap();
+        assert_ne!(port, 0);
+
+        let mut stream = std::net::TcpStream::connect(("127.0.0.1", port)).unwrap();
+        stream
+            .write_all(
+                b"GET /__mockerize/health HTTP/1.1\r\nHost: localhost\r\nConnection: close\r\n\r\n",
+            )
+            .unwrap();
+        let mut response = String::new();
+        stream.read_to_string(&mut response).unwrap();
+        assert!(response.starts_with("HTTP/1.1 200"), "{}", response);
+
+        // A second server sharing the PID file refuses to start, leaving the first one's files alone
+        let output = Command::new(env!("CARGO_BIN_EXE_mockerize-cli"))
+            .arg("run")
+            .arg(path("server.json"))
+            .args(["--pid-file", &path("server.pid")])
+            .args(["--port-file", &path("server.port")])
+            .args(["--ready-file", &path("server.ready")])
+            .output()
+            .expect("Failed to execute process");
+        assert!(!output.status.success());
+        assert!(String::from_utf8_lossy(&output.stderr).contains("already running"));
+        assert!(std::path::Path::new(&path("server.ready")).exists());
+        assert_eq!(
+            std::fs::read_to_string(path("server.port")).unwrap(),
+            format!("{}\n", port)
+        );
+
+        server.kill().unwrap();
+        let output = server.wait_with_output().unwrap();
+        let stdout = String::from_utf8_lossy(&output.stdout);
+        assert!(stdout.contains(&format!("Listening on 127.0.0.1:{}.", port)));
+        assert!(stdout.contains(r#""msg":"Listening""#));
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    #[cfg(unix)]
+    fn run_cmd_reloads_on_sighup_and_cleans_up_on_sigterm() {
+        use std::io::{Read, Write};
+
+        let dir = std::env::temp_dir().join(format!("mockerize-reload-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let path = |name: &str| dir.join(name).to_string_lossy().to_string();
+
+        let config = std::fs::read_to_string("tests/data/example.server.json")
+            .unwrap()
+            .replace("\"port\": 8080", "\"port\": 0");
+        std::fs::write(path("server.json"), &config).unwrap();
+
+        let server = Command::new(env!("CARGO_BIN_EXE_mockerize-cli"))
+            .arg("run")
+            .arg(path("server.json"))
+            .args(["--pid-file", &path("server.pid")])
+            .args(["--port-file", &path("server.port")])
+            .args(["--ready-file", &path("server.ready")])
+            .stdout(std::process::Stdio::piped())
+            .stderr(std::process::Stdio::null())
+            .spawn()
+            .expect("Failed to execute process");
+
+        let deadline = std::time::Instant::now() + std::time::Duration::from_secs(10);
+        while !std::path::Path::new(&path("server.ready")).exists() {
+            assert!(
+                std::time::Instant::now() < deadline,
+                "Server never became ready"
+            );
+            std::thread::sleep(std::time::Duration::from_millis(50));
+        }
+        let port: u16 = std::fs::read_to_string(path("server.port"))
+            .unwrap()
+            .trim()
+            .parse()
+            .unwrap();
+
+        let get_lists = || {
+            let mut stream = std::net::TcpStream::connect(("127.0.0.1", port)).unwrap();
+            stream
+                .write_all(
+                    b"GET /api/v1/lists HTTP/1.1\r\nHost: localhost\r\nConnection: close\r\n\r\n",
+                )
+                .unwrap();
+            let mut response = String::new();
+            stream.read_to_string(&mut response).unwrap();
+            response
+        };
+        let signal = |name: &str| {
+            let status = Command::new("kill")
+                .args([name, &server.id().to_string()])
+                .status()
+                .unwrap();
+            assert!(status.success());
+        };
+        assert!(get_lists().contains("Chores"));
+
+        // A reload serves the changed config on the same port
+        std::fs::write(path("server.json"), config.replace("Chores", "Errands")).unwrap();
+        signal("-HUP");
+        let deadline = std::time::Instant::now() + std::time::Duration::from_secs(10);
+        while !get_lists().contains("Errands") {
+            assert!(
+                std::time::Instant::now() < deadline,
+                "Server never reloaded"
+            );
+            std::thread::sleep(std::time::Duration::from_millis(50));
+        }
+
+        signal("-TERM");
+        let output = server.wait_with_output().unwrap();
+        assert!(output.status.success());
+        for name in ["server.pid", "server.port", "server.ready"] {
+            assert!(
+                !std::path::Path::new(&path(name)).exists(),
+                "{} was left behind",
+                name
+            );
+        }
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    #[cfg(unix)]
+    fn run_cmd_stops_and_cleans_up_on_sigquit() {
+        let dir = std::env::temp_dir().join(format!("mockerize-quit-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let path = |name: &str| dir.join(name).to_string_lossy().to_string();
+
+        let config = std::fs::read_to_string("tests/data/example.server.json")
+            .unwrap()
+            .replace("\"port\": 8080", "\"port\": 0");
+        std::fs::write(path("server.json"), &config).unwrap();
+
+        let server = Command::new(env!("CARGO_BIN_EXE_mockerize-cli"))
+            .arg("run")
+            .arg(path("server.json"))
+            .args(["--pid-file", &path("server.pid")])
+            .args(["--ready-file", &path("server.ready")])
+            .stdout(std::process::Stdio::null())
+            .stderr(std::process::Stdio::null())
+            .spawn()
+            .expect("Failed to execute process");
+
+        let deadline = std::time::Instant::now() + std::time::Duration::from_secs(10);
+        while !std::path::Path::new(&path("server.ready")).exists() {
+            assert!(
+                std::time::Instant::now() < deadline,
+                "Server never became ready"
+            );
+            std::thread::sleep(std::time::Duration::from_millis(50));
+        }
+
+        let status = Command::new("kill")
+            .args(["-QUIT", &server.id().to_string()])
+            .status()
+            .unwrap();
+        assert!(status.success());
+        let output = server.wait_with_output().unwrap();
+        assert!(output.status.success());
+        for name in ["server.pid", "server.ready"] {
+            assert!(
+                !std::path::Path::new(&path(name)).exists(),
+                "{} was left behind",
+                name
+            );
+        }
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn edit_cmds_change_routes_responses_and_headers() {
+        let path = std::env::temp_dir()
+            .join(format!("mockerize-edit-{}.json", std::process::id()))
+            .to_string_lossy()
+            .to_string();
+        std::fs::copy("tests/data/example.server.json", &path).unwrap();
+
+        let run = |args: &[&str]| {
+            let output = Command::new(env!("CARGO_BIN_EXE_mockerize-cli"))
+                .args(args)
+                .output()
+                .expect("Failed to execute process");
+            assert!(
+                output.status.success(),
+                "{}",
+                String::from_utf8_lossy(&output.stderr)
+            );
+            String::from_utf8_lossy(&output.stdout).to_string()
+        };
+
+        // Adding then removing a header leaves the rest of the file byte-identical
+        let original = std::fs::read_to_string(&path).unwrap();
+        let route = ["--method", "DELETE", "--path", "/api/v1/lists/:item-id"];
+        run(&[&["header", "add", &path, "X-Edited", "yes"][..], &route].concat());
+        assert_ne!(std::fs::read_to_string(&path).unwrap(), original);
+        run(&[&["header", "remove", &path, "X-Edited"][..], &route].concat());
+        assert_eq!(std::fs::read_to_string(&path).unwrap(), original);
+
+        run(&["route", "add", &path, "post", "/users", "--status", "201"]);
+        run(&["response", "add", &path, "POST", "/users", "Conflict"]);
+        run(&[
+            "response",
+            "add",
+            &path,
+            "POST",
+            "/users",
+            "Invalid",
+            "--status",
+            "422",
+            "--body",
+            r#"{"error":"invalid"}"#,
+            "--type",
+            "json",
+            "--activate",
+        ]);
+        run(&["response", "remove", &path, "POST", "/users", "Conflict"]);
+        run(&["header", "add", &path, "X-Server", "mock"]);
+        run(&[
+            "header",
+            "add",
+            &path,
+            "Retry-After",
+            "5",
+            "--method",
+            "POST",
+            "--path",
+            "/users",
+            "--response",
+            "Invalid",
+        ]);
+
+        let listing = run(&["route", "list", &path]);
+        assert!(listing.contains("POST    /users\n    Default (201)\n  * Invalid (422)\n"));
+
+        let serverinfo = mockerize_cli::http::ServerInfo::from_file(&path).unwrap();
+        let route = serverinfo.router.routes.last().unwrap();
+        let active = route.get_active_response().unwrap();
+        assert_eq!(active.get_response_body(), r#"{"error":"invalid"}"#);
+        assert_eq!(active.headers[0].key, "Retry-After");
+        assert!(serverinfo
+            .server
+            .headers
+            .iter()
+            .any(|h| h.key == "X-Server"));
+
+        // The file keeps its four space indentation
+        let written = std::fs::read_to_string(&path).unwrap();
+        assert!(written.starts_with("{\n    \"server\": {\n        \"id\""));
+
+        // Invalid edits are rejected without touching the file
+        let output = Command::new(env!("CARGO_BIN_EXE_mockerize-cli"))
+            .args(["header", "remove", &path, "X-Missing"])
+            .output()
+            .unwrap();
+        assert!(!output.status.success());
+        let output = Command::new(env!("CARGO_BIN_EXE_mockerize-cli"))
+            .args(["route", "add", &path, "GET", "/bad", "--status", "1000"])
+            .output()
+            .unwrap();
+        assert!(String::from_utf8_lossy(&output.stderr).contains("not a valid HTTP status"));
+        assert_eq!(std::fs::read_to_string(&path).unwrap(), written);
+
+        run(&["route", "remove", &path, "POST", "/users"]);
+        assert!(!run(&["route", "list", &path]).contains("/users"));
+
+        std::fs::remove_file(&path).unwrap();
+    }
 }
@@ -1,8 +1,16 @@
+use std::fs;
 use std::net::TcpListener;
 
+use rcgen::{BasicConstraints, CertificateParams, DnType, IsCa, KeyPair};
+
 use mockerize_cli::http::{
-    Header, Method, Response, ResponseType, Route, Router, Server, ServerInfo,
+    ClientAuth, ClientCertMatch, GraphqlConfig, GraphqlMatch, GrpcConfig, GrpcStatus, Header,
+    Method, RequestValidation, Resource, Response, ResponseType, Route, Router, Scenario,
+    SchemaSource, Server, ServerInfo, StateAction, TlsConfig, WebSocketScript,
 };
+use mockerize_cli::mock::{MockResponse, MockServer};
+use mockerize_cli::startup::{Listener, RunOptions};
+use mockerize_cli::tls::EphemeralCa;
 
 pub struct TestApp {
     pub address: String,
@@ -13,17 +21,30 @@ fn make_serverinfo() -> ServerInfo {
     let server = Server::new(router.id, "127.0.0.1", 0).unwrap(); // addr here is disregarded
     router.bind_server(&server);
 
-    ServerInfo { server, router }
+    ServerInfo {
+        server,
+        router,
+        scenarios: vec![],
+    }
 }
 
 // Launch our application in the background
 async fn spawn_app(serverinfo: ServerInfo) -> TestApp {
+    spawn_app_with_options(serverinfo, RunOptions::default()).await
+}
+
+async fn spawn_app_with_options(serverinfo: ServerInfo, options: RunOptions) -> TestApp {
     let listener = TcpListener::bind("127.0.0.1:0").expect("Failed to bind random port");
     let port = listener.local_addr().unwrap().port();
-    let address = format!("http://127.0.0.1:{}", port);
+    let scheme = if options.tls.is_some() || serverinfo.server.tls.is_some() {
+        "https"
+    } else {
+        "http"
+    };
+    let address = format!("{}://localhost:{}", scheme, port);
 
     let server =
-        mockerize_cli::startup::run(serverinfo, listener, None).expect("Failed to bind address");
+        mockerize_cli::startup::run(serverinfo, listener, options).expect("Failed to bind address");
     let _ = tokio::spawn(server);
 
     TestApp { address: address }
@@ -46,6 +67,21 @@ async fn startup_run_instantiates_a_working_http_server() {
     assert_eq!(Some(0), response.content_length());
 }
 
+#[tokio::test]
+async fn server_answers_health_checks() {
+    let app = spawn_app(make_serverinfo()).await;
+    let client = reqwest::Client::new();
+
+    let response = client
+        .get(format!("{}/__mockerize/health", &app.address))
+        .send()
+        .await
+        .expect("Failed to execute request.");
+
+    assert_eq!(response.status().as_u16(), 200);
+    assert_eq!(response.text().await.unwrap(), r#"{"status":"ok"}"#);
+}
+
 #[tokio::test]
 async fn server_is_able_to_serve_a_registered_route() {
     let mut serverinfo = make_serverinfo();
@@ -158,3 +194,1124 @@ async fn server_responses_include_cascaded_headers() {
         "response-level-value"
     );
 }
+
+#[tokio::test]
+async fn stateful_responses_share_a_state_store() {
+    let mut serverinfo = make_serverinfo();
+
+    // POST stores the request body under a key derived from the path param
+    let mut response = Response::new("", 201, ResponseType::Json, "{{body}}");
+    response.templated = true;
+    response.add_state_action(StateAction::Set {
+        key: "items/{{params.id}}".into(),
+        value: "{{body}}".into(),
+    });
+    let mut route = Route::new("/items/:id", Method::POST);
+    route.add_response(response);
+    serverinfo.router.add_route(route);
+
+    // GET reads it back
+    let mut response = Response::new("", 200, ResponseType::Json, "{{state.items/{{params.id}}}}");
+    response.templated = true;
+    let mut route = Route::new("/items/:id", Method::GET);
+    route.add_response(response);
+    serverinfo.router.add_route(route);
+
+    let app = spawn_app(serverinfo).await;
+    let client = reqwest::Client::new();
+
+    let response = client
+        .post(format!("{}/items/1", &app.address))
+        .body(r#"{"name":"Widget"}"#)
+        .send()
+        .await
+        .expect("Failed to execute request.");
+    assert_eq!(response.status().as_u16(), 201);
+
+    let response = client
+        .get(format!("{}/items/1", &app.address))
+        .send()
+        .await
+        .expect("Failed to execute request.");
+    assert_eq!(response.text().await.unwrap(), r#"{"name":"Widget"}"#);
+
+    // State is inspectable and resettable through the admin endpoints
+    let response = client
+        .get(format!("{}/__mockerize/state", &app.address))
+        .send()
+        .await
+        .expect("Failed to execute request.");
+    assert_eq!(
+        response.text().await.unwrap(),
+        r#"{"items/1":{"name":"Widget"}}"#
+    );
+
+    let response = client
+        .delete(format!("{}/__mockerize/state", &app.address))
+        .send()
+        .await
+        .expect("Failed to execute request.");
+    assert_eq!(response.status().as_u16(), 204);
+
+    let response = client
+        .get(format!("{}/items/1", &app.address))
+        .send()
+        .await
+        .expect("Failed to execute request.");
+    assert_eq!(response.text().await.unwrap(), "");
+}
+
+#[tokio::test]
+async fn scenario_state_changes_which_response_is_served() {
+    let mut serverinfo = make_serverinfo();
+    serverinfo.scenarios.push(Scenario::new("order", "created"));
+
+    // GET /order serves a different response per scenario state
+    let created = Response::new("", 200, ResponseType::Text, "created");
+    let created_id = created.id;
+    let mut paid = Response::new("", 200, ResponseType::Text, "paid");
+    paid.scenario.required_state = Some("paid".into());
+
+    let mut route = Route::new("/order", Method::GET);
+    route.scenario.scenario = Some("order".into());
+    route.add_response(paid);
+    route.add_response(created);
+    route.set_active_response(created_id);
+    serverinfo.router.add_route(route);
+
+    // POST /order/pay is only served while the order is awaiting payment
+    let mut route = Route::new("/order/pay", Method::POST);
+    route.scenario.scenario = Some("order".into());
+    route.scenario.required_state = Some("created".into());
+    route.scenario.new_state = Some("paid".into());
+    route.add_response(Response::new("", 204, ResponseType::Text, ""));
+    serverinfo.router.add_route(route);
+
+    let app = spawn_app(serverinfo).await;
+    let client = reqwest::Client::new();
+    let get_order = || async {
+        client
+            .get(format!("{}/order", &app.address))
+            .send()
+            .await
+            .expect("Failed to execute request.")
+            .text()
+            .await
+            .unwrap()
+    };
+    let pay_order = || async {
+        client
+            .post(format!("{}/order/pay", &app.address))
+            .send()
+            .await
+            .expect("Failed to execute request.")
+            .status()
+            .as_u16()
+    };
+
+    assert_eq!(get_order().await, "created");
+    assert_eq!(pay_order().await, 204);
+    assert_eq!(get_order().await, "paid");
+
+    // Paying a second time no longer matches the route
+    assert_eq!(pay_order().await, 404);
+
+    let response = client
+        .get(format!("{}/__mockerize/scenarios", &app.address))
+        .send()
+        .await
+        .expect("Failed to execute request.");
+    assert_eq!(response.text().await.unwrap(), r#"{"order":"paid"}"#);
+
+    let response = client
+        .delete(format!("{}/__mockerize/scenarios", &app.address))
+        .send()
+        .await
+        .expect("Failed to execute request.");
+    assert_eq!(response.status().as_u16(), 204);
+    assert_eq!(get_order().await, "created");
+
+    let response = client
+        .put(format!("{}/__mockerize/scenarios/order", &app.address))
+        .header("Content-Type", "application/json")
+        .body(r#"{"state": "paid"}"#)
+        .send()
+        .await
+        .expect("Failed to execute request.");
+    assert_eq!(response.status().as_u16(), 204);
+    assert_eq!(get_order().await, "paid");
+
+    // Of many concurrent payments, only one is served
+    let response = client
+        .delete(format!("{}/__mockerize/scenarios", &app.address))
+        .send()
+        .await
+        .expect("Failed to execute request.");
+    assert_eq!(response.status().as_u16(), 204);
+    let statuses = futures_util::future::join_all((0..16).map(|_| pay_order())).await;
+    assert_eq!(statuses.iter().filter(|status| **status == 204).count(), 1);
+    assert_eq!(statuses.iter().filter(|status| **status == 404).count(), 15);
+}
+
+#[tokio::test]
+async fn resources_provide_crud_endpoints() {
+    let mut serverinfo = make_serverinfo();
+    let mut resource = Resource::new("users", "/api/users");
+    resource.seed = vec![
+        serde_json::json!({"id": 1, "name": "Alice"}),
+        serde_json::json!({"id": 2, "name": "Bob"}),
+    ];
+    serverinfo.router.add_resource(resource);
+
+    let app = spawn_app(serverinfo).await;
+    let client = reqwest::Client::new();
+    let users = format!("{}/api/users", &app.address);
+
+    let response = client
+        .get(format!("{}?page=2&per_page=1", &users))
+        .send()
+        .await
+        .expect("Failed to execute request.");
+    assert_eq!(response.status().as_u16(), 200);
+    assert_eq!(
+        response.text().await.unwrap(),
+        r#"{"data":[{"id":2,"name":"Bob"}],"page":2,"per_page":1,"total_pages":2,"total":2}"#
+    );
+
+    let response = client
+        .post(&users)
+        .header("Content-Type", "application/json")
+        .body(r#"{"id": 3, "name": "Carol"}"#)
+        .send()
+        .await
+        .expect("Failed to execute request.");
+    assert_eq!(response.status().as_u16(), 201);
+
+    let response = client
+        .put(format!("{}/3", &users))
+        .header("Content-Type", "application/json")
+        .body(r#"{"name": "Caroline"}"#)
+        .send()
+        .await
+        .expect("Failed to execute request.");
+    assert_eq!(response.status().as_u16(), 200);
+
+    let response = client
+        .get(format!("{}/3", &users))
+        .send()
+        .await
+        .expect("Failed to execute request.");
+    assert_eq!(
+        response.text().await.unwrap(),
+        r#"{"id":3,"name":"Caroline"}"#
+    );
+
+    let response = client
+        .delete(format!("{}/1", &users))
+        .send()
+        .await
+        .expect("Failed to execute request.");
+    assert_eq!(response.status().as_u16(), 204);
+
+    let response = client
+        .get(format!("{}/1", &users))
+        .send()
+        .await
+        .expect("Failed to execute request.");
+    assert_eq!(response.status().as_u16(), 404);
+
+    // Resetting restores the seed data
+    let response = client
+        .delete(format!("{}/__mockerize/resources/users", &app.address))
+        .send()
+        .await
+        .expect("Failed to execute request.");
+    assert_eq!(response.status().as_u16(), 204);
+
+    let response = client
+        .get(format!("{}/1", &users))
+        .send()
+        .await
+        .expect("Failed to execute request.");
+    assert_eq!(response.status().as_u16(), 200);
+    let response = client
+        .get(format!("{}/3", &users))
+        .send()
+        .await
+        .expect("Failed to execute request.");
+    assert_eq!(response.status().as_u16(), 404);
+}
+
+#[tokio::test]
+async fn requests_failing_validation_are_rejected() {
+    let mut serverinfo = make_serverinfo();
+
+    let mut route = Route::new("/users", Method::POST);
+    route.add_response(Response::new("", 201, ResponseType::Json, "{}"));
+    route.validation = Some(RequestValidation {
+        body: Some(SchemaSource::Inline(serde_json::json!({
+            "type": "object",
+            "required": ["name"]
+        }))),
+        failure_status: 422,
+        ..Default::default()
+    });
+    serverinfo.router.add_route(route);
+
+    let app = spawn_app(serverinfo).await;
+    let client = reqwest::Client::new();
+
+    let response = client
+        .post(format!("{}/users", &app.address))
+        .body(r#"{"name": "Alice"}"#)
+        .send()
+        .await
+        .expect("Failed to execute request.");
+    assert_eq!(response.status().as_u16(), 201);
+
+    let response = client
+        .post(format!("{}/users", &app.address))
+        .body(r#"{"email": "alice@example.com"}"#)
+        .send()
+        .await
+        .expect("Failed to execute request.");
+    assert_eq!(response.status().as_u16(), 422);
+
+    let body: serde_json::Value = serde_json::from_str(&response.text().await.unwrap()).unwrap();
+    let errors = body["errors"].as_array().unwrap();
+    assert_eq!(errors.len(), 1);
+    assert_eq!(errors[0]["location"], "body");
+    assert!(errors[0]["message"].as_str().unwrap().contains("name"));
+}
+
+#[tokio::test]
+async fn graphql_routes_match_operations_and_mock_from_schema() {
+    let mut serverinfo = make_serverinfo();
+
+    let mut response = Response::new(
+        "",
+        200,
+        ResponseType::Json,
+        r#"{"data": {"user": {"name": "Alice"}}}"#,
+    );
+    response.graphql = Some(GraphqlMatch {
+        operation_name: Some("GetUser".into()),
+        variables: Some(serde_json::json!({ "id": "1" })),
+    });
+    let mut route = Route::new("/graphql", Method::POST);
+    route.graphql = Some(GraphqlConfig {
+        schema: Some("tests/data/users.graphql".into()),
+    });
+    route.add_response(response);
+    serverinfo.router.add_route(route);
+
+    let app = spawn_app(serverinfo).await;
+    let client = reqwest::Client::new();
+    let query = "query GetUser($id: ID!) { user(id: $id) { name age } }";
+
+    let response = client
+        .post(format!("{}/graphql", &app.address))
+        .body(serde_json::json!({ "query": query, "variables": { "id": "1" } }).to_string())
+        .send()
+        .await
+        .expect("Failed to execute request.");
+    assert_eq!(response.status().as_u16(), 200);
+    let body: serde_json::Value = serde_json::from_str(&response.text().await.unwrap()).unwrap();
+    assert_eq!(body["data"]["user"]["name"], "Alice");
+
+    // No response matches, so data is generated from the schema
+    let response = client
+        .post(format!("{}/graphql", &app.address))
+        .body(serde_json::json!({ "query": query, "variables": { "id": "2" } }).to_string())
+        .send()
+        .await
+        .expect("Failed to execute request.");
+    assert_eq!(response.status().as_u16(), 200);
+    assert_eq!(
+        response.headers()["content-type"].to_str().unwrap(),
+        "application/json"
+    );
+    let body: serde_json::Value = serde_json::from_str(&response.text().await.unwrap()).unwrap();
+    assert_eq!(body["data"]["user"]["name"], "Hello World");
+    assert_eq!(body["data"]["user"]["age"], 42);
+
+    let response = client
+        .post(format!("{}/graphql", &app.address))
+        .body(serde_json::json!({ "query": "{ user(id: 1) { email } }" }).to_string())
+        .send()
+        .await
+        .expect("Failed to execute request.");
+    assert_eq!(response.status().as_u16(), 400);
+    let body: serde_json::Value = serde_json::from_str(&response.text().await.unwrap()).unwrap();
+    assert!(body["errors"][0]["message"]
+        .as_str()
+        .unwrap()
+        .contains("email"));
+}
+
+#[cfg(unix)]
+#[tokio::test]
+async fn server_can_listen_on_a_unix_socket() {
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+    let mut serverinfo = make_serverinfo();
+    let mut route = Route::new("/sidecar", Method::GET);
+    route.add_response(Response::new("", 200, ResponseType::Text, "over a socket"));
+    serverinfo.router.add_route(route);
+
+    let path = std::env::temp_dir().join(format!("mockerize-{}.sock", uuid::Uuid::new_v4()));
+    let listener = std::os::unix::net::UnixListener::bind(&path).unwrap();
+    let server = mockerize_cli::startup::run(serverinfo, listener, RunOptions::default())
+        .expect("Failed to bind socket");
+    tokio::spawn(server);
+
+    let mut stream = tokio::net::UnixStream::connect(&path).await.unwrap();
+    stream
+        .write_all(b"GET /sidecar HTTP/1.1\r\nHost: localhost\r\nConnection: close\r\n\r\n")
+        .await
+        .unwrap();
+    let mut response = String::new();
+    stream.read_to_string(&mut response).await.unwrap();
+
+    assert!(response.starts_with("HTTP/1.1 200 OK"));
+    assert!(response.ends_with("over a socket"));
+    fs::remove_file(&path).unwrap();
+}
+
+#[tokio::test]
+async fn server_can_serve_https_with_a_self_signed_certificate() {
+    let mut serverinfo = make_serverinfo();
+    let mut route = Route::new("/secure", Method::GET);
+    route.add_response(Response::new("", 200, ResponseType::Text, "Secure"));
+    serverinfo.router.add_route(route);
+
+    let ca = EphemeralCa::generate().unwrap();
+    let options = RunOptions {
+        tls: Some(ca.server_config(&["localhost".to_string()], None).unwrap()),
+        ..Default::default()
+    };
+    let app = spawn_app_with_options(serverinfo, options).await;
+
+    let ca = reqwest::Certificate::from_pem(ca.cert_pem().as_bytes()).unwrap();
+    let client = reqwest::Client::builder()
+        .add_root_certificate(ca)
+        .build()
+        .unwrap();
+
+    let response = client
+        .get(format!("{}/secure", &app.address))
+        .send()
+        .await
+        .expect("Failed to execute request.");
+    assert_eq!(response.text().await.unwrap(), "Secure");
+
+    // Plain HTTP is not accepted
+    let plain_address = app.address.replace("https://", "http://");
+    let response = reqwest::Client::new()
+        .get(format!("{}/secure", plain_address))
+        .send()
+        .await;
+    assert!(response.is_err());
+}
+
+#[tokio::test]
+async fn server_can_serve_http_and_https_listeners_together() {
+    let mut serverinfo = make_serverinfo();
+    let mut route = Route::new("/both", Method::GET);
+    route.add_response(Response::new("", 200, ResponseType::Text, "Either way"));
+    serverinfo.router.add_route(route);
+
+    let https = TcpListener::bind("127.0.0.1:0").unwrap();
+    let http = TcpListener::bind("127.0.0.1:0").unwrap();
+    let https_address = format!("https://localhost:{}", https.local_addr().unwrap().port());
+    let http_address = format!("http://localhost:{}", http.local_addr().unwrap().port());
+
+    let ca = EphemeralCa::generate().unwrap();
+    let options = RunOptions {
+        tls: Some(ca.server_config(&["localhost".to_string()], None).unwrap()),
+        ..Default::default()
+    };
+    let listeners = vec![Listener::Tcp(https), Listener::Cleartext(http)];
+    let server = mockerize_cli::startup::run_listeners(serverinfo, listeners, options)
+        .expect("Failed to bind address");
+    tokio::spawn(server);
+
+    let ca = reqwest::Certificate::from_pem(ca.cert_pem().as_bytes()).unwrap();
+    let client = reqwest::Client::builder()
+        .add_root_certificate(ca)
+        .build()
+        .unwrap();
+
+    for address in [https_address, http_address] {
+        let response = client
+            .get(format!("{}/both", address))
+            .send()
+            .await
+            .expect("Failed to execute request.");
+        assert_eq!(response.text().await.unwrap(), "Either way");
+    }
+}
+
+#[tokio::test]
+async fn cleartext_listeners_do_not_require_client_certificates() {
+    let dir = std::env::temp_dir().join(format!("mockerize-mtls-{}", uuid::Uuid::new_v4()));
+    fs::create_dir_all(&dir).unwrap();
+    let path = |name: &str| dir.join(name).to_string_lossy().to_string();
+
+    let ca_key = KeyPair::generate().unwrap();
+    let mut ca_params = CertificateParams::new(Vec::<String>::new()).unwrap();
+    ca_params.is_ca = IsCa::Ca(BasicConstraints::Unconstrained);
+    ca_params
+        .distinguished_name
+        .push(DnType::CommonName, "Test CA");
+    let ca = ca_params.self_signed(&ca_key).unwrap();
+
+    let server_key = KeyPair::generate().unwrap();
+    let mut server_params = CertificateParams::new(vec!["localhost".to_string()]).unwrap();
+    server_params
+        .distinguished_name
+        .push(DnType::CommonName, "localhost");
+    let server_cert = server_params.signed_by(&server_key, &ca, &ca_key).unwrap();
+
+    fs::write(path("ca.pem"), ca.pem()).unwrap();
+    fs::write(path("server.pem"), server_cert.pem()).unwrap();
+    fs::write(path("server.key"), server_key.serialize_pem()).unwrap();
+
+    let mut serverinfo = make_serverinfo();
+    let mut client_auth = ClientAuth::new(&path("ca.pem"));
+    client_auth.rejection.status = 403;
+    serverinfo.server.tls = Some(TlsConfig {
+        client_auth: Some(client_auth),
+        ..TlsConfig::new(&path("server.pem"), &path("server.key"))
+    });
+    let mut route = Route::new("/internal", Method::GET);
+    route.add_response(Response::new("", 200, ResponseType::Text, "Internal"));
+    serverinfo.router.add_route(route);
+
+    let https = TcpListener::bind("127.0.0.1:0").unwrap();
+    let http = TcpListener::bind("127.0.0.1:0").unwrap();
+    let https_address = format!("https://localhost:{}", https.local_addr().unwrap().port());
+    let http_address = format!("http://localhost:{}", http.local_addr().unwrap().port());
+
+    let listeners = vec![Listener::Tcp(https), Listener::Cleartext(http)];
+    let server =
+        mockerize_cli::startup::run_listeners(serverinfo, listeners, RunOptions::default())
+            .expect("Failed to bind address");
+    tokio::spawn(server);
+
+    let response = reqwest::Client::new()
+        .get(format!("{}/internal", http_address))
+        .send()
+        .await
+        .expect("Failed to execute request.");
+    assert_eq!(response.status().as_u16(), 200);
+
+    // Over TLS, the certificate is still required
+    let client = reqwest::Client::builder()
+        .add_root_certificate(reqwest::Certificate::from_pem(ca.pem().as_bytes()).unwrap())
+        .build()
+        .unwrap();
+    let response = client
+        .get(format!("{}/internal", https_address))
+        .send()
+        .await
+        .expect("Failed to execute request.");
+    assert_eq!(response.status().as_u16(), 403);
+
+    fs::remove_dir_all(&dir).unwrap();
+}
+
+#[tokio::test]
+async fn virtual_hosts_are_dispatched_by_host_header_and_sni() {
+    let dir = std::env::temp_dir().join(format!("mockerize-vhosts-{}", uuid::Uuid::new_v4()));
+    fs::create_dir_all(&dir).unwrap();
+    let path = |name: &str| dir.join(name).to_string_lossy().to_string();
+
+    let ca_key = KeyPair::generate().unwrap();
+    let mut ca_params = CertificateParams::new(Vec::<String>::new()).unwrap();
+    ca_params.is_ca = IsCa::Ca(BasicConstraints::Unconstrained);
+    ca_params
+        .distinguished_name
+        .push(DnType::CommonName, "Test CA");
+    let ca = ca_params.self_signed(&ca_key).unwrap();
+
+    // Each server has its own certificate, only valid for its own hostname
+    let mut servers = vec![];
+    for (host, body) in [("api.example.com", "API"), ("localhost", "Default")] {
+        let key = KeyPair::generate().unwrap();
+        let mut params = CertificateParams::new(vec![host.to_string()]).unwrap();
+        params.distinguished_name.push(DnType::CommonName, host);
+        let cert = params.signed_by(&key, &ca, &ca_key).unwrap();
+        fs::write(path(&format!("{}.pem", host)), cert.pem()).unwrap();
+        fs::write(path(&format!("{}.key", host)), key.serialize_pem()).unwrap();
+
+        let mut serverinfo = make_serverinfo();
+        serverinfo.server.tls = Some(TlsConfig::new(
+            &path(&format!("{}.pem", host)),
+            &path(&format!("{}.key", host)),
+        ));
+        if host != "localhost" {
+            serverinfo.server.hosts = vec![host.to_string()];
+        }
+        let mut route = Route::new("/whoami", Method::GET);
+        route.add_response(Response::new("", 200, ResponseType::Text, body));
+        serverinfo.router.add_route(route);
+        servers.push(serverinfo);
+    }
+
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let addr = listener.local_addr().unwrap();
+    let server = mockerize_cli::startup::run_virtual_hosts(
+        servers,
+        vec![Listener::Tcp(listener)],
+        RunOptions::default(),
+    )
+    .expect("Failed to bind address");
+    tokio::spawn(server);
+
+    let client = reqwest::Client::builder()
+        .add_root_certificate(reqwest::Certificate::from_pem(ca.pem().as_bytes()).unwrap())
+        .resolve("api.example.com", addr)
+        .build()
+        .unwrap();
+
+    for (host, expected) in [("api.example.com", "API"), ("localhost", "Default")] {
+        let response = client
+            .get(format!("https://{}:{}/whoami", host, addr.port()))
+            .send()
+            .await
+            .expect("Failed to execute request.");
+        assert_eq!(response.text().await.unwrap(), expected);
+    }
+
+    // Unknown hosts are served by the default server
+    let response = client
+        .get(format!("https://localhost:{}/whoami", addr.port()))
+        .header("Host", "other.example.com")
+        .send()
+        .await
+        .expect("Failed to execute request.");
+    assert_eq!(response.text().await.unwrap(), "Default");
+
+    fs::remove_dir_all(&dir).unwrap();
+}
+
+#[tokio::test]
+async fn server_selects_responses_by_client_certificate() {
+    let dir = std::env::temp_dir().join(format!("mockerize-mtls-{}", uuid::Uuid::new_v4()));
+    fs::create_dir_all(&dir).unwrap();
+
+    // A single CA issues both the server and client certificates
+    let ca_key = KeyPair::generate().unwrap();
+    let mut ca_params = CertificateParams::new(Vec::<String>::new()).unwrap();
+    ca_params.is_ca = IsCa::Ca(BasicConstraints::Unconstrained);
+    ca_params
+        .distinguished_name
+        .push(DnType::CommonName, "Test CA");
+    let ca = ca_params.self_signed(&ca_key).unwrap();
+
+    let server_key = KeyPair::generate().unwrap();
+    let mut server_params = CertificateParams::new(vec!["localhost".to_string()]).unwrap();
+    server_params
+        .distinguished_name
+        .push(DnType::CommonName, "localhost");
+    let server_cert = server_params.signed_by(&server_key, &ca, &ca_key).unwrap();
+
+    let client_key = KeyPair::generate().unwrap();
+    let mut client_params = CertificateParams::new(Vec::<String>::new()).unwrap();
+    client_params
+        .distinguished_name
+        .push(DnType::CommonName, "tenant-a");
+    let client_cert = client_params.signed_by(&client_key, &ca, &ca_key).unwrap();
+
+    let path = |name: &str| dir.join(name).to_string_lossy().to_string();
+    fs::write(path("ca.pem"), ca.pem()).unwrap();
+    fs::write(path("server.pem"), server_cert.pem()).unwrap();
+    fs::write(path("server.key"), server_key.serialize_pem()).unwrap();
+
+    let mut serverinfo = make_serverinfo();
+    let mut client_auth = ClientAuth::new(&path("ca.pem"));
+    client_auth.rejection.status = 403;
+    client_auth.rejection.response = "Certificate required".to_string();
+    serverinfo.server.tls = Some(TlsConfig {
+        client_auth: Some(client_auth),
+        ..TlsConfig::new(&path("server.pem"), &path("server.key"))
+    });
+
+    let mut tenant_a = Response::new("", 200, ResponseType::Text, "Hello, tenant A");
+    tenant_a.client_cert = Some(ClientCertMatch {
+        subject: Some("tenant-a".to_string()),
+        san: None,
+    });
+    let mut route = Route::new("/whoami", Method::GET);
+    route.add_response(tenant_a);
+    route.add_response(Response::new(
+        "",
+        200,
+        ResponseType::Text,
+        "Hello, stranger",
+    ));
+    serverinfo.router.add_route(route);
+
+    let app = spawn_app(serverinfo).await;
+    let ca = reqwest::Certificate::from_pem(ca.pem().as_bytes()).unwrap();
+
+    let identity = reqwest::Identity::from_pkcs8_pem(
+        client_cert.pem().as_bytes(),
+        client_key.serialize_pem().as_bytes(),
+    )
+    .unwrap();
+    let client = reqwest::Client::builder()
+        .add_root_certificate(ca.clone())
+        .identity(identity)
+        .build()
+        .unwrap();
+    let response = client
+        .get(format!("{}/whoami", &app.address))
+        .send()
+        .await
+        .expect("Failed to execute request.");
+    assert_eq!(response.text().await.unwrap(), "Hello, tenant A");
+
+    // Clients without a certificate are served the configured rejection
+    let client = reqwest::Client::builder()
+        .add_root_certificate(ca)
+        .build()
+        .unwrap();
+    let response = client
+        .get(format!("{}/whoami", &app.address))
+        .send()
+        .await
+        .expect("Failed to execute request.");
+    assert_eq!(response.status().as_u16(), 403);
+    assert_eq!(response.text().await.unwrap(), "Certificate required");
+
+    fs::remove_dir_all(&dir).unwrap();
+}
+
+#[tokio::test]
+async fn server_accepts_h2c_with_prior_knowledge() {
+    let mut serverinfo = make_serverinfo();
+    let mut route = Route::new("/h2", Method::GET);
+    route.add_response(Response::new("", 200, ResponseType::Text, "Hello, h2"));
+    serverinfo.router.add_route(route);
+    let app = spawn_app(serverinfo).await;
+
+    let client = reqwest::Client::builder()
+        .http2_prior_knowledge()
+        .build()
+        .unwrap();
+    let response = client
+        .get(format!("{}/h2", &app.address))
+        .send()
+        .await
+        .expect("Failed to execute request.");
+    assert_eq!(response.version(), reqwest::Version::HTTP_2);
+    assert_eq!(response.text().await.unwrap(), "Hello, h2");
+
+    // HTTP/1.1 is still served on the same listener
+    let response = reqwest::get(format!("{}/h2", &app.address)).await.unwrap();
+    assert_eq!(response.version(), reqwest::Version::HTTP_11);
+}
+
+#[tokio::test]
+async fn server_can_disable_http2() {
+    let mut serverinfo = make_serverinfo();
+    serverinfo.server.http2 = false;
+    let mut route = Route::new("/h1", Method::GET);
+    route.add_response(Response::new("", 200, ResponseType::Text, "Hello, h1"));
+    serverinfo.router.add_route(route);
+    let app = spawn_app(serverinfo).await;
+
+    let client = reqwest::Client::builder()
+        .http2_prior_knowledge()
+        .build()
+        .unwrap();
+    let response = client.get(format!("{}/h1", &app.address)).send().await;
+    assert!(response.is_err());
+}
+
+// The protocol a TLS server on `port` picks via ALPN, when offered both HTTP/2 and HTTP/1.1
+fn negotiated_alpn(port: u16, ca_cert_pem: &str) -> Option<Vec<u8>> {
+    use rustls::pki_types::pem::PemObject;
+    use rustls::pki_types::{CertificateDer, ServerName};
+    use std::sync::Arc;
+
+    let mut roots = rustls::RootCertStore::empty();
+    roots
+        .add(CertificateDer::from_pem_slice(ca_cert_pem.as_bytes()).unwrap())
+        .unwrap();
+    let mut config = rustls::ClientConfig::builder()
+        .with_root_certificates(roots)
+        .with_no_client_auth();
+    config.alpn_protocols = vec![b"h2".to_vec(), b"http/1.1".to_vec()];
+
+    let name = ServerName::try_from("localhost").unwrap();
+    let mut conn = rustls::ClientConnection::new(Arc::new(config), name).unwrap();
+    let mut socket = std::net::TcpStream::connect(("127.0.0.1", port)).unwrap();
+    while conn.is_handshaking() {
+        conn.complete_io(&mut socket).unwrap();
+    }
+    conn.alpn_protocol().map(<[u8]>::to_vec)
+}
+
+#[tokio::test]
+async fn server_only_offers_http2_over_tls_if_enabled() {
+    for http2 in [true, false] {
+        let mut serverinfo = make_serverinfo();
+        serverinfo.server.http2 = http2;
+
+        let ca = EphemeralCa::generate().unwrap();
+        let options = RunOptions {
+            tls: Some(ca.server_config(&["localhost".to_string()], None).unwrap()),
+            ..Default::default()
+        };
+        let app = spawn_app_with_options(serverinfo, options).await;
+        let port = app.address.rsplit(':').next().unwrap().parse().unwrap();
+
+        let ca_cert_pem = ca.cert_pem();
+        let protocol = tokio::task::spawn_blocking(move || negotiated_alpn(port, &ca_cert_pem))
+            .await
+            .unwrap();
+        let expected: &[u8] = if http2 { b"h2" } else { b"http/1.1" };
+        assert_eq!(protocol.as_deref(), Some(expected));
+
+        // HTTP/1.1 is served either way
+        let ca = reqwest::Certificate::from_pem(ca.cert_pem().as_bytes()).unwrap();
+        let client = reqwest::Client::builder()
+            .add_root_certificate(ca)
+            .build()
+            .unwrap();
+        let response = client
+            .get(format!("{}/__mockerize/health", &app.address))
+            .send()
+            .await
+            .expect("Failed to execute request.");
+        assert!(response.status().is_success());
+    }
+}
+
+#[tokio::test]
+async fn server_follows_websocket_scripts() {
+    use futures_util::{SinkExt, StreamExt};
+    use tokio_tungstenite::tungstenite::Message;
+
+    let mut serverinfo = make_serverinfo();
+    let script: WebSocketScript = serde_json::from_str(
+        r#"{
+            "onConnect": [{ "text": "welcome" }],
+            "replies": [
+                { "match": { "text": "ping" }, "messages": [{ "text": "pong" }] },
+                {
+                    "match": { "json": { "type": "bye" } },
+                    "messages": [{ "json": { "type": "goodbye" } }],
+                    "close": { "code": 4001, "reason": "finished" }
+                }
+            ],
+            "pushes": [{ "intervalMs": 50, "message": { "text": "tick" }, "count": 1 }]
+        }"#,
+    )
+    .unwrap();
+    let mut route = Route::new("/live", Method::GET);
+    route.websocket = Some(script);
+    serverinfo.router.add_route(route);
+
+    // HTTP routes are served alongside WebSocket routes
+    let mut route = Route::new("/hello", Method::GET);
+    route.add_response(Response::new("", 200, ResponseType::Text, "Hello"));
+    serverinfo.router.add_route(route);
+
+    let app = spawn_app(serverinfo).await;
+    let response = reqwest::get(format!("{}/hello", &app.address))
+        .await
+        .unwrap();
+    assert_eq!(response.text().await.unwrap(), "Hello");
+
+    let url = format!("{}/live", app.address.replace("http://", "ws://"));
+    let (mut socket, _) = tokio_tungstenite::connect_async(url).await.unwrap();
+
+    assert_eq!(
+        socket.next().await.unwrap().unwrap(),
+        Message::text("welcome")
+    );
+    assert_eq!(socket.next().await.unwrap().unwrap(), Message::text("tick"));
+
+    socket.send(Message::text("ping")).await.unwrap();
+    assert_eq!(socket.next().await.unwrap().unwrap(), Message::text("pong"));
+
+    socket
+        .send(Message::text(r#"{"type": "bye", "id": 1}"#))
+        .await
+        .unwrap();
+    assert_eq!(
+        socket.next().await.unwrap().unwrap(),
+        Message::text(r#"{"type":"goodbye"}"#)
+    );
+    match socket.next().await.unwrap().unwrap() {
+        Message::Close(Some(frame)) => {
+            assert_eq!(u16::from(frame.code), 4001);
+            assert_eq!(frame.reason, "finished");
+        }
+        other => panic!("Expected a close frame, got {:?}", other),
+    }
+}
+
+#[tokio::test]
+async fn server_streams_sse_responses() {
+    let mut serverinfo = make_serverinfo();
+    let events = r#"[
+        { "event": "greeting", "data": "hello", "id": "1" },
+        { "data": { "count": 2 }, "id": "2", "delayMs": 10 },
+        { "data": "tick", "delayMs": 10, "repeat": 2 }
+    ]"#;
+    let mut route = Route::new("/events", Method::GET);
+    route.add_response(Response::new("", 200, ResponseType::Sse, events));
+    serverinfo.router.add_route(route);
+    let app = spawn_app(serverinfo).await;
+    let client = reqwest::Client::new();
+
+    let response = client
+        .get(format!("{}/events", &app.address))
+        .send()
+        .await
+        .expect("Failed to execute request.");
+    assert_eq!(
+        response.headers()["content-type"].to_str().unwrap(),
+        "text/event-stream"
+    );
+    assert_eq!(
+        response.text().await.unwrap(),
+        "event: greeting\nid: 1\ndata: hello\n\nid: 2\ndata: {\"count\":2}\n\ndata: tick\n\ndata: tick\n\n"
+    );
+
+    // Reconnecting clients resume after the last event they saw
+    let response = client
+        .get(format!("{}/events", &app.address))
+        .header("Last-Event-ID", "2")
+        .send()
+        .await
+        .expect("Failed to execute request.");
+    assert_eq!(
+        response.text().await.unwrap(),
+        "data: tick\n\ndata: tick\n\n"
+    );
+}
+
+#[tokio::test]
+async fn server_streams_chunked_bodies() {
+    let mut serverinfo = make_serverinfo();
+    let mut response = Response::new("", 200, ResponseType::Json, "");
+    response.stream = serde_json::from_str(
+        r#"{ "records": { "count": 3, "template": "{\"id\": {{index}}}", "delayMs": 5 } }"#,
+    )
+    .unwrap();
+    response.add_header(Header::new("Content-Type", "application/x-ndjson"));
+    let mut route = Route::new("/records", Method::GET);
+    route.add_response(response);
+    serverinfo.router.add_route(route);
+    let app = spawn_app(serverinfo).await;
+
+    let response = reqwest::get(format!("{}/records", &app.address))
+        .await
+        .expect("Failed to execute request.");
+    assert_eq!(
+        response.headers()["transfer-encoding"].to_str().unwrap(),
+        "chunked"
+    );
+    assert_eq!(
+        response.text().await.unwrap(),
+        "{\"id\": 0}\n{\"id\": 1}\n{\"id\": 2}\n"
+    );
+}
+
+// Send a single length-prefixed gRPC message, returning the response body and trailers
+async fn call_grpc(address: &str, method: &str, message: &[u8]) -> (Vec<u8>, hyper::HeaderMap) {
+    use http_body_util::{BodyExt, Full};
+    use hyper_util::rt::{TokioExecutor, TokioIo};
+
+    let stream = tokio::net::TcpStream::connect(address).await.unwrap();
+    let (mut sender, connection) =
+        hyper::client::conn::http2::handshake(TokioExecutor::new(), TokioIo::new(stream))
+            .await
+            .unwrap();
+    tokio::spawn(connection);
+
+    let mut body = vec![0];
+    body.extend_from_slice(&(message.len() as u32).to_be_bytes());
+    body.extend_from_slice(message);
+    let request = hyper::Request::post(format!("http://{}{}", address, method))
+        .header("content-type", "application/grpc")
+        .header("te", "trailers")
+        .body(Full::new(hyper::body::Bytes::from(body)))
+        .unwrap();
+
+    let response = sender.send_request(request).await.unwrap();
+    let collected = response.into_body().collect().await.unwrap();
+    let trailers = collected.trailers().cloned().unwrap_or_default();
+    (collected.to_bytes().to_vec(), trailers)
+}
+
+#[tokio::test]
+async fn grpc_server_serves_methods_from_proto() {
+    let mut serverinfo = make_serverinfo();
+    serverinfo.server.grpc = Some(GrpcConfig::new("tests/data/greeter.proto"));
+
+    let mut response = Response::new(
+        "",
+        200,
+        ResponseType::Json,
+        r#"{"message": "Hello, {{body.name}}"}"#,
+    );
+    response.templated = true;
+    let mut route = Route::new("helloworld.Greeter/SayHello", Method::POST);
+    route.add_response(response);
+    serverinfo.router.add_route(route);
+
+    let mut response = Response::new("", 200, ResponseType::Json, "");
+    response.grpc = Some(GrpcStatus {
+        code: 5,
+        message: "Nobody here".to_string(),
+        trailers: vec![Header::new("x-cost", "3")],
+    });
+    let mut route = Route::new("/helloworld.Greeter/SayHellos", Method::POST);
+    route.add_response(response);
+    serverinfo.router.add_route(route);
+
+    let listener = TcpListener::bind("127.0.0.1:0").expect("Failed to bind random port");
+    let address = listener.local_addr().unwrap().to_string();
+    let server = mockerize_cli::grpc::run(serverinfo, listener).expect("Failed to bind address");
+    tokio::spawn(server);
+
+    // HelloRequest { name: "World" }
+    let request = [&[0x0a, 5][..], b"World"].concat();
+    let (body, trailers) = call_grpc(&address, "/helloworld.Greeter/SayHello", &request).await;
+    assert_eq!(trailers["grpc-status"], "0");
+    // A single HelloReply { message: "Hello, World" }
+    let reply = [&[0x0a, 12][..], b"Hello, World"].concat();
+    assert_eq!(body[5..], reply[..]);
+
+    let (body, trailers) = call_grpc(&address, "/helloworld.Greeter/SayHellos", &request).await;
+    assert!(body.is_empty());
+    assert_eq!(trailers["grpc-status"], "5");
+    assert_eq!(trailers["grpc-message"], "Nobody here");
+    assert_eq!(trailers["x-cost"], "3");
+
+    let (_, trailers) = call_grpc(&address, "/helloworld.Greeter/Unknown", &request).await;
+    assert_eq!(trailers["grpc-status"], "12");
+}
+
+#[tokio::test]
+async fn mock_server_serves_routes_and_records_requests() {
+    let server = MockServer::builder()
+        .header("X-Mock", "yes")
+        .route(
+            Method::GET,
+            "/users/:id",
+            MockResponse::new(200).json(&serde_json::json!({ "id": 1 })),
+        )
+        .route(
+            Method::POST,
+            "/users",
+            MockResponse::new(201).body("Created"),
+        )
+        .start()
+        .await
+        .expect("Failed to start mock server");
+    let client = reqwest::Client::new();
+
+    let response = client.get(server.url("/users/1")).send().await.unwrap();
+    assert_eq!(response.status().as_u16(), 200);
+    assert_eq!(response.headers()["content-type"], "application/json");
+    assert_eq!(response.headers()["x-mock"], "yes");
+    assert_eq!(response.text().await.unwrap(), r#"{"id":1}"#);
+
+    let response = client
+        .post(format!("{}/users?invite=true", server.uri()))
+        .body(r#"{"name":"Ada"}"#)
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.text().await.unwrap(), "Created");
+
+    // Large bodies are served and recorded in full
+    let large = "x".repeat(1024 * 1024);
+    let response = client
+        .post(server.url("/users"))
+        .body(large.clone())
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.text().await.unwrap(), "Created");
+    assert_eq!(server.requests_to(Method::POST, "/users")[1].body, large);
+
+    // Health checks are not recorded, but requests for unknown routes are
+    client
+        .get(server.url("/__mockerize/health"))
+        .send()
+        .await
+        .unwrap();
+    client.get(server.url("/missing")).send().await.unwrap();
+
+    server.verify(Method::GET, "/users/:id", 1).unwrap();
+    server.verify(Method::GET, "/missing", 1).unwrap();
+    let created = server.requests_to(Method::POST, "/users");
+    assert_eq!(created.len(), 2);
+    assert_eq!(created[0].query["invite"], "true");
+    assert_eq!(created[0].version, "HTTP/1.1");
+    assert_eq!(created[0].json().unwrap()["name"], "Ada");
+    assert_eq!(server.received_requests().len(), 4);
+
+    let error = server.verify(Method::DELETE, "/users/:id", 1).unwrap_err();
+    assert!(error.to_string().contains("GET /users/1"), "{}", error);
+
+    server.reset();
+    server.verify(Method::GET, "/users/:id", 0).unwrap();
+
+    // Dropping the server shuts it down
+    let uri = server.uri();
+    drop(server);
+    tokio::time::sleep(std::time::Duration::from_millis(500)).await;
+    assert!(client.get(uri).send().await.is_err());
+}
+
+#[tokio::test]
+async fn mock_server_serves_https_for_tls_configs_and_rejects_grpc() {
+    let dir = std::env::temp_dir().join(format!("mockerize-mock-tls-{}", uuid::Uuid::new_v4()));
+    fs::create_dir_all(&dir).unwrap();
+    let path = |name: &str| dir.join(name).to_string_lossy().to_string();
+
+    let key = KeyPair::generate().unwrap();
+    let cert = CertificateParams::new(vec!["localhost".to_string()])
+        .unwrap()
+        .self_signed(&key)
+        .unwrap();
+    fs::write(path("server.pem"), cert.pem()).unwrap();
+    fs::write(path("server.key"), key.serialize_pem()).unwrap();
+
+    let mut serverinfo = make_serverinfo();
+    serverinfo.server.tls = Some(TlsConfig::new(&path("server.pem"), &path("server.key")));
+    let server = MockServer::builder()
+        .config(serverinfo)
+        .route(Method::GET, "/ping", MockResponse::new(200).body("pong"))
+        .start()
+        .await
+        .expect("Failed to start mock server");
+    assert!(server.uri().starts_with("https://"), "{}", server.uri());
+
+    let client = reqwest::Client::builder()
+        .danger_accept_invalid_certs(true)
+        .build()
+        .unwrap();
+    let response = client.get(server.url("/ping")).send().await.unwrap();
+    assert_eq!(response.text().await.unwrap(), "pong");
+
+    let mut serverinfo = make_serverinfo();
+    serverinfo.server.grpc = Some(GrpcConfig::new("tests/data/greeter.proto"));
+    assert!(MockServer::builder()
+        .config(serverinfo)
+        .start()
+        .await
+        .is_err());
+
+    fs::remove_dir_all(&dir).unwrap();
+}